@@ -0,0 +1,241 @@
+use crate::auth;
+use crate::escrow;
+use crate::rental_core::{nft_minter, payments};
+use crate::storage::{config_store, moderation_store, property_store, rental_store, user_store};
+use crate::types::{
+    AanganError, EscrowStatus, ModerationAction, ModerationEntry, NFTMetadata, Property,
+    RentalAgreement, RentalStatus, User,
+};
+use candid::Principal;
+use ic_cdk_macros::*;
+
+/// Longest reason accepted for a moderation action.
+pub const MAX_REASON_LENGTH: usize = 500;
+
+/// Upper bound on the number of log entries returned per call.
+pub const MAX_LOG_PAGE_SIZE: u32 = 100;
+
+fn validate_reason(reason: &str) -> Result<(), AanganError> {
+    if reason.trim().is_empty() {
+        return Err(AanganError::validation("reason", "must not be empty"));
+    }
+    if reason.chars().count() > MAX_REASON_LENGTH {
+        return Err(AanganError::validation(
+            "reason",
+            format!("must be at most {} characters", MAX_REASON_LENGTH),
+        ));
+    }
+    Ok(())
+}
+
+#[update]
+pub fn suspend_user(user: Principal, reason: String) -> Result<User, AanganError> {
+    let admin = auth::require_admin()?;
+    validate_reason(&reason)?;
+
+    if auth::is_admin(&user) {
+        return Err(AanganError::validation(
+            "user",
+            "administrators cannot be suspended",
+        ));
+    }
+
+    let mut profile =
+        user_store::get_user(&user).ok_or_else(|| AanganError::not_found("User", user))?;
+    if profile.is_suspended() {
+        return Err(AanganError::conflict("User is already suspended"));
+    }
+
+    profile.suspend();
+    user_store::update_user(&user, profile.clone())?;
+    moderation_store::append(admin, ModerationAction::SuspendUser { user }, reason);
+
+    Ok(profile)
+}
+
+#[update]
+pub fn reinstate_user(user: Principal, reason: String) -> Result<User, AanganError> {
+    let admin = auth::require_admin()?;
+    validate_reason(&reason)?;
+
+    let mut profile =
+        user_store::get_user(&user).ok_or_else(|| AanganError::not_found("User", user))?;
+    if !profile.is_suspended() {
+        return Err(AanganError::conflict("User is not suspended"));
+    }
+
+    profile.reinstate();
+    user_store::update_user(&user, profile.clone())?;
+    moderation_store::append(admin, ModerationAction::ReinstateUser { user }, reason);
+
+    Ok(profile)
+}
+
+#[update]
+pub fn delist_property(property_id: u64, reason: String) -> Result<Property, AanganError> {
+    let admin = auth::require_admin()?;
+    validate_reason(&reason)?;
+
+    let mut property = property_store::get_property(property_id)
+        .ok_or_else(|| AanganError::not_found("Property", property_id))?;
+    if property.is_delisted() {
+        return Err(AanganError::conflict("Property is already delisted"));
+    }
+
+    property.delist();
+    property_store::update_property(property.clone())?;
+    moderation_store::append(
+        admin,
+        ModerationAction::DelistProperty { property_id },
+        reason,
+    );
+
+    Ok(property)
+}
+
+#[update]
+pub fn relist_property(property_id: u64, reason: String) -> Result<Property, AanganError> {
+    let admin = auth::require_admin()?;
+    validate_reason(&reason)?;
+
+    let mut property = property_store::get_property(property_id)
+        .ok_or_else(|| AanganError::not_found("Property", property_id))?;
+    if !property.is_delisted() {
+        return Err(AanganError::conflict("Property is not delisted"));
+    }
+
+    property.relist();
+    property_store::update_property(property.clone())?;
+    moderation_store::append(
+        admin,
+        ModerationAction::RelistProperty { property_id },
+        reason,
+    );
+
+    Ok(property)
+}
+
+/// Cancels a rental in any non-terminal status, waives the installments that are not yet
+/// due and refunds a funded deposit to the tenant. A disputed deposit stays in escrow for
+/// the parties to settle. If the refund fails the cancellation still stands and the tenant
+/// can reclaim the deposit with `refund_deposit`.
+#[update]
+pub async fn force_cancel_rental(
+    rental_id: u64,
+    reason: String,
+) -> Result<RentalAgreement, AanganError> {
+    let admin = auth::require_admin()?;
+    validate_reason(&reason)?;
+
+    let mut rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+
+    if matches!(
+        rental.status,
+        RentalStatus::Completed
+            | RentalStatus::Cancelled
+            | RentalStatus::Expired
+            | RentalStatus::Rejected
+    ) {
+        return Err(AanganError::invalid_state(
+            "Requested, Confirmed, Active or Terminating",
+            format!("{:?}", rental.status),
+        ));
+    }
+
+    if escrow::is_locked(rental_id) {
+        return Err(AanganError::conflict(
+            "A ledger operation is already in progress for this rental",
+        ));
+    }
+
+    rental.cancel();
+    payments::waive_from(rental_id, crate::clock::now())?;
+    rental_store::update_rental(rental.clone())?;
+    nft_minter::sync_status(&rental)?;
+
+    moderation_store::append(admin, ModerationAction::CancelRental { rental_id }, reason);
+
+    let funded = rental
+        .escrow
+        .as_ref()
+        .is_some_and(|escrow| escrow.status == EscrowStatus::Funded);
+    if funded {
+        match escrow::refund(rental_id).await {
+            Ok(refunded) => return Ok(refunded),
+            Err(e) => ic_cdk::println!("refund of cancelled rental {} failed: {}", rental_id, e),
+        }
+    }
+
+    Ok(rental)
+}
+
+#[update]
+pub fn void_nft(nft_id: u64, reason: String) -> Result<NFTMetadata, AanganError> {
+    let admin = auth::require_admin()?;
+    validate_reason(&reason)?;
+
+    let nft = nft_minter::void_nft(nft_id)?;
+    moderation_store::append(admin, ModerationAction::VoidNFT { nft_id }, reason);
+
+    Ok(nft)
+}
+
+/// Makes `user` an administrator. Controllers only; controllers are always administrators.
+#[update]
+pub fn grant_admin(user: Principal, reason: String) -> Result<(), AanganError> {
+    let controller = auth::require_controller()?;
+    validate_reason(&reason)?;
+
+    if user == Principal::anonymous() {
+        return Err(AanganError::validation(
+            "user",
+            "the anonymous principal cannot be an administrator",
+        ));
+    }
+    if !config_store::update_config(|config| config.grant_admin(user)) {
+        return Err(AanganError::conflict("User is already an administrator"));
+    }
+    moderation_store::append(controller, ModerationAction::GrantAdmin { user }, reason);
+
+    Ok(())
+}
+
+/// Revokes an administrator granted through the init args or `grant_admin`. Controllers
+/// only; a controller's own admin rights can only be removed by removing it as controller.
+#[update]
+pub fn revoke_admin(user: Principal, reason: String) -> Result<(), AanganError> {
+    let controller = auth::require_controller()?;
+    validate_reason(&reason)?;
+
+    if !config_store::update_config(|config| config.revoke_admin(&user)) {
+        return Err(AanganError::not_found("Admin", user));
+    }
+    moderation_store::append(controller, ModerationAction::RevokeAdmin { user }, reason);
+
+    Ok(())
+}
+
+/// Administrators besides the canister controllers.
+#[query]
+pub fn list_admins() -> Result<Vec<Principal>, AanganError> {
+    auth::require_admin()?;
+    Ok(config_store::get_config().admins.unwrap_or_default())
+}
+
+#[query]
+pub fn get_moderation_log(
+    start: Option<u64>,
+    limit: Option<u32>,
+) -> Result<Vec<ModerationEntry>, AanganError> {
+    auth::require_admin()?;
+    let limit = limit
+        .unwrap_or(MAX_LOG_PAGE_SIZE)
+        .clamp(1, MAX_LOG_PAGE_SIZE) as usize;
+    Ok(moderation_store::get_entries(start.unwrap_or(0), limit))
+}
+
+#[query]
+pub fn is_admin() -> bool {
+    auth::is_admin(&auth::get_caller())
+}
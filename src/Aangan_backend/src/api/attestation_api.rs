@@ -0,0 +1,20 @@
+use crate::auth;
+use crate::rental_core::attestation;
+use crate::types::{AanganError, PreparedLeaseAttestation, SignedLeaseAttestation};
+use ic_cdk_macros::*;
+use serde_bytes::ByteBuf;
+
+#[update]
+pub fn prepare_lease_attestation(rental_id: u64) -> Result<PreparedLeaseAttestation, AanganError> {
+    let caller = auth::require_active()?;
+    attestation::prepare_lease_attestation(rental_id, caller)
+}
+
+#[query]
+pub fn get_lease_attestation(
+    rental_id: u64,
+    message: ByteBuf,
+) -> Result<SignedLeaseAttestation, AanganError> {
+    let caller = auth::require_authenticated()?;
+    attestation::get_lease_attestation(rental_id, &message, caller)
+}
@@ -0,0 +1,38 @@
+use crate::auth;
+use crate::escrow;
+use crate::storage::rental_store;
+use crate::types::{AanganError, RentalAgreement};
+use ic_cdk_macros::*;
+use icrc_ledger_types::icrc1::account::Account;
+
+#[update]
+pub async fn release_deposit(rental_id: u64) -> Result<RentalAgreement, AanganError> {
+    let caller = auth::require_authenticated()?;
+    escrow::release_deposit(rental_id, caller).await
+}
+
+#[update]
+pub async fn refund_deposit(rental_id: u64) -> Result<RentalAgreement, AanganError> {
+    let caller = auth::require_authenticated()?;
+    escrow::refund_deposit(rental_id, caller).await
+}
+
+#[update]
+pub fn dispute_deposit(rental_id: u64) -> Result<RentalAgreement, AanganError> {
+    let caller = auth::require_authenticated()?;
+    escrow::dispute_deposit(rental_id, caller)
+}
+
+#[query]
+pub fn get_deposit_account(rental_id: u64) -> Result<Account, AanganError> {
+    let caller = auth::require_authenticated()?;
+
+    let rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+
+    if rental.landlord != caller && rental.tenant != caller {
+        return Err(AanganError::unauthorized("Access denied"));
+    }
+
+    Ok(escrow::deposit_account(rental_id))
+}
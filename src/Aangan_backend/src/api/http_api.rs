@@ -0,0 +1,80 @@
+use crate::certification;
+use crate::storage::image_store;
+use crate::types::{
+    HttpRequest, HttpResponse, ImageAsset, StreamingCallbackFunction,
+    StreamingCallbackHttpResponse, StreamingCallbackToken, StreamingStrategy,
+};
+use ic_cdk_macros::*;
+use serde_bytes::ByteBuf;
+
+#[query]
+pub fn http_request(request: HttpRequest) -> HttpResponse {
+    if request.method != "GET" && request.method != "HEAD" {
+        return HttpResponse::new(
+            405,
+            vec![("Allow".to_string(), "GET, HEAD".to_string())],
+            vec![],
+        );
+    }
+
+    let path = request.url.split('?').next().unwrap_or_default();
+    if let Some(id) = path.strip_prefix("/images/") {
+        return match id.parse::<u64>() {
+            Ok(id) => serve_image(id, request.certificate_version),
+            Err(_) => HttpResponse::bad_request("Invalid image id"),
+        };
+    }
+
+    match certification::get_certified_asset(path, request.certificate_version) {
+        Some(asset) => HttpResponse::new(asset.status_code, asset.headers, asset.content),
+        None => HttpResponse::not_found(),
+    }
+}
+
+fn serve_image(id: u64, certificate_version: Option<u16>) -> HttpResponse {
+    let Some(asset) = image_store::get_asset(id) else {
+        return HttpResponse::not_found();
+    };
+    let Some(body) = image_store::get_chunk(id, 0) else {
+        return HttpResponse::not_found();
+    };
+    let Some(certificate_headers) =
+        certification::get_certificate_headers(&ImageAsset::url(id), certificate_version)
+    else {
+        return HttpResponse::not_found();
+    };
+
+    let mut headers = certification::image_headers(&asset);
+    headers.extend(certificate_headers);
+    let mut response = HttpResponse::new(200, headers, body);
+    response.streaming_strategy = next_token(&asset, 0).map(|token| StreamingStrategy::Callback {
+        callback: StreamingCallbackFunction::new(
+            ic_cdk::id(),
+            "http_request_streaming_callback".to_string(),
+        ),
+        token,
+    });
+    response
+}
+
+fn next_token(asset: &ImageAsset, chunk_index: u32) -> Option<StreamingCallbackToken> {
+    (chunk_index + 1 < asset.chunk_count).then(|| StreamingCallbackToken {
+        asset_id: asset.id,
+        chunk_index: chunk_index + 1,
+    })
+}
+
+#[query]
+pub fn http_request_streaming_callback(
+    token: StreamingCallbackToken,
+) -> StreamingCallbackHttpResponse {
+    let asset =
+        image_store::get_asset(token.asset_id).unwrap_or_else(|| ic_cdk::trap("Image not found"));
+    let body = image_store::get_chunk(token.asset_id, token.chunk_index)
+        .unwrap_or_else(|| ic_cdk::trap("Chunk not found"));
+
+    StreamingCallbackHttpResponse {
+        body: ByteBuf::from(body),
+        token: next_token(&asset, token.chunk_index),
+    }
+}
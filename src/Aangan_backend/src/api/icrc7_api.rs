@@ -0,0 +1,87 @@
+use crate::rental_core::icrc7;
+use crate::storage::rental_store;
+use crate::types::SupportedStandard;
+use candid::Nat;
+use ic_cdk_macros::*;
+use icrc_ledger_types::icrc::generic_value::ICRC3Map;
+use icrc_ledger_types::icrc1::account::Account;
+
+#[query]
+pub fn icrc7_collection_metadata() -> ICRC3Map {
+    icrc7::collection_metadata()
+}
+
+#[query]
+pub fn icrc7_token_metadata(token_ids: Vec<Nat>) -> Vec<Option<ICRC3Map>> {
+    icrc7::check_batch_size(token_ids.len());
+    token_ids
+        .iter()
+        .map(|id| {
+            icrc7::token_id(id)
+                .and_then(rental_store::get_nft)
+                .map(|nft| icrc7::token_metadata(&nft))
+        })
+        .collect()
+}
+
+#[query]
+pub fn icrc7_owner_of(token_ids: Vec<Nat>) -> Vec<Option<Account>> {
+    icrc7::check_batch_size(token_ids.len());
+    token_ids
+        .iter()
+        .map(|id| {
+            icrc7::token_id(id)
+                .and_then(rental_store::get_token)
+                .map(|nft| icrc7::owner_account(nft.owner))
+        })
+        .collect()
+}
+
+#[query]
+pub fn icrc7_balance_of(accounts: Vec<Account>) -> Vec<Nat> {
+    icrc7::check_batch_size(accounts.len());
+    accounts
+        .iter()
+        .map(|account| {
+            if icrc7::holds_tokens(account) {
+                Nat::from(rental_store::count_nfts_by_owner(&account.owner))
+            } else {
+                Nat::from(0u64)
+            }
+        })
+        .collect()
+}
+
+#[query]
+pub fn icrc7_tokens_of(account: Account, prev: Option<Nat>, take: Option<Nat>) -> Vec<Nat> {
+    if !icrc7::holds_tokens(&account) {
+        return Vec::new();
+    }
+
+    let prev = match prev {
+        Some(prev) => match icrc7::token_id(&prev) {
+            Some(prev) => Some(prev),
+            None => return Vec::new(),
+        },
+        None => None,
+    };
+    let take = take
+        .and_then(|take| usize::try_from(take.0).ok())
+        .unwrap_or(icrc7::DEFAULT_TAKE_VALUE)
+        .min(icrc7::MAX_TAKE_VALUE);
+
+    rental_store::get_nft_ids_by_owner(&account.owner, prev, take)
+        .into_iter()
+        .map(Nat::from)
+        .collect()
+}
+
+#[query]
+pub fn icrc7_supported_standards() -> Vec<SupportedStandard> {
+    icrc7::supported_standards()
+}
+
+#[query]
+pub fn icrc10_supported_standards() -> Vec<SupportedStandard> {
+    icrc7::supported_standards()
+}
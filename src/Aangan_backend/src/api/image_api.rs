@@ -0,0 +1,51 @@
+use crate::auth;
+use crate::certification;
+use crate::storage::{image_store, user_store};
+use crate::types::{AanganError, ImageAsset, Role, UploadSession};
+use ic_cdk_macros::*;
+use serde_bytes::ByteBuf;
+
+/// Opens an upload session for a property image. Landlords only.
+#[update]
+pub fn start_upload(content_type: String, total_size: u64) -> Result<UploadSession, AanganError> {
+    let caller = auth::require_active()?;
+
+    let user =
+        user_store::get_user(&caller).ok_or_else(|| AanganError::not_found("User", caller))?;
+    if !user.has_role(&Role::Landlord) {
+        return Err(AanganError::unauthorized(
+            "Only landlords can upload images",
+        ));
+    }
+
+    image_store::start_upload(caller, content_type, total_size, crate::clock::now())
+}
+
+#[update]
+pub fn put_chunk(upload_id: u64, chunk_index: u32, content: ByteBuf) -> Result<(), AanganError> {
+    let caller = auth::require_active()?;
+    image_store::put_chunk(upload_id, chunk_index, content.into_vec(), &caller)
+}
+
+#[update]
+pub fn commit_upload(upload_id: u64, sha256: Option<ByteBuf>) -> Result<ImageAsset, AanganError> {
+    let caller = auth::require_active()?;
+    let asset = image_store::commit_upload(upload_id, sha256.map(ByteBuf::into_vec), &caller)?;
+    certification::certify_image(&asset);
+    Ok(asset)
+}
+
+/// Deletes one of the caller's images, or abandons an upload that is still open.
+#[update]
+pub fn delete_image(id: u64) -> Result<(), AanganError> {
+    let caller = auth::require_active()?;
+    if image_store::delete_image(id, &caller)?.is_some() {
+        certification::uncertify_image(id);
+    }
+    Ok(())
+}
+
+#[query]
+pub fn get_image(id: u64) -> Result<ImageAsset, AanganError> {
+    image_store::get_asset(id).ok_or_else(|| AanganError::not_found("Image", id))
+}
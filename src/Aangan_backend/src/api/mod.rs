@@ -1,4 +1,11 @@
+pub mod admin_api;
+pub mod attestation_api;
+pub mod escrow_api;
+pub mod http_api;
+pub mod icrc7_api;
+pub mod image_api;
 pub mod nft_api;
+pub mod payment_api;
 pub mod property_api;
 pub mod rental_api;
 pub mod user_api;
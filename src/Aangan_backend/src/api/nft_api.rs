@@ -1,28 +1,79 @@
 use crate::auth;
-use crate::storage::rental_store;
-use crate::types::NFTMetadata;
+use crate::rental_core::assignment;
+use crate::storage::{nft_event_store, rental_store};
+use crate::types::{AanganError, LeaseAssignment, NFTEvent, NFTMetadata};
+use candid::Principal;
 use ic_cdk_macros::*;
 
 #[query]
-pub fn get_my_nfts() -> Result<Vec<NFTMetadata>, String> {
+pub fn get_my_nfts(active_only: Option<bool>) -> Result<Vec<NFTMetadata>, AanganError> {
     let caller = auth::require_authenticated()?;
-    Ok(rental_store::get_nfts_by_owner(&caller))
+    let mut nfts = rental_store::get_nfts_by_owner(&caller);
+
+    // Confirmed leases that have not started yet count as active
+    if active_only.unwrap_or(false) {
+        nfts.retain(NFTMetadata::is_live);
+    }
+
+    Ok(nfts)
 }
 
 #[query]
-pub fn get_nft_by_id(nft_id: u64) -> Result<NFTMetadata, String> {
+pub fn get_nft_by_id(nft_id: u64) -> Result<NFTMetadata, AanganError> {
     let caller = auth::require_authenticated()?;
 
-    let nft = rental_store::get_nft(nft_id).ok_or_else(|| "NFT not found".to_string())?;
+    let nft = rental_store::get_nft(nft_id).ok_or_else(|| AanganError::not_found("NFT", nft_id))?;
 
     if nft.owner != caller {
-        return Err("Access denied".to_string());
+        return Err(AanganError::unauthorized("Access denied"));
     }
 
     Ok(nft)
 }
 
 #[query]
-pub fn get_nft_metadata(nft_id: u64) -> Result<NFTMetadata, String> {
-    rental_store::get_nft(nft_id).ok_or_else(|| "NFT not found".to_string())
+pub fn get_nft_metadata(nft_id: u64) -> Result<NFTMetadata, AanganError> {
+    rental_store::get_nft(nft_id).ok_or_else(|| AanganError::not_found("NFT", nft_id))
+}
+
+#[query]
+pub fn get_nft_history(nft_id: u64) -> Result<Vec<NFTEvent>, AanganError> {
+    rental_store::get_nft(nft_id).ok_or_else(|| AanganError::not_found("NFT", nft_id))?;
+    Ok(nft_event_store::get_events(nft_id))
+}
+
+#[update]
+pub fn propose_lease_transfer(nft_id: u64, to: Principal) -> Result<LeaseAssignment, AanganError> {
+    let caller = auth::require_active()?;
+    assignment::propose_transfer(nft_id, to, caller)
+}
+
+#[update]
+pub fn approve_lease_transfer(nft_id: u64) -> Result<LeaseAssignment, AanganError> {
+    let caller = auth::require_active()?;
+    assignment::approve_transfer(nft_id, caller)
+}
+
+#[update]
+pub fn reject_lease_transfer(nft_id: u64) -> Result<LeaseAssignment, AanganError> {
+    let caller = auth::require_authenticated()?;
+    assignment::reject_transfer(nft_id, caller)
+}
+
+#[update]
+pub fn cancel_lease_transfer(nft_id: u64) -> Result<LeaseAssignment, AanganError> {
+    let caller = auth::require_authenticated()?;
+    assignment::cancel_transfer(nft_id, caller)
+}
+
+#[query]
+pub fn get_pending_lease_transfer(nft_id: u64) -> Result<Option<LeaseAssignment>, AanganError> {
+    let caller = auth::require_authenticated()?;
+    assignment::get_pending_transfer(nft_id, caller)
+}
+
+#[query]
+pub fn get_assignment_history(rental_id: u64) -> Result<Vec<LeaseAssignment>, AanganError> {
+    let caller = auth::require_authenticated()?;
+    assignment::get_assignment_history(rental_id, caller)
 }
@@ -0,0 +1,31 @@
+use crate::auth;
+use crate::rental_core::payments;
+use crate::storage::rental_store;
+use crate::types::{AanganError, RentPayment};
+use ic_cdk_macros::*;
+
+#[update]
+pub async fn pay_rent(rental_id: u64, period: u32) -> Result<RentPayment, AanganError> {
+    let caller = auth::require_authenticated()?;
+    payments::pay_rent(rental_id, period, caller).await
+}
+
+#[update]
+pub fn waive_rent(rental_id: u64, period: u32) -> Result<RentPayment, AanganError> {
+    let caller = auth::require_authenticated()?;
+    payments::waive_rent(rental_id, period, caller)
+}
+
+#[query]
+pub fn get_payment_history(rental_id: u64) -> Result<Vec<RentPayment>, AanganError> {
+    let caller = auth::require_authenticated()?;
+
+    let rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+
+    if rental.landlord != caller && rental.tenant != caller {
+        return Err(AanganError::unauthorized("Access denied"));
+    }
+
+    Ok(payments::get_payment_history(rental_id))
+}
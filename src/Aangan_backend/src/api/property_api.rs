@@ -1,9 +1,16 @@
 use crate::auth;
-use crate::storage::{property_store, user_store};
-use crate::types::{Property, PropertyType, Role};
+use crate::rental_core::calendar;
+use crate::storage::{image_store, property_store, user_store};
+use crate::types::{
+    AanganError, DateRange, GeoPoint, NearbyProperty, Property, PropertyType, Role, SearchPage,
+    SearchQuery, MAX_PAGE_SIZE,
+};
 use candid::Principal;
 use ic_cdk_macros::*;
 
+/// Largest radius accepted by `properties_near` (100 km).
+const MAX_SEARCH_RADIUS_M: u32 = 100_000;
+
 #[update]
 pub fn add_property(
     title: String,
@@ -17,18 +24,28 @@ pub fn add_property(
     area_sqft: u32,
     images: Vec<String>,
     amenities: Vec<String>,
-) -> Result<Property, String> {
-    let caller = auth::require_authenticated()?;
+    location: Option<GeoPoint>,
+) -> Result<Property, AanganError> {
+    let caller = auth::require_active()?;
+
+    if let Some(location) = &location {
+        location.validate()?;
+    }
+
+    image_store::validate_property_images(&images, &caller)?;
 
     // Verify user is a landlord
-    let user = user_store::get_user(&caller).ok_or_else(|| "User not found".to_string())?;
+    let user =
+        user_store::get_user(&caller).ok_or_else(|| AanganError::not_found("User", caller))?;
 
-    if user.role != Role::Landlord {
-        return Err("Only landlords can add properties".to_string());
+    if !user.has_role(&Role::Landlord) {
+        return Err(AanganError::unauthorized(
+            "Only landlords can add properties",
+        ));
     }
 
     let property_id = crate::storage::get_next_property_id();
-    let property = Property::new(
+    let mut property = Property::new(
         property_id,
         caller,
         title,
@@ -43,6 +60,7 @@ pub fn add_property(
         images,
         amenities,
     );
+    property.set_location(location);
 
     property_store::create_property(property.clone())?;
     Ok(property)
@@ -58,36 +76,160 @@ pub fn get_available_properties() -> Vec<Property> {
     property_store::get_available_properties()
 }
 
+/// Windows within `[from, to)` not covered by a Confirmed or Active lease.
+#[query]
+pub fn get_availability(
+    property_id: u64,
+    from: u64,
+    to: u64,
+) -> Result<Vec<DateRange>, AanganError> {
+    get_visible_property(property_id)
+        .ok_or_else(|| AanganError::not_found("Property", property_id))?;
+
+    if to <= from {
+        return Err(AanganError::validation("to", "must be after from"));
+    }
+
+    Ok(calendar::free_windows(
+        property_id,
+        &DateRange {
+            start: from,
+            end: to,
+        },
+    ))
+}
+
+#[query]
+pub fn search_properties(query: SearchQuery) -> SearchPage {
+    property_store::search_properties(&query)
+}
+
+#[query]
+pub fn text_search(query: String, limit: u32) -> Vec<Property> {
+    let limit = limit.clamp(1, MAX_PAGE_SIZE) as usize;
+    property_store::text_search(&query, limit)
+}
+
 #[query]
-pub fn get_property_by_id(id: u64) -> Result<Property, String> {
-    property_store::get_property(id).ok_or_else(|| "Property not found".to_string())
+pub fn properties_near(
+    latitude: f64,
+    longitude: f64,
+    radius_m: u32,
+    available_only: bool,
+) -> Result<Vec<NearbyProperty>, AanganError> {
+    let center = GeoPoint {
+        latitude,
+        longitude,
+    };
+    center.validate()?;
+
+    if radius_m == 0 || radius_m > MAX_SEARCH_RADIUS_M {
+        return Err(AanganError::validation(
+            "radius_m",
+            format!("must be between 1 and {} meters", MAX_SEARCH_RADIUS_M),
+        ));
+    }
+
+    Ok(property_store::properties_near(
+        &center,
+        radius_m as f64,
+        available_only,
+    ))
 }
 
 #[query]
-pub fn get_properties_by_landlord(landlord: Option<Principal>) -> Result<Vec<Property>, String> {
+pub fn properties_in_bbox(
+    min_latitude: f64,
+    min_longitude: f64,
+    max_latitude: f64,
+    max_longitude: f64,
+    available_only: bool,
+) -> Result<Vec<NearbyProperty>, AanganError> {
+    let min = GeoPoint {
+        latitude: min_latitude,
+        longitude: min_longitude,
+    };
+    let max = GeoPoint {
+        latitude: max_latitude,
+        longitude: max_longitude,
+    };
+    min.validate()?;
+    max.validate()?;
+
+    if min_latitude > max_latitude {
+        return Err(AanganError::validation(
+            "min_latitude",
+            "must not exceed max_latitude",
+        ));
+    }
+
+    Ok(property_store::properties_in_bbox(
+        &min,
+        &max,
+        available_only,
+    ))
+}
+
+/// A delisted property is only visible to its owner and to administrators.
+fn get_visible_property(id: u64) -> Option<Property> {
+    let property = property_store::get_property(id)?;
+    if property.is_delisted() {
+        let caller = auth::get_caller();
+        if property.owner != caller && !auth::is_admin(&caller) {
+            return None;
+        }
+    }
+    Some(property)
+}
+
+#[query]
+pub fn get_property_by_id(id: u64) -> Result<Property, AanganError> {
+    get_visible_property(id).ok_or_else(|| AanganError::not_found("Property", id))
+}
+
+#[query]
+pub fn get_properties_by_landlord(
+    landlord: Option<Principal>,
+) -> Result<Vec<Property>, AanganError> {
+    let caller = auth::get_caller();
     let target_principal = match landlord {
         Some(p) => p,
         None => auth::require_authenticated()?,
     };
 
-    Ok(property_store::get_properties_by_owner(&target_principal))
+    let mut properties = property_store::get_properties_by_owner(&target_principal);
+    if target_principal != caller && !auth::is_admin(&caller) {
+        properties.retain(|property| !property.is_delisted());
+    }
+    Ok(properties)
 }
 
 #[query]
-pub fn get_my_properties() -> Result<Vec<Property>, String> {
+pub fn get_my_properties() -> Result<Vec<Property>, AanganError> {
     let caller = auth::require_authenticated()?;
     Ok(property_store::get_properties_by_owner(&caller))
 }
 
 #[update]
-pub fn update_property_availability(property_id: u64, available: bool) -> Result<Property, String> {
-    let caller = auth::require_authenticated()?;
+pub fn update_property_availability(
+    property_id: u64,
+    available: bool,
+) -> Result<Property, AanganError> {
+    let caller = auth::require_active()?;
 
     let mut property = property_store::get_property(property_id)
-        .ok_or_else(|| "Property not found".to_string())?;
+        .ok_or_else(|| AanganError::not_found("Property", property_id))?;
 
     if property.owner != caller {
-        return Err("Only property owner can update availability".to_string());
+        return Err(AanganError::unauthorized(
+            "Only property owner can update availability",
+        ));
+    }
+
+    if available && property.is_delisted() {
+        return Err(AanganError::conflict(
+            "Property has been delisted by an administrator",
+        ));
     }
 
     property.update_availability(available);
@@ -95,3 +237,50 @@ pub fn update_property_availability(property_id: u64, available: bool) -> Result
 
     Ok(property)
 }
+
+#[update]
+pub fn set_property_location(
+    property_id: u64,
+    location: Option<GeoPoint>,
+) -> Result<Property, AanganError> {
+    let caller = auth::require_active()?;
+
+    if let Some(location) = &location {
+        location.validate()?;
+    }
+
+    let mut property = property_store::get_property(property_id)
+        .ok_or_else(|| AanganError::not_found("Property", property_id))?;
+
+    if property.owner != caller {
+        return Err(AanganError::unauthorized(
+            "Only property owner can update location",
+        ));
+    }
+
+    property.set_location(location);
+    property_store::update_property(property.clone())?;
+
+    Ok(property)
+}
+
+#[update]
+pub fn set_property_images(property_id: u64, images: Vec<String>) -> Result<Property, AanganError> {
+    let caller = auth::require_active()?;
+
+    let mut property = property_store::get_property(property_id)
+        .ok_or_else(|| AanganError::not_found("Property", property_id))?;
+
+    if property.owner != caller {
+        return Err(AanganError::unauthorized(
+            "Only property owner can update images",
+        ));
+    }
+
+    image_store::validate_property_images(&images, &caller)?;
+
+    property.set_images(images);
+    property_store::update_property(property.clone())?;
+
+    Ok(property)
+}
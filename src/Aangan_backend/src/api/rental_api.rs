@@ -1,38 +1,67 @@
 use crate::auth;
-use crate::rental_core::rental_manager;
+use crate::escrow;
+use crate::rental_core::{acceptance, calendar, nft_minter, renewal, rental_manager, termination};
 use crate::storage::{property_store, rental_store, user_store};
-use crate::types::{RentalAgreement, RentalStatus, Role};
+use crate::types::{
+    AanganError, DateRange, Escrow, LeaseRenewal, LeaseTerms, RentalAgreement, RentalStatus, Role,
+    TerminationTerms,
+};
 use ic_cdk_macros::*;
+use serde_bytes::ByteBuf;
+use std::future::Future;
 
 #[update]
 pub fn request_rental(
     property_id: u64,
     start_date: u64,
     end_date: u64,
-) -> Result<RentalAgreement, String> {
-    let caller = auth::require_authenticated()?;
+) -> Result<RentalAgreement, AanganError> {
+    let caller = auth::require_active()?;
+
+    let period = DateRange {
+        start: start_date,
+        end: end_date,
+    };
+    calendar::validate_period(&period, crate::clock::now())?;
 
     // Verify user is a tenant
-    let user = user_store::get_user(&caller).ok_or_else(|| "User not found".to_string())?;
+    let user =
+        user_store::get_user(&caller).ok_or_else(|| AanganError::not_found("User", caller))?;
 
-    if user.role != Role::Tenant {
-        return Err("Only tenants can request rentals".to_string());
+    if !user.has_role(&Role::Tenant) {
+        return Err(AanganError::unauthorized(
+            "Only tenants can request rentals",
+        ));
     }
 
     let property = property_store::get_property(property_id)
-        .ok_or_else(|| "Property not found".to_string())?;
+        .ok_or_else(|| AanganError::not_found("Property", property_id))?;
 
     if !property.is_available {
-        return Err("Property is not available for rent".to_string());
+        return Err(AanganError::conflict("Property is not available for rent"));
     }
 
-    // Check if there's already an active rental for this property
-    if let Some(existing_rental) = rental_store::get_rental_by_property(property_id) {
-        if existing_rental.status == RentalStatus::Active
-            || existing_rental.status == RentalStatus::Confirmed
-        {
-            return Err("Property already has an active rental".to_string());
-        }
+    if property.owner == caller {
+        return Err(AanganError::validation(
+            "property_id",
+            "landlords cannot apply for their own property",
+        ));
+    }
+
+    if !calendar::is_free(property_id, &period, None) {
+        return Err(AanganError::conflict(
+            "Requested dates overlap an existing lease",
+        ));
+    }
+
+    // Applications compete until the landlord accepts one of them
+    if rental_store::get_rentals_by_property(property_id)
+        .iter()
+        .any(|rental| rental.tenant == caller && rental.status == RentalStatus::Requested)
+    {
+        return Err(AanganError::conflict(
+            "You already have a pending application for this property",
+        ));
     }
 
     let rental_id = crate::storage::get_next_rental_id();
@@ -49,88 +78,473 @@ pub fn request_rental(
 
     rental_store::create_rental(rental.clone())?;
 
-    // Update property availability
-    let mut updated_property = property;
-    updated_property.update_availability(false);
-    property_store::update_property(updated_property)?;
-
     Ok(rental)
 }
 
+/// Pending applications for one of the caller's properties.
+#[query]
+pub fn list_applications(property_id: u64) -> Result<Vec<RentalAgreement>, AanganError> {
+    let caller = auth::require_authenticated()?;
+
+    let property = property_store::get_property(property_id)
+        .ok_or_else(|| AanganError::not_found("Property", property_id))?;
+
+    if property.owner != caller {
+        return Err(AanganError::unauthorized(
+            "Only property owner can list applications",
+        ));
+    }
+
+    let mut applications = rental_store::get_rentals_by_property(property_id);
+    applications.retain(|rental| rental.status == RentalStatus::Requested);
+    Ok(applications)
+}
+
+/// Same as [`accept_application`], kept for existing clients.
+#[update]
+pub async fn confirm_rental(rental_id: u64) -> Result<RentalAgreement, AanganError> {
+    accept_application(rental_id).await
+}
+
+/// Attaches the lease document's hash and clauses to an application. Landlord only; both
+/// parties have to sign again afterwards.
 #[update]
-pub fn confirm_rental(rental_id: u64) -> Result<RentalAgreement, String> {
+pub fn set_lease_terms(rental_id: u64, terms: LeaseTerms) -> Result<RentalAgreement, AanganError> {
+    let caller = auth::require_active()?;
+    acceptance::set_lease_terms(rental_id, terms, caller)
+}
+
+/// Hash of everything the parties agree to by signing the application.
+#[query]
+pub fn get_terms_hash(rental_id: u64) -> Result<ByteBuf, AanganError> {
     let caller = auth::require_authenticated()?;
+    let hash = acceptance::get_terms_hash(rental_id, caller)?;
+    Ok(ByteBuf::from(hash.to_vec()))
+}
 
-    let mut rental =
-        rental_store::get_rental(rental_id).ok_or_else(|| "Rental not found".to_string())?;
+/// Accepts the terms identified by `terms_hash` on behalf of the landlord or the tenant.
+#[update]
+pub fn sign_lease(rental_id: u64, terms_hash: ByteBuf) -> Result<RentalAgreement, AanganError> {
+    let caller = auth::require_active()?;
+    acceptance::sign_lease(rental_id, &terms_hash, caller)
+}
+
+/// Confirms one application and rejects the pending applications whose dates overlap it.
+/// Both parties must have signed the application's current terms.
+#[update]
+pub async fn accept_application(rental_id: u64) -> Result<RentalAgreement, AanganError> {
+    let caller = auth::require_active()?;
+
+    let rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
 
     if rental.landlord != caller {
-        return Err("Only landlord can confirm rental".to_string());
+        return Err(AanganError::unauthorized(
+            "Only landlord can confirm rental",
+        ));
+    }
+
+    ensure_acceptable(&rental)?;
+
+    // Pull the tenant's deposit into escrow before anything else changes. Holding the
+    // guard also keeps a competing application from being accepted in the meantime.
+    let guard = escrow::LedgerGuard::acquire(rental_id)?;
+    if rental_store::get_rentals_by_property(rental.property_id)
+        .iter()
+        .any(|other| {
+            other.id != rental_id
+                && other.period().overlaps(&rental.period())
+                && escrow::is_locked(other.id)
+        })
+    {
+        return Err(AanganError::conflict(
+            "Another application for this property is being accepted",
+        ));
     }
+    let deposit = escrow::fund_deposit(&rental).await?;
 
+    confirm_funded(rental_id, deposit, guard, escrow::refund).await
+}
+
+/// Records a deposit that was just pulled into escrow and confirms the application. If the
+/// confirmation fails, `refund` returns the deposit to the tenant.
+async fn confirm_funded<Refund, Refunded>(
+    rental_id: u64,
+    deposit: Option<Escrow>,
+    guard: escrow::LedgerGuard,
+    refund: Refund,
+) -> Result<RentalAgreement, AanganError>
+where
+    Refund: FnOnce(u64) -> Refunded,
+    Refunded: Future<Output = Result<RentalAgreement, AanganError>>,
+{
+    // Record the deposit before anything else can fail, so that it can be refunded
+    let funded = deposit.is_some();
+    if let Some(deposit) = deposit {
+        let mut rental = rental_store::get_rental(rental_id)
+            .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+        rental.set_escrow(deposit);
+        rental_store::update_rental(rental)?;
+    }
+
+    match confirm_application(rental_id) {
+        Ok(rental) => Ok(rental),
+        Err(e) => {
+            // The refund takes the ledger guard itself
+            drop(guard);
+            if funded {
+                if let Err(refund_error) = refund(rental_id).await {
+                    ic_cdk::println!(
+                        "refund of unconfirmed rental {} failed: {}",
+                        rental_id,
+                        refund_error
+                    );
+                }
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Checks that can change while the deposit transfer is in flight, so they run both before
+/// and after it.
+fn ensure_acceptable(rental: &RentalAgreement) -> Result<(), AanganError> {
     if rental.status != RentalStatus::Requested {
-        return Err("Rental is not in requested state".to_string());
+        return Err(AanganError::invalid_state(
+            "Requested",
+            format!("{:?}", rental.status),
+        ));
     }
 
-    // Mint NFT for the rental
-    let nft_id = rental_manager::mint_rental_nft(&rental)?;
+    acceptance::ensure_fully_signed(rental)?;
+
+    let property = property_store::get_property(rental.property_id)
+        .ok_or_else(|| AanganError::not_found("Property", rental.property_id))?;
+    if property.is_delisted() {
+        return Err(AanganError::conflict(
+            "Property has been delisted by an administrator",
+        ));
+    }
+    if !property.is_available {
+        return Err(AanganError::conflict("Property is not available for rent"));
+    }
+
+    if !calendar::is_free(rental.property_id, &rental.period(), None) {
+        return Err(AanganError::conflict(
+            "Requested dates overlap an existing lease",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Mints the NFT and confirms an application whose deposit is in escrow, then rejects the
+/// pending applications whose dates overlap it.
+fn confirm_application(rental_id: u64) -> Result<RentalAgreement, AanganError> {
+    let mut rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+    ensure_acceptable(&rental)?;
 
-    // Update rental status
+    let nft_id = rental_manager::mint_rental_nft(&rental)?;
     rental.confirm(nft_id);
     rental_store::update_rental(rental.clone())?;
 
+    // Applications for other dates stay pending
+    for mut other in rental_store::get_rentals_by_property(rental.property_id) {
+        if other.status == RentalStatus::Requested && other.period().overlaps(&rental.period()) {
+            other.reject();
+            rental_store::update_rental(other)?;
+        }
+    }
+
     Ok(rental)
 }
 
 #[update]
-pub fn cancel_rental(rental_id: u64) -> Result<RentalAgreement, String> {
+pub fn activate_rental(rental_id: u64) -> Result<RentalAgreement, AanganError> {
+    let caller = auth::require_authenticated()?;
+
+    let rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+
+    if rental.landlord != caller {
+        return Err(AanganError::unauthorized(
+            "Only landlord can activate rental",
+        ));
+    }
+
+    rental_manager::activate_rental(rental_id)
+}
+
+#[update]
+pub fn cancel_rental(rental_id: u64) -> Result<RentalAgreement, AanganError> {
     let caller = auth::require_authenticated()?;
 
-    let mut rental =
-        rental_store::get_rental(rental_id).ok_or_else(|| "Rental not found".to_string())?;
+    let mut rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
 
     if rental.landlord != caller && rental.tenant != caller {
-        return Err("Only landlord or tenant can cancel rental".to_string());
+        return Err(AanganError::unauthorized(
+            "Only landlord or tenant can cancel rental",
+        ));
+    }
+
+    // Running leases end through `request_termination`
+    if matches!(
+        rental.status,
+        RentalStatus::Active | RentalStatus::Terminating
+    ) {
+        return Err(AanganError::invalid_state(
+            "Requested or Confirmed",
+            format!("{:?}", rental.status),
+        ));
     }
 
-    if rental.status == RentalStatus::Active {
-        return Err("Cannot cancel active rental".to_string());
+    if escrow::is_locked(rental_id) {
+        return Err(AanganError::conflict(
+            "A ledger operation is already in progress for this rental",
+        ));
     }
 
     rental.cancel();
     rental_store::update_rental(rental.clone())?;
-
-    // Make property available again
-    if let Some(mut property) = property_store::get_property(rental.property_id) {
-        property.update_availability(true);
-        let _ = property_store::update_property(property);
-    }
+    nft_minter::sync_status(&rental)?;
 
     Ok(rental)
 }
 
+/// Sets the notice period and penalty for ending the lease early. Landlord only, before the
+/// application is accepted.
+#[update]
+pub fn set_termination_terms(
+    rental_id: u64,
+    terms: TerminationTerms,
+) -> Result<RentalAgreement, AanganError> {
+    let caller = auth::require_active()?;
+    termination::set_termination_terms(rental_id, terms, caller)
+}
+
+/// Gives notice on an Active lease, which completes once the notice period has passed.
+#[update]
+pub fn request_termination(rental_id: u64) -> Result<RentalAgreement, AanganError> {
+    let caller = auth::require_authenticated()?;
+    termination::request_termination(rental_id, caller)
+}
+
+#[update]
+pub async fn pay_termination_penalty(rental_id: u64) -> Result<RentalAgreement, AanganError> {
+    let caller = auth::require_authenticated()?;
+    termination::pay_termination_penalty(rental_id, caller).await
+}
+
 #[query]
-pub fn get_my_rentals() -> Result<Vec<RentalAgreement>, String> {
+pub fn get_my_rentals() -> Result<Vec<RentalAgreement>, AanganError> {
     let caller = auth::require_authenticated()?;
 
-    let user = user_store::get_user(&caller).ok_or_else(|| "User not found".to_string())?;
+    let user =
+        user_store::get_user(&caller).ok_or_else(|| AanganError::not_found("User", caller))?;
 
-    match user.role {
-        Role::Tenant => Ok(rental_store::get_rentals_by_tenant(&caller)),
-        Role::Landlord => Ok(rental_store::get_rentals_by_landlord(&caller)),
+    let mut rentals = Vec::new();
+    if user.has_role(&Role::Tenant) {
+        rentals.extend(rental_store::get_rentals_by_tenant(&caller));
     }
+    if user.has_role(&Role::Landlord) {
+        rentals.extend(rental_store::get_rentals_by_landlord(&caller));
+    }
+    rentals.sort_by_key(|rental| rental.id);
+    rentals.dedup_by_key(|rental| rental.id);
+    Ok(rentals)
 }
 
 #[query]
-pub fn get_rental_by_id(rental_id: u64) -> Result<RentalAgreement, String> {
+pub fn get_rental_by_id(rental_id: u64) -> Result<RentalAgreement, AanganError> {
     let caller = auth::require_authenticated()?;
 
-    let rental =
-        rental_store::get_rental(rental_id).ok_or_else(|| "Rental not found".to_string())?;
+    let rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
 
     if rental.landlord != caller && rental.tenant != caller {
-        return Err("Access denied".to_string());
+        return Err(AanganError::unauthorized("Access denied"));
     }
 
     Ok(rental)
 }
+
+/// Proposes extending the lease to `new_end_date`, at `new_rent_amount` if given.
+#[update]
+pub fn propose_renewal(
+    rental_id: u64,
+    new_end_date: u64,
+    new_rent_amount: Option<u64>,
+) -> Result<LeaseRenewal, AanganError> {
+    let caller = auth::require_active()?;
+    renewal::propose_renewal(rental_id, new_end_date, new_rent_amount, caller)
+}
+
+/// Accepts the pending renewal, signing the amended terms identified by `terms_hash`.
+#[update]
+pub fn accept_renewal(rental_id: u64, terms_hash: ByteBuf) -> Result<LeaseRenewal, AanganError> {
+    let caller = auth::require_active()?;
+    renewal::accept_renewal(rental_id, &terms_hash, caller)
+}
+
+#[update]
+pub fn reject_renewal(rental_id: u64) -> Result<LeaseRenewal, AanganError> {
+    let caller = auth::require_authenticated()?;
+    renewal::reject_renewal(rental_id, caller)
+}
+
+#[update]
+pub fn withdraw_renewal(rental_id: u64) -> Result<LeaseRenewal, AanganError> {
+    let caller = auth::require_authenticated()?;
+    renewal::withdraw_renewal(rental_id, caller)
+}
+
+#[query]
+pub fn get_pending_renewal(rental_id: u64) -> Result<Option<LeaseRenewal>, AanganError> {
+    let caller = auth::require_authenticated()?;
+    renewal::get_pending_renewal(rental_id, caller)
+}
+
+#[query]
+pub fn get_renewal_history(rental_id: u64) -> Result<Vec<LeaseRenewal>, AanganError> {
+    let caller = auth::require_authenticated()?;
+    renewal::get_renewal_history(rental_id, caller)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::get_next_rental_id;
+    use crate::types::{EscrowStatus, Property, PropertyType};
+    use candid::Principal;
+    use std::cell::Cell;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    const START: u64 = 1_800_000_000_000_000_000;
+    const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+    const DEPOSIT: u64 = 100_000_000;
+
+    fn landlord() -> Principal {
+        Principal::from_slice(&[2])
+    }
+
+    fn tenant() -> Principal {
+        Principal::from_slice(&[3])
+    }
+
+    /// Runs a future that never waits, which holds for every ledger stand-in below.
+    fn run<F: Future>(future: F) -> F::Output {
+        match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future is waiting on a real call"),
+        }
+    }
+
+    fn store_property() {
+        property_store::create_property(Property::new(
+            1,
+            landlord(),
+            "Flat".to_string(),
+            String::new(),
+            String::new(),
+            25_000_000,
+            DEPOSIT,
+            PropertyType::Apartment,
+            2,
+            1,
+            800,
+            vec![],
+            vec![],
+        ))
+        .unwrap();
+    }
+
+    /// A signed application for the days `[from, to)` after `START`.
+    fn apply(from: u64, to: u64) -> u64 {
+        let rental = RentalAgreement::new(
+            get_next_rental_id(),
+            1,
+            landlord(),
+            tenant(),
+            START + from * DAY_NANOS,
+            START + to * DAY_NANOS,
+            25_000_000,
+            DEPOSIT,
+        );
+        rental_store::create_rental(rental.clone()).unwrap();
+        let terms = LeaseTerms {
+            document_hash: ByteBuf::from(vec![7u8; 32]),
+            clauses: vec![],
+        };
+        acceptance::set_lease_terms(rental.id, terms, landlord()).unwrap();
+        for signer in [landlord(), tenant()] {
+            let hash = acceptance::get_terms_hash(rental.id, signer).unwrap();
+            acceptance::sign_lease(rental.id, &hash, signer).unwrap();
+        }
+        rental.id
+    }
+
+    fn status(rental_id: u64) -> RentalStatus {
+        rental_store::get_rental(rental_id).unwrap().status
+    }
+
+    fn accept(
+        rental_id: u64,
+        refunded: &Cell<Option<u64>>,
+    ) -> Result<RentalAgreement, AanganError> {
+        let guard = escrow::LedgerGuard::acquire(rental_id).unwrap();
+        let deposit = Escrow::new(tenant(), DEPOSIT, 1);
+        run(confirm_funded(rental_id, Some(deposit), guard, |id| {
+            refunded.set(Some(id));
+            async move { Ok(rental_store::get_rental(id).unwrap()) }
+        }))
+    }
+
+    #[test]
+    fn accepting_an_application_rejects_the_overlapping_ones() {
+        store_property();
+        let accepted = apply(0, 30);
+        let overlapping = apply(20, 50);
+        let later = apply(30, 60);
+        let refunded = Cell::new(None);
+
+        let rental = accept(accepted, &refunded).unwrap();
+
+        assert_eq!(rental.status, RentalStatus::Confirmed);
+        assert!(rental.nft_id.is_some());
+        assert_eq!(rental.escrow.unwrap().status, EscrowStatus::Funded);
+        assert_eq!(status(overlapping), RentalStatus::Rejected);
+        // Applications for other dates stay pending
+        assert_eq!(status(later), RentalStatus::Requested);
+        assert_eq!(refunded.get(), None);
+        assert!(!escrow::is_locked(accepted));
+    }
+
+    #[test]
+    fn a_failed_confirmation_refunds_the_deposit() {
+        store_property();
+        let rental_id = apply(0, 30);
+        let other = apply(20, 50);
+        // The landlord closes the listing while the deposit transfer is in flight
+        let mut property = property_store::get_property(1).unwrap();
+        property.update_availability(false);
+        property_store::update_property(property).unwrap();
+        let refunded = Cell::new(None);
+
+        assert!(matches!(
+            accept(rental_id, &refunded),
+            Err(AanganError::Conflict(_))
+        ));
+
+        assert_eq!(refunded.get(), Some(rental_id));
+        let rental = rental_store::get_rental(rental_id).unwrap();
+        assert_eq!(rental.status, RentalStatus::Requested);
+        assert_eq!(rental.nft_id, None);
+        // The deposit was recorded for the refund to pay out
+        assert_eq!(rental.escrow.unwrap().amount, DEPOSIT);
+        assert_eq!(status(other), RentalStatus::Requested);
+        assert!(!escrow::is_locked(rental_id));
+    }
+}
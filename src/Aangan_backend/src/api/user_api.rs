@@ -1,16 +1,19 @@
 use crate::auth;
-use crate::storage::user_store;
-use crate::types::{Role, User};
+use crate::storage::{property_store, rental_store, user_store};
+use crate::types::{AanganError, ProfileVisibility, PublicUser, Role, User, Viewer};
 use candid::Principal;
 use ic_cdk_macros::*;
 
+/// Upper bound on the number of users returned per call.
+pub const MAX_USER_PAGE_SIZE: u32 = 100;
+
 #[update]
 pub fn create_user(
     role: Role,
     name: Option<String>,
     email: Option<String>,
     phone: Option<String>,
-) -> Result<User, String> {
+) -> Result<User, AanganError> {
     let caller = auth::require_authenticated()?;
 
     let user = User::new(caller, role, name, email, phone);
@@ -19,14 +22,32 @@ pub fn create_user(
     Ok(user)
 }
 
+/// Returns a profile with contact details filtered by the user's visibility settings.
 #[query]
-pub fn get_user(principal: Option<Principal>) -> Result<User, String> {
+pub fn get_user(principal: Option<Principal>) -> Result<PublicUser, AanganError> {
     let target_principal = match principal {
         Some(p) => p,
         None => auth::require_authenticated()?,
     };
 
-    user_store::get_user(&target_principal).ok_or_else(|| "User not found".to_string())
+    let user = user_store::get_user(&target_principal)
+        .ok_or_else(|| AanganError::not_found("User", target_principal))?;
+
+    Ok(user.view(viewer_of(&target_principal)))
+}
+
+fn viewer_of(target: &Principal) -> Viewer {
+    if auth::is_anonymous() {
+        return Viewer::Public;
+    }
+    let caller = auth::get_caller();
+    if caller == *target || auth::is_admin(&caller) {
+        Viewer::Owner
+    } else if rental_store::share_live_rental(&caller, target) {
+        Viewer::Counterparty
+    } else {
+        Viewer::Public
+    }
 }
 
 #[update]
@@ -34,10 +55,11 @@ pub fn update_user_profile(
     name: Option<String>,
     email: Option<String>,
     phone: Option<String>,
-) -> Result<User, String> {
-    let caller = auth::require_authenticated()?;
+) -> Result<User, AanganError> {
+    let caller = auth::require_active()?;
 
-    let mut user = user_store::get_user(&caller).ok_or_else(|| "User not found".to_string())?;
+    let mut user =
+        user_store::get_user(&caller).ok_or_else(|| AanganError::not_found("User", caller))?;
 
     user.update_profile(name, email, phone);
     user_store::update_user(&caller, user.clone())?;
@@ -45,13 +67,169 @@ pub fn update_user_profile(
     Ok(user)
 }
 
+#[update]
+pub fn set_profile_visibility(visibility: ProfileVisibility) -> Result<User, AanganError> {
+    let caller = auth::require_active()?;
+
+    let mut user =
+        user_store::get_user(&caller).ok_or_else(|| AanganError::not_found("User", caller))?;
+
+    user.set_visibility(visibility);
+    user_store::update_user(&caller, user.clone())?;
+
+    Ok(user)
+}
+
+/// Full user records, ordered by principal. Admin only.
 #[query]
-pub fn get_all_users() -> Vec<User> {
-    user_store::get_all_users()
+pub fn get_all_users(
+    after: Option<Principal>,
+    limit: Option<u32>,
+) -> Result<Vec<User>, AanganError> {
+    auth::require_admin()?;
+    let limit = limit
+        .unwrap_or(MAX_USER_PAGE_SIZE)
+        .clamp(1, MAX_USER_PAGE_SIZE) as usize;
+    Ok(user_store::get_users_page(after, limit))
 }
 
 #[query]
-pub fn get_my_profile() -> Result<User, String> {
+pub fn get_my_profile() -> Result<User, AanganError> {
     let caller = auth::require_authenticated()?;
-    get_user(Some(caller))
+    user_store::get_user(&caller).ok_or_else(|| AanganError::not_found("User", caller))
+}
+
+#[update]
+pub fn add_role(role: Role) -> Result<User, AanganError> {
+    let caller = auth::require_active()?;
+
+    let mut user =
+        user_store::get_user(&caller).ok_or_else(|| AanganError::not_found("User", caller))?;
+
+    if !user.add_role(role) {
+        return Err(AanganError::conflict("User already holds this role"));
+    }
+    user_store::update_user(&caller, user.clone())?;
+
+    Ok(user)
+}
+
+#[update]
+pub fn remove_role(role: Role) -> Result<User, AanganError> {
+    let caller = auth::require_active()?;
+
+    let mut user =
+        user_store::get_user(&caller).ok_or_else(|| AanganError::not_found("User", caller))?;
+
+    if user.roles == [role.clone()] {
+        return Err(AanganError::validation(
+            "role",
+            "a user must keep at least one role",
+        ));
+    }
+    ensure_role_unused(&user, &role)?;
+    if !user.remove_role(&role) {
+        return Err(AanganError::validation(
+            "role",
+            "user does not hold this role",
+        ));
+    }
+    user_store::update_user(&caller, user.clone())?;
+
+    Ok(user)
+}
+
+/// A landlord keeps the role while they own properties or let a live lease, and a tenant
+/// while they rent under a live lease.
+fn ensure_role_unused(user: &User, role: &Role) -> Result<(), AanganError> {
+    let principal = &user.user_principal;
+    let in_use = match role {
+        Role::Landlord => {
+            !property_store::get_properties_by_owner(principal).is_empty()
+                || rental_store::get_rentals_by_landlord(principal)
+                    .iter()
+                    .any(|rental| rental.is_live())
+        }
+        Role::Tenant => rental_store::get_rentals_by_tenant(principal)
+            .iter()
+            .any(|rental| rental.is_live()),
+    };
+
+    if in_use {
+        return Err(AanganError::conflict(format!(
+            "The {:?} role is still in use by properties or live leases",
+            role
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Property, PropertyType, RentalAgreement};
+
+    fn user(principal: Principal) -> User {
+        let mut user = User::new(principal, Role::Landlord, None, None, None);
+        user.add_role(Role::Tenant);
+        user
+    }
+
+    fn property(id: u64, owner: Principal) -> Property {
+        Property::new(
+            id,
+            owner,
+            "Flat".to_string(),
+            String::new(),
+            String::new(),
+            25_000_000,
+            100_000_000,
+            PropertyType::Apartment,
+            2,
+            1,
+            800,
+            vec![],
+            vec![],
+        )
+    }
+
+    #[test]
+    fn unused_roles_can_be_removed() {
+        let user = user(Principal::from_slice(&[2]));
+        assert!(ensure_role_unused(&user, &Role::Landlord).is_ok());
+        assert!(ensure_role_unused(&user, &Role::Tenant).is_ok());
+    }
+
+    #[test]
+    fn property_owner_keeps_landlord_role() {
+        let owner = Principal::from_slice(&[2]);
+        property_store::create_property(property(1, owner)).unwrap();
+
+        assert!(matches!(
+            ensure_role_unused(&user(owner), &Role::Landlord),
+            Err(AanganError::Conflict(_))
+        ));
+        assert!(ensure_role_unused(&user(owner), &Role::Tenant).is_ok());
+    }
+
+    #[test]
+    fn parties_to_a_live_lease_keep_their_roles() {
+        let landlord = Principal::from_slice(&[2]);
+        let tenant = Principal::from_slice(&[3]);
+        let mut rental = RentalAgreement::new(1, 1, landlord, tenant, 0, 1, 0, 0);
+        rental_store::create_rental(rental.clone()).unwrap();
+
+        // A pending application does not hold on to either role
+        assert!(ensure_role_unused(&user(landlord), &Role::Landlord).is_ok());
+        assert!(ensure_role_unused(&user(tenant), &Role::Tenant).is_ok());
+
+        rental.activate();
+        rental_store::update_rental(rental.clone()).unwrap();
+        assert!(ensure_role_unused(&user(landlord), &Role::Landlord).is_err());
+        assert!(ensure_role_unused(&user(tenant), &Role::Tenant).is_err());
+
+        rental.complete();
+        rental_store::update_rental(rental).unwrap();
+        assert!(ensure_role_unused(&user(tenant), &Role::Tenant).is_ok());
+    }
 }
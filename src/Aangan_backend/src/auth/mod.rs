@@ -1,3 +1,5 @@
+use crate::storage::{config_store, user_store};
+use crate::types::AanganError;
 use candid::Principal;
 use ic_cdk::caller;
 
@@ -9,10 +11,43 @@ pub fn is_anonymous() -> bool {
     caller() == Principal::anonymous()
 }
 
-pub fn require_authenticated() -> Result<Principal, String> {
+pub fn require_authenticated() -> Result<Principal, AanganError> {
     let caller = get_caller();
     if is_anonymous() {
-        return Err("Authentication required".to_string());
+        return Err(AanganError::unauthorized("Authentication required"));
+    }
+    Ok(caller)
+}
+
+/// Controllers are always administrators; further admins come from the init args.
+pub fn is_admin(principal: &Principal) -> bool {
+    ic_cdk::api::is_controller(principal) || config_store::get_config().is_admin(principal)
+}
+
+pub fn require_admin() -> Result<Principal, AanganError> {
+    let caller = require_authenticated()?;
+    if !is_admin(&caller) {
+        return Err(AanganError::unauthorized("Admin access required"));
+    }
+    Ok(caller)
+}
+
+/// Only controllers can change who else is an administrator.
+pub fn require_controller() -> Result<Principal, AanganError> {
+    let caller = require_authenticated()?;
+    if !ic_cdk::api::is_controller(&caller) {
+        return Err(AanganError::unauthorized("Controller access required"));
+    }
+    Ok(caller)
+}
+
+/// Like [`require_authenticated`], but also rejects suspended users. Used by endpoints that
+/// create listings, leases or profile changes; settling existing obligations (rent, deposits,
+/// cancellations) stays open to suspended users.
+pub fn require_active() -> Result<Principal, AanganError> {
+    let caller = require_authenticated()?;
+    if user_store::get_user(&caller).is_some_and(|user| user.is_suspended()) {
+        return Err(AanganError::unauthorized("Account suspended"));
     }
     Ok(caller)
 }
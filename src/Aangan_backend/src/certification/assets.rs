@@ -0,0 +1,329 @@
+//! Response certification for assets served by `http_request`, following
+//! `asset_util::CertifiedAssets` from the vendored internet-identity tree. Both the v1
+//! (`http_assets`) and v2 (`http_expr`) certification schemes are maintained.
+
+use crate::types::HeaderField;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ic_cdk::api::data_certificate;
+use ic_cdk::trap;
+use ic_certification::{
+    fork, fork_hash, labeled, labeled_hash, pruned, AsHashTree, Hash, HashTree, NestedTree, RbTree,
+};
+use ic_representation_independent_hash::{representation_independent_hash, Value};
+use serde::Serialize;
+use sha2::Digest;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+pub const IC_CERTIFICATE_HEADER: &str = "IC-Certificate";
+pub const IC_CERTIFICATE_EXPRESSION_HEADER: &str = "IC-CertificateExpression";
+pub const LABEL_ASSETS_V1: &str = "http_assets";
+pub const LABEL_ASSETS_V2: &str = "http_expr";
+pub const STATUS_CODE_PSEUDO_HEADER: &str = ":ic-cert-status";
+pub const EXACT_MATCH_TERMINATOR: &str = "<$>";
+pub const IC_CERTIFICATE_EXPRESSION: &str =
+    "default_certification(ValidationArgs{certification:Certification{no_request_certification: Empty{},\
+    response_certification:ResponseCertification{response_header_exclusions:ResponseHeaderList{headers:[]}}}})";
+
+static EXPR_HASH: LazyLock<Hash> =
+    LazyLock::new(|| sha2::Sha256::digest(IC_CERTIFICATE_EXPRESSION).into());
+
+const HTTP_OK_STATUS: u16 = 200;
+
+/// Assets together with their certification trees. [CertifiedAssets::root_hash] must be
+/// set as the canister's certified data for the certification to be valid.
+#[derive(Debug, Default, Clone)]
+pub struct CertifiedAssets {
+    assets: HashMap<String, CertifiedAsset>,
+    certification_v1: RbTree<String, Hash>,
+    certification_v2: NestedTree<Vec<u8>, Vec<u8>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CertifiedAsset {
+    pub status_code: u16,
+    pub headers: Vec<HeaderField>,
+    pub content: Vec<u8>,
+}
+
+impl CertifiedAssets {
+    /// Certifies a `200 OK` response for `url_path`, replacing any previous one.
+    pub fn certify_asset(&mut self, url_path: &str, content: Vec<u8>, headers: Vec<HeaderField>) {
+        let body_hash = sha2::Sha256::digest(&content).into();
+        self.add_certification_v1(url_path, body_hash);
+        self.add_certification_v2(url_path, HTTP_OK_STATUS, &headers, body_hash);
+        self.assets.insert(
+            url_path.to_string(),
+            CertifiedAsset {
+                status_code: HTTP_OK_STATUS,
+                headers,
+                content,
+            },
+        );
+    }
+
+    /// Certifies a `200 OK` response for `url_path` whose body is kept elsewhere, e.g. an
+    /// image streamed from stable memory. `body_hash` is the SHA-256 of the whole body.
+    pub fn certify_hashed_asset(
+        &mut self,
+        url_path: &str,
+        body_hash: Hash,
+        headers: &[HeaderField],
+    ) {
+        self.add_certification_v1(url_path, body_hash);
+        self.add_certification_v2(url_path, HTTP_OK_STATUS, headers, body_hash);
+    }
+
+    /// Removes the response certified for `url_path`, if any.
+    pub fn remove_asset(&mut self, url_path: &str) {
+        self.certification_v1.delete(url_path.as_bytes());
+        self.certification_v2.delete(&v2_segments(url_path));
+        self.assets.remove(url_path);
+    }
+
+    pub fn root_hash(&self) -> Hash {
+        fork_hash(
+            // NB: Labels added in lexicographic order.
+            &labeled_hash(
+                LABEL_ASSETS_V1.as_bytes(),
+                &self.certification_v1.root_hash(),
+            ),
+            &labeled_hash(
+                LABEL_ASSETS_V2.as_bytes(),
+                &self.certification_v2.root_hash(),
+            ),
+        )
+    }
+
+    /// Returns the asset with certificate headers for the highest certificate version not
+    /// above `max_certificate_version`. Version 1 is the default for legacy clients.
+    /// A canister that also issues canister signatures passes its pruned `sigs` subtree.
+    pub fn get_certified_asset(
+        &self,
+        url_path: &str,
+        max_certificate_version: Option<u16>,
+        sigs_tree: Option<HashTree>,
+    ) -> Option<CertifiedAsset> {
+        let mut certified_asset = self.assets.get(url_path).cloned()?;
+        match max_certificate_version {
+            Some(x) if x >= 2 => certified_asset
+                .headers
+                .extend(self.certificate_headers_v2(url_path, sigs_tree)),
+            _ => certified_asset
+                .headers
+                .extend(self.certificate_headers_v1(url_path, sigs_tree)),
+        }
+        Some(certified_asset)
+    }
+
+    /// Certificate headers for a response certified with [Self::certify_hashed_asset], or
+    /// `None` if nothing is certified for `url_path`.
+    pub fn get_certificate_headers(
+        &self,
+        url_path: &str,
+        max_certificate_version: Option<u16>,
+        sigs_tree: Option<HashTree>,
+    ) -> Option<Vec<HeaderField>> {
+        self.certification_v1.get(url_path.as_bytes())?;
+        Some(match max_certificate_version {
+            Some(x) if x >= 2 => self.certificate_headers_v2(url_path, sigs_tree),
+            _ => self.certificate_headers_v1(url_path, sigs_tree),
+        })
+    }
+
+    fn add_certification_v1(&mut self, absolute_path: &str, body_hash: Hash) {
+        self.certification_v1
+            .insert(absolute_path.to_string(), body_hash)
+    }
+
+    fn add_certification_v2(
+        &mut self,
+        absolute_path: &str,
+        status_code: u16,
+        headers: &[HeaderField],
+        body_hash: Hash,
+    ) {
+        let mut segments = v2_segments(absolute_path);
+        // delete the old certification subtree for the given path, if any
+        self.certification_v2.delete(&segments);
+        segments.push(Vec::from(EXPR_HASH.as_slice()));
+        segments.push(vec![]);
+        segments.push(Vec::from(response_hash(status_code, headers, &body_hash)));
+
+        self.certification_v2.insert(&segments, vec![])
+    }
+
+    fn witness_v1(&self, absolute_path: &str) -> HashTree {
+        let witness = self.certification_v1.witness(absolute_path.as_bytes());
+        fork(
+            labeled(LABEL_ASSETS_V1, witness),
+            pruned(labeled_hash(
+                LABEL_ASSETS_V2.as_bytes(),
+                &self.certification_v2.root_hash(),
+            )),
+        )
+    }
+
+    fn witness_v2(&self, absolute_path: &str) -> HashTree {
+        let witness = self.certification_v2.witness(&v2_segments(absolute_path));
+        fork(
+            pruned(labeled_hash(
+                LABEL_ASSETS_V1.as_bytes(),
+                &self.certification_v1.root_hash(),
+            )),
+            labeled(LABEL_ASSETS_V2.as_bytes(), witness),
+        )
+    }
+
+    fn certificate_headers_v1(
+        &self,
+        absolute_path: &str,
+        sigs_tree: Option<HashTree>,
+    ) -> Vec<HeaderField> {
+        let certificate = data_certificate().unwrap_or_else(|| {
+            trap("data certificate is only available in query calls");
+        });
+
+        let witness = self.witness_v1(absolute_path);
+        let tree = match sigs_tree {
+            Some(sigs) => fork(witness, sigs),
+            None => witness,
+        };
+        vec![(
+            IC_CERTIFICATE_HEADER.to_string(),
+            format!(
+                "certificate=:{}:, tree=:{}:",
+                BASE64.encode(certificate),
+                BASE64.encode(to_cbor(&tree))
+            ),
+        )]
+    }
+
+    fn certificate_headers_v2(
+        &self,
+        absolute_path: &str,
+        sigs_tree: Option<HashTree>,
+    ) -> Vec<HeaderField> {
+        let certificate = data_certificate().unwrap_or_else(|| {
+            trap("data certificate is only available in query calls");
+        });
+
+        let mut path: Vec<String> = absolute_path.split('/').map(str::to_string).collect();
+        // replace the first empty split segment (due to absolute path) with "http_expr"
+        path[0] = LABEL_ASSETS_V2.to_string();
+        path.push(EXACT_MATCH_TERMINATOR.to_string());
+
+        let witness = self.witness_v2(absolute_path);
+        let tree = match sigs_tree {
+            Some(sigs) => fork(witness, sigs),
+            None => witness,
+        };
+
+        vec![
+            (
+                IC_CERTIFICATE_HEADER.to_string(),
+                format!(
+                    "certificate=:{}:, tree=:{}:, expr_path=:{}:, version=2",
+                    BASE64.encode(certificate),
+                    BASE64.encode(to_cbor(&tree)),
+                    BASE64.encode(to_cbor(&path))
+                ),
+            ),
+            (
+                IC_CERTIFICATE_EXPRESSION_HEADER.to_string(),
+                IC_CERTIFICATE_EXPRESSION.to_string(),
+            ),
+        ]
+    }
+}
+
+/// Path segments of `absolute_path` in the v2 tree, ending with the exact match terminator.
+fn v2_segments(absolute_path: &str) -> Vec<Vec<u8>> {
+    assert!(absolute_path.starts_with('/'));
+    let mut segments: Vec<Vec<u8>> = absolute_path
+        .split('/')
+        .skip(1) // leading empty string due to absolute path
+        .map(|segment| segment.as_bytes().to_vec())
+        .collect();
+    segments.push(EXACT_MATCH_TERMINATOR.as_bytes().to_vec());
+    segments
+}
+
+fn to_cbor<T: Serialize>(value: &T) -> Vec<u8> {
+    let mut serializer = serde_cbor::ser::Serializer::new(vec![]);
+    serializer.self_describe().unwrap();
+    value
+        .serialize(&mut serializer)
+        .unwrap_or_else(|e| trap(&format!("failed to serialize certification data: {e}")));
+    serializer.into_inner()
+}
+
+fn response_hash(status_code: u16, headers: &[HeaderField], body_hash: &Hash) -> Hash {
+    let mut response_metadata: HashMap<String, Value> = headers
+        .iter()
+        .map(|(header, value)| (header.to_ascii_lowercase(), Value::String(value.clone())))
+        .collect();
+    response_metadata.insert(
+        IC_CERTIFICATE_EXPRESSION_HEADER.to_ascii_lowercase(),
+        Value::String(IC_CERTIFICATE_EXPRESSION.to_string()),
+    );
+    response_metadata.insert(
+        STATUS_CODE_PSEUDO_HEADER.to_string(),
+        Value::Number(status_code as u64),
+    );
+    let mut response_metadata_hash: Vec<u8> =
+        representation_independent_hash(&response_metadata.into_iter().collect::<Vec<_>>()).into();
+    response_metadata_hash.extend_from_slice(body_hash);
+    sha2::Sha256::digest(&response_metadata_hash).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_certification::LookupResult;
+
+    fn image_headers() -> Vec<HeaderField> {
+        vec![("Content-Type".to_string(), "image/png".to_string())]
+    }
+
+    #[test]
+    fn hashed_assets_are_certified_in_both_trees() {
+        let body_hash: Hash = sha2::Sha256::digest(b"png bytes").into();
+        let mut assets = CertifiedAssets::default();
+        assets.certify_hashed_asset("/images/7", body_hash, &image_headers());
+
+        let v1 = assets.witness_v1("/images/7");
+        assert_eq!(v1.digest(), assets.root_hash());
+        assert_eq!(
+            v1.lookup_path([LABEL_ASSETS_V1.as_bytes(), b"/images/7".as_slice()]),
+            LookupResult::Found(body_hash.as_slice())
+        );
+
+        let v2 = assets.witness_v2("/images/7");
+        assert_eq!(v2.digest(), assets.root_hash());
+        let response_hash = response_hash(HTTP_OK_STATUS, &image_headers(), &body_hash);
+        let path: [&[u8]; 7] = [
+            LABEL_ASSETS_V2.as_bytes(),
+            b"images",
+            b"7",
+            EXACT_MATCH_TERMINATOR.as_bytes(),
+            EXPR_HASH.as_slice(),
+            b"",
+            response_hash.as_slice(),
+        ];
+        assert_eq!(v2.lookup_path(path), LookupResult::Found(b""));
+        // The content itself is not held on the heap
+        assert!(assets
+            .get_certified_asset("/images/7", None, None)
+            .is_none());
+    }
+
+    #[test]
+    fn uncertified_paths_have_no_certificate_headers() {
+        let assets = CertifiedAssets::default();
+        assert!(assets
+            .get_certificate_headers("/images/7", Some(2), None)
+            .is_none());
+    }
+}
@@ -0,0 +1,277 @@
+//! Certified HTTP documents for rental NFTs and uploaded images, and canister signatures.
+//!
+//! Every NFT is published as `/nft/{id}.json` and `/nft/{id}.svg`. The documents are kept in
+//! a heap-only certification tree whose root hash is the canister's certified data, so the
+//! tree is rebuilt from stable storage on init and upgrade. After an upgrade it is rebuilt
+//! in batches, one per message, so documents are served again as their batch is certified.
+//! Images stay in stable memory; only the hash of each committed image is added to the tree.
+//!
+//! Canister signatures live next to the documents under the `sig` label, as in the vendored
+//! internet-identity canister. A signature is added in an update call and can be fetched in
+//! query calls for a minute afterwards; the map is not persisted across upgrades.
+
+mod assets;
+mod nft_documents;
+
+use crate::storage::{image_store, rental_store};
+use crate::types::{HeaderField, ImageAsset, NFTMetadata};
+use assets::{CertifiedAsset, CertifiedAssets};
+use ic_canister_sig_creation::signature_map::{
+    CanisterSigError, CanisterSigInputs, SignatureMap, LABEL_SIG,
+};
+use ic_certification::{fork_hash, labeled_hash, pruned, Hash};
+use std::cell::RefCell;
+
+pub use nft_documents::{canister_url, json_path, svg_path};
+
+thread_local! {
+    static CERTIFIED_ASSETS: RefCell<CertifiedAssets> = RefCell::new(CertifiedAssets::default());
+    static SIGNATURES: RefCell<SignatureMap> = RefCell::new(SignatureMap::default());
+}
+
+fn sigs_root_hash() -> Hash {
+    SIGNATURES.with(|sigs| labeled_hash(LABEL_SIG, &sigs.borrow().root_hash()))
+}
+
+fn update_certified_data() {
+    let assets_root_hash = CERTIFIED_ASSETS.with(|assets| assets.borrow().root_hash());
+    // NB: The asset labels sort before `sig`.
+    let root_hash = fork_hash(&assets_root_hash, &sigs_root_hash());
+    set_certified_data(&root_hash);
+}
+
+#[cfg(target_arch = "wasm32")]
+fn set_certified_data(root_hash: &Hash) {
+    ic_cdk::api::set_certified_data(root_hash);
+}
+
+/// Unit tests run outside a canister, where the tree is kept but there is nothing to certify.
+#[cfg(not(target_arch = "wasm32"))]
+fn set_certified_data(_root_hash: &Hash) {}
+
+fn insert_nft_documents(assets: &mut CertifiedAssets, nft: &NFTMetadata) {
+    for (path, content, content_type) in nft_documents::render(nft) {
+        assets.certify_asset(&path, content, nft_documents::headers(content_type));
+    }
+}
+
+/// Re-certifies the documents of a newly minted or changed NFT.
+pub fn certify_nft(nft: &NFTMetadata) {
+    CERTIFIED_ASSETS.with(|assets| insert_nft_documents(&mut assets.borrow_mut(), nft));
+    update_certified_data();
+}
+
+fn insert_image(assets: &mut CertifiedAssets, image: &ImageAsset) {
+    let body_hash = image
+        .sha256
+        .as_slice()
+        .try_into()
+        .unwrap_or_else(|_| ic_cdk::trap("image hash is not 32 bytes"));
+    assets.certify_hashed_asset(&ImageAsset::url(image.id), body_hash, &image_headers(image));
+}
+
+/// Certifies a newly committed image.
+pub fn certify_image(image: &ImageAsset) {
+    CERTIFIED_ASSETS.with(|assets| insert_image(&mut assets.borrow_mut(), image));
+    update_certified_data();
+}
+
+/// Stops serving a deleted image.
+pub fn uncertify_image(id: u64) {
+    CERTIFIED_ASSETS.with(|assets| assets.borrow_mut().remove_asset(&ImageAsset::url(id)));
+    update_certified_data();
+}
+
+/// Rebuilds the certification tree for all stored NFTs and images.
+pub fn certify_all() {
+    CERTIFIED_ASSETS.with(|assets| {
+        let mut assets = assets.borrow_mut();
+        *assets = CertifiedAssets::default();
+        for nft in rental_store::get_all_nfts() {
+            insert_nft_documents(&mut assets, &nft);
+        }
+        for image in image_store::get_all_assets() {
+            insert_image(&mut assets, &image);
+        }
+    });
+    update_certified_data();
+}
+
+/// NFTs or images certified per message while the tree is rebuilt after an upgrade.
+const REBUILD_BATCH_SIZE: usize = 200;
+
+/// Position of a rebuild: the last NFT or image certified so far.
+enum RebuildCursor {
+    Nfts(Option<u64>),
+    Images(Option<u64>),
+}
+
+/// Certifies one batch and returns where the next one starts, or `None` when done.
+fn rebuild_step(cursor: RebuildCursor, batch_size: usize) -> Option<RebuildCursor> {
+    let next = CERTIFIED_ASSETS.with(|assets| {
+        let mut assets = assets.borrow_mut();
+        match cursor {
+            RebuildCursor::Nfts(after) => {
+                let batch = rental_store::get_nft_batch(after, batch_size);
+                for nft in &batch {
+                    insert_nft_documents(&mut assets, nft);
+                }
+                Some(match batch.last() {
+                    Some(last) if batch.len() == batch_size => RebuildCursor::Nfts(Some(last.id)),
+                    _ => RebuildCursor::Images(None),
+                })
+            }
+            RebuildCursor::Images(after) => {
+                let batch = image_store::get_asset_batch(after, batch_size);
+                for image in &batch {
+                    insert_image(&mut assets, image);
+                }
+                match batch.last() {
+                    Some(last) if batch.len() == batch_size => {
+                        Some(RebuildCursor::Images(Some(last.id)))
+                    }
+                    _ => None,
+                }
+            }
+        }
+    });
+    update_certified_data();
+    next
+}
+
+/// Clears the tree and certifies every stored NFT and image again, in timer-driven batches.
+/// Documents minted or changed meanwhile are certified as usual.
+#[cfg(feature = "timers")]
+pub fn start_rebuild() {
+    CERTIFIED_ASSETS.with(|assets| *assets.borrow_mut() = CertifiedAssets::default());
+    schedule_rebuild(RebuildCursor::Nfts(None));
+}
+
+#[cfg(feature = "timers")]
+fn schedule_rebuild(cursor: RebuildCursor) {
+    ic_cdk_timers::set_timer(std::time::Duration::ZERO, move || {
+        if let Some(next) = rebuild_step(cursor, REBUILD_BATCH_SIZE) {
+            schedule_rebuild(next);
+        }
+    });
+}
+
+#[cfg(not(feature = "timers"))]
+pub fn start_rebuild() {
+    CERTIFIED_ASSETS.with(|assets| *assets.borrow_mut() = CertifiedAssets::default());
+    let mut cursor = Some(RebuildCursor::Nfts(None));
+    while let Some(current) = cursor {
+        cursor = rebuild_step(current, REBUILD_BATCH_SIZE);
+    }
+}
+
+/// Headers served with an image. They are part of the certified response, so they must not
+/// depend on the request.
+pub fn image_headers(image: &ImageAsset) -> Vec<HeaderField> {
+    vec![
+        ("Content-Type".to_string(), image.content_type.clone()),
+        ("Content-Length".to_string(), image.size.to_string()),
+        // Asset ids are never reused, so the content behind a URL cannot change.
+        (
+            "Cache-Control".to_string(),
+            "public, max-age=31536000, immutable".to_string(),
+        ),
+        (
+            "ETag".to_string(),
+            format!("\"{}\"", hex::encode(&image.sha256)),
+        ),
+    ]
+}
+
+pub fn get_certified_asset(
+    url_path: &str,
+    certificate_version: Option<u16>,
+) -> Option<CertifiedAsset> {
+    CERTIFIED_ASSETS.with(|assets| {
+        assets.borrow().get_certified_asset(
+            url_path,
+            certificate_version,
+            Some(pruned(sigs_root_hash())),
+        )
+    })
+}
+
+/// Certificate headers for a response whose body is not held in the tree, such as an image.
+pub fn get_certificate_headers(
+    url_path: &str,
+    certificate_version: Option<u16>,
+) -> Option<Vec<HeaderField>> {
+    CERTIFIED_ASSETS.with(|assets| {
+        assets.borrow().get_certificate_headers(
+            url_path,
+            certificate_version,
+            Some(pruned(sigs_root_hash())),
+        )
+    })
+}
+
+/// Signs `inputs` with the canister's signature key for the seed in `inputs`.
+pub fn add_signature(inputs: &CanisterSigInputs) {
+    SIGNATURES.with(|sigs| sigs.borrow_mut().add_signature(inputs));
+    update_certified_data();
+}
+
+/// Returns the CBOR-encoded signature previously added for `inputs`. Only available in
+/// query calls.
+pub fn get_signature(inputs: &CanisterSigInputs) -> Result<Vec<u8>, CanisterSigError> {
+    let assets_root_hash = CERTIFIED_ASSETS.with(|assets| assets.borrow().root_hash());
+    SIGNATURES.with(|sigs| {
+        sigs.borrow()
+            .get_signature_as_cbor(inputs, Some(assets_root_hash))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rental_core::nft_minter;
+    use candid::Principal;
+
+    fn root_hash() -> Hash {
+        CERTIFIED_ASSETS.with(|assets| assets.borrow().root_hash())
+    }
+
+    #[test]
+    fn batched_rebuild_certifies_the_same_tree() {
+        let owner = Principal::from_slice(&[2]);
+        for id in 1..=5 {
+            rental_store::create_nft(nft_minter::create_rental_nft(
+                id,
+                Principal::from_slice(&[3]),
+                1,
+                id,
+                "Flat".to_string(),
+                "1 Main Road".to_string(),
+                String::new(),
+                100,
+                0,
+                1,
+            ))
+            .unwrap();
+        }
+        for _ in 0..3 {
+            let session = image_store::start_upload(owner, "image/png".to_string(), 3, 0).unwrap();
+            image_store::put_chunk(session.id, 0, vec![1, 2, 3], &owner).unwrap();
+            certify_image(&image_store::commit_upload(session.id, None, &owner).unwrap());
+        }
+        certify_all();
+        let expected = root_hash();
+
+        CERTIFIED_ASSETS.with(|assets| *assets.borrow_mut() = CertifiedAssets::default());
+        let mut cursor = Some(RebuildCursor::Nfts(None));
+        let mut steps = 0;
+        while let Some(current) = cursor {
+            assert_ne!(root_hash(), expected);
+            cursor = rebuild_step(current, 2);
+            steps += 1;
+        }
+
+        assert_eq!(root_hash(), expected);
+        assert_eq!(steps, 5);
+    }
+}
@@ -0,0 +1,134 @@
+//! `token_uri`-style JSON and SVG documents rendered from `NFTMetadata`.
+
+use crate::types::{HeaderField, NFTMetadata};
+use candid::Principal;
+use serde_json::json;
+
+const JSON_CONTENT_TYPE: &str = "application/json";
+const SVG_CONTENT_TYPE: &str = "image/svg+xml";
+/// Longest line drawn on the SVG card before it is cut off with an ellipsis.
+const SVG_LINE_CHARS: usize = 44;
+
+pub fn json_path(id: u64) -> String {
+    format!("/nft/{}.json", id)
+}
+
+pub fn svg_path(id: u64) -> String {
+    format!("/nft/{}.svg", id)
+}
+
+/// Absolute URL of a path served by this canister.
+pub fn canister_url(path: &str) -> String {
+    format!("https://{}.icp0.io{}", canister_id(), path)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn canister_id() -> Principal {
+    ic_cdk::id()
+}
+
+/// Outside a canister, e.g. in unit tests, documents link to the anonymous principal.
+#[cfg(not(target_arch = "wasm32"))]
+fn canister_id() -> Principal {
+    Principal::anonymous()
+}
+
+pub fn headers(content_type: &str) -> Vec<HeaderField> {
+    vec![
+        ("Content-Type".to_string(), content_type.to_string()),
+        ("Access-Control-Allow-Origin".to_string(), "*".to_string()),
+        // Documents change when the NFT does, so clients must revalidate.
+        ("Cache-Control".to_string(), "no-cache".to_string()),
+    ]
+}
+
+/// Both documents of `nft` as `(url_path, content, content_type)`.
+pub fn render(nft: &NFTMetadata) -> Vec<(String, Vec<u8>, &'static str)> {
+    vec![
+        (json_path(nft.id), render_json(nft), JSON_CONTENT_TYPE),
+        (svg_path(nft.id), render_svg(nft), SVG_CONTENT_TYPE),
+    ]
+}
+
+fn render_json(nft: &NFTMetadata) -> Vec<u8> {
+    let property_image = if nft.image.starts_with('/') {
+        canister_url(&nft.image)
+    } else {
+        nft.image.clone()
+    };
+
+    let document = json!({
+        "name": nft.name,
+        "description": nft.description,
+        "image": canister_url(&svg_path(nft.id)),
+        "attributes": nft
+            .attributes
+            .iter()
+            .map(|attribute| json!({
+                "trait_type": attribute.trait_type,
+                "value": attribute.value,
+            }))
+            .collect::<Vec<_>>(),
+        "properties": {
+            "owner": nft.owner.to_text(),
+            "property_id": nft.property_id,
+            "rental_agreement_id": nft.rental_agreement_id,
+            "property_image": property_image,
+            "created_at": nft.created_at,
+        },
+    });
+    serde_json::to_vec(&document).unwrap_or_default()
+}
+
+fn render_svg(nft: &NFTMetadata) -> Vec<u8> {
+    let mut lines = vec![format!("Lease NFT #{}", nft.id), nft.name.clone()];
+    lines.extend(
+        nft.attributes
+            .iter()
+            .map(|attribute| format!("{}: {}", attribute.trait_type, attribute.value)),
+    );
+
+    let text: String = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let (size, weight) = if i == 0 { (24, "bold") } else { (15, "normal") };
+            format!(
+                r#"<text x="24" y="{}" font-size="{}" font-weight="{}">{}</text>"#,
+                48 + i * 36,
+                size,
+                weight,
+                escape_xml(&truncate(line, SVG_LINE_CHARS))
+            )
+        })
+        .collect();
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="480" height="{}" font-family="sans-serif" fill="#1f2937"><rect width="100%" height="100%" rx="16" fill="#fef3c7"/>{}</svg>"##,
+        48 + lines.len() * 36,
+        text
+    )
+    .into_bytes()
+}
+
+fn truncate(line: &str, max_chars: usize) -> String {
+    if line.chars().count() <= max_chars {
+        return line.to_string();
+    }
+    let mut truncated: String = line.chars().take(max_chars - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+fn escape_xml(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&apos;".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
@@ -0,0 +1,17 @@
+//! Current time, readable outside a canister so that unit tests can exercise code that
+//! timestamps records.
+
+/// Nanoseconds since the Unix epoch.
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> u64 {
+    ic_cdk::api::time()
+}
+
+/// Nanoseconds since the Unix epoch.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("system clock is before the Unix epoch")
+        .as_nanos() as u64
+}
@@ -0,0 +1,71 @@
+use crate::types::AanganError;
+use candid::{Nat, Principal};
+use icrc_ledger_types::icrc1::account::{Account, Subaccount};
+use icrc_ledger_types::icrc1::transfer::{TransferArg, TransferError};
+use icrc_ledger_types::icrc2::transfer_from::{TransferFromArgs, TransferFromError};
+
+fn nat_to_u64(value: Nat) -> Result<u64, AanganError> {
+    u64::try_from(value.0).map_err(|_| AanganError::ledger("Ledger value does not fit in u64"))
+}
+
+pub async fn fee(ledger: Principal) -> Result<u64, AanganError> {
+    let (fee,): (Nat,) = ic_cdk::call(ledger, "icrc1_fee", ())
+        .await
+        .map_err(|(code, msg)| {
+            AanganError::ledger(format!("Ledger call failed ({:?}): {}", code, msg))
+        })?;
+    nat_to_u64(fee)
+}
+
+pub async fn transfer_from(
+    ledger: Principal,
+    from: Account,
+    to: Account,
+    amount: u64,
+) -> Result<u64, AanganError> {
+    let args = TransferFromArgs {
+        spender_subaccount: None,
+        from,
+        to,
+        amount: Nat::from(amount),
+        fee: None,
+        memo: None,
+        created_at_time: Some(ic_cdk::api::time()),
+    };
+
+    let (result,): (Result<Nat, TransferFromError>,) =
+        ic_cdk::call(ledger, "icrc2_transfer_from", (args,))
+            .await
+            .map_err(|(code, msg)| {
+                AanganError::ledger(format!("Ledger call failed ({:?}): {}", code, msg))
+            })?;
+
+    let block =
+        result.map_err(|e| AanganError::ledger(format!("Deposit transfer failed: {}", e)))?;
+    nat_to_u64(block)
+}
+
+pub async fn transfer(
+    ledger: Principal,
+    from_subaccount: Subaccount,
+    to: Account,
+    amount: u64,
+) -> Result<u64, AanganError> {
+    let args = TransferArg {
+        from_subaccount: Some(from_subaccount),
+        to,
+        fee: None,
+        created_at_time: Some(ic_cdk::api::time()),
+        memo: None,
+        amount: Nat::from(amount),
+    };
+
+    let (result,): (Result<Nat, TransferError>,) = ic_cdk::call(ledger, "icrc1_transfer", (args,))
+        .await
+        .map_err(|(code, msg)| {
+            AanganError::ledger(format!("Ledger call failed ({:?}): {}", code, msg))
+        })?;
+
+    let block = result.map_err(|e| AanganError::ledger(format!("Deposit payout failed: {}", e)))?;
+    nat_to_u64(block)
+}
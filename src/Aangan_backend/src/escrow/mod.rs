@@ -0,0 +1,293 @@
+//! Security-deposit escrow.
+//!
+//! On confirmation the tenant's deposit is pulled with ICRC-2 `transfer_from` into a
+//! subaccount of this canister derived from the rental id. The deposit stays there until
+//! it is released to the landlord or refunded to the tenant with an ICRC-1 `transfer`.
+//!
+//! The deposit belongs to the tenant who funded it. When the lease is assigned to a new
+//! tenant it stays in escrow for the rest of the lease and is still refunded to, and can
+//! only be released by, the original depositor.
+
+use candid::Principal;
+use icrc_ledger_types::icrc1::account::{Account, Subaccount};
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+
+use crate::rental_core::termination;
+use crate::storage::{config_store, rental_store};
+use crate::types::{AanganError, Escrow, EscrowStatus, RentalAgreement, RentalStatus};
+
+pub mod ledger;
+
+const SUBACCOUNT_DOMAIN: &[u8] = b"aangan-deposit";
+
+thread_local! {
+    static IN_FLIGHT: RefCell<BTreeSet<u64>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+/// Prevents concurrent ledger operations on the same rental across `await` points.
+pub struct LedgerGuard {
+    rental_id: u64,
+}
+
+impl LedgerGuard {
+    pub fn acquire(rental_id: u64) -> Result<Self, AanganError> {
+        IN_FLIGHT.with(|in_flight| {
+            if !in_flight.borrow_mut().insert(rental_id) {
+                return Err(AanganError::conflict(
+                    "A ledger operation is already in progress for this rental",
+                ));
+            }
+            Ok(Self { rental_id })
+        })
+    }
+}
+
+impl Drop for LedgerGuard {
+    fn drop(&mut self) {
+        IN_FLIGHT.with(|in_flight| {
+            in_flight.borrow_mut().remove(&self.rental_id);
+        });
+    }
+}
+
+pub fn is_locked(rental_id: u64) -> bool {
+    IN_FLIGHT.with(|in_flight| in_flight.borrow().contains(&rental_id))
+}
+
+pub fn deposit_subaccount(rental_id: u64) -> Subaccount {
+    let mut subaccount = [0u8; 32];
+    subaccount[..SUBACCOUNT_DOMAIN.len()].copy_from_slice(SUBACCOUNT_DOMAIN);
+    subaccount[24..].copy_from_slice(&rental_id.to_be_bytes());
+    subaccount
+}
+
+pub fn deposit_account(rental_id: u64) -> Account {
+    Account {
+        owner: ic_cdk::id(),
+        subaccount: Some(deposit_subaccount(rental_id)),
+    }
+}
+
+pub fn ledger_canister() -> Result<Principal, AanganError> {
+    config_store::get_config()
+        .ledger_canister_id
+        .ok_or_else(|| AanganError::ledger("Escrow ledger is not configured"))
+}
+
+/// Pulls the deposit of `rental` from the tenant into the rental's deposit subaccount.
+/// Returns `None` when the agreement carries no deposit.
+pub async fn fund_deposit(rental: &RentalAgreement) -> Result<Option<Escrow>, AanganError> {
+    if rental.deposit_amount == 0 {
+        return Ok(None);
+    }
+
+    let ledger = ledger_canister()?;
+    let block = ledger::transfer_from(
+        ledger,
+        Account::from(rental.tenant),
+        deposit_account(rental.id),
+        rental.deposit_amount,
+    )
+    .await?;
+
+    Ok(Some(Escrow::new(
+        rental.tenant,
+        rental.deposit_amount,
+        block,
+    )))
+}
+
+/// The principal who funded the rental's deposit, if it has one.
+pub fn depositor(rental: &RentalAgreement) -> Option<Principal> {
+    rental
+        .escrow
+        .as_ref()
+        .map(|escrow| escrow.depositor_or(rental.tenant))
+}
+
+/// Pays the deposit out to the landlord. Only the depositor can release it.
+pub async fn release_deposit(
+    rental_id: u64,
+    caller: Principal,
+) -> Result<RentalAgreement, AanganError> {
+    let rental = load_open_escrow(rental_id)?;
+
+    if depositor(&rental) != Some(caller) {
+        return Err(AanganError::unauthorized(
+            "Only tenant can release the deposit",
+        ));
+    }
+
+    settle(rental, EscrowStatus::Released).await
+}
+
+/// Returns the deposit to the depositor. The landlord can refund at any time; the depositor
+/// can reclaim an undisputed deposit once the rental has been cancelled.
+pub async fn refund_deposit(
+    rental_id: u64,
+    caller: Principal,
+) -> Result<RentalAgreement, AanganError> {
+    let rental = load_open_escrow(rental_id)?;
+    let escrow_status = rental.escrow.as_ref().map(|escrow| escrow.status.clone());
+
+    let tenant_may_reclaim = depositor(&rental) == Some(caller)
+        && rental.status == RentalStatus::Cancelled
+        && escrow_status == Some(EscrowStatus::Funded);
+
+    if rental.landlord != caller && !tenant_may_reclaim {
+        return Err(AanganError::unauthorized(
+            "Only landlord can refund the deposit",
+        ));
+    }
+
+    // A termination penalty the tenant still owes is paid out of the deposit first
+    let rental = if termination::is_penalty_covered_by_deposit(&rental) {
+        termination::settle_penalty_from_deposit(rental_id).await?
+    } else {
+        rental
+    };
+
+    settle(rental, EscrowStatus::Refunded).await
+}
+
+/// Returns the deposit to the depositor on behalf of the platform, e.g. when a rental is
+/// cancelled by an administrator or its confirmation cannot be completed. Callers are
+/// responsible for authorization.
+pub async fn refund(rental_id: u64) -> Result<RentalAgreement, AanganError> {
+    let rental = load_open_escrow(rental_id)?;
+    settle(rental, EscrowStatus::Refunded).await
+}
+
+/// Pays `amount` out of a funded deposit to `recipient`, e.g. a termination penalty owed by
+/// the tenant. The ledger fee is taken from the deposit as well, and the rest stays in
+/// escrow. Returns the block index of the transfer.
+pub async fn deduct(rental_id: u64, recipient: Principal, amount: u64) -> Result<u64, AanganError> {
+    let rental = load_open_escrow(rental_id)?;
+    let _guard = LedgerGuard::acquire(rental_id)?;
+
+    let escrow = rental
+        .escrow
+        .ok_or_else(|| AanganError::not_found("Escrow", rental_id))?;
+    if escrow.status != EscrowStatus::Funded {
+        return Err(AanganError::invalid_state(
+            "Funded",
+            format!("{:?}", escrow.status),
+        ));
+    }
+
+    let ledger = ledger_canister()?;
+    let fee = ledger::fee(ledger).await?;
+    if escrow.amount < amount.saturating_add(fee) {
+        return Err(AanganError::ledger("Deposit does not cover the deduction"));
+    }
+
+    let block = ledger::transfer(
+        ledger,
+        deposit_subaccount(rental_id),
+        Account::from(recipient),
+        amount,
+    )
+    .await?;
+
+    // Re-read the agreement: it may have changed while the transfer was in flight.
+    let mut rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+    let mut escrow = escrow;
+    escrow.deduct(amount + fee);
+    rental.set_escrow(escrow);
+    rental_store::update_rental(rental)?;
+
+    Ok(block)
+}
+
+/// Freezes the deposit so the tenant can no longer reclaim it unilaterally.
+pub fn dispute_deposit(rental_id: u64, caller: Principal) -> Result<RentalAgreement, AanganError> {
+    let mut rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+
+    if rental.landlord != caller && rental.tenant != caller {
+        return Err(AanganError::unauthorized(
+            "Only landlord or tenant can dispute the deposit",
+        ));
+    }
+
+    if is_locked(rental_id) {
+        return Err(AanganError::conflict(
+            "A ledger operation is already in progress for this rental",
+        ));
+    }
+
+    let escrow = rental
+        .escrow
+        .as_mut()
+        .ok_or_else(|| AanganError::not_found("Escrow", rental_id))?;
+
+    if escrow.status != EscrowStatus::Funded {
+        return Err(AanganError::invalid_state(
+            "Funded",
+            format!("{:?}", escrow.status),
+        ));
+    }
+
+    escrow.dispute();
+    rental.updated_at = crate::clock::now();
+    rental_store::update_rental(rental.clone())?;
+
+    Ok(rental)
+}
+
+fn load_open_escrow(rental_id: u64) -> Result<RentalAgreement, AanganError> {
+    let rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+
+    match &rental.escrow {
+        Some(escrow) if escrow.is_open() => Ok(rental),
+        Some(escrow) => Err(AanganError::invalid_state(
+            "Funded or Disputed",
+            format!("{:?}", escrow.status),
+        )),
+        None => Err(AanganError::not_found("Escrow", rental_id)),
+    }
+}
+
+async fn settle(
+    rental: RentalAgreement,
+    outcome: EscrowStatus,
+) -> Result<RentalAgreement, AanganError> {
+    let _guard = LedgerGuard::acquire(rental.id)?;
+
+    let escrow = rental
+        .escrow
+        .clone()
+        .ok_or_else(|| AanganError::not_found("Escrow", rental.id))?;
+
+    let recipient = match outcome {
+        EscrowStatus::Released => rental.landlord,
+        _ => escrow.depositor_or(rental.tenant),
+    };
+
+    let ledger = ledger_canister()?;
+    let fee = ledger::fee(ledger).await?;
+    if escrow.amount <= fee {
+        return Err(AanganError::ledger("Deposit does not cover the ledger fee"));
+    }
+
+    let block = ledger::transfer(
+        ledger,
+        deposit_subaccount(rental.id),
+        Account::from(recipient),
+        escrow.amount - fee,
+    )
+    .await?;
+
+    // Re-read the agreement: it may have changed while the transfer was in flight.
+    let mut rental = rental_store::get_rental(rental.id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental.id))?;
+    let mut escrow = escrow;
+    escrow.settle(outcome, block);
+    rental.set_escrow(escrow);
+    rental_store::update_rental(rental.clone())?;
+
+    Ok(rental)
+}
@@ -1,20 +1,38 @@
+use candid::{Nat, Principal};
 use ic_cdk_macros::*;
+use icrc_ledger_types::icrc::generic_value::ICRC3Map;
+use icrc_ledger_types::icrc1::account::Account;
+use serde_bytes::ByteBuf;
 
 mod api;
 mod auth;
+mod certification;
+mod clock;
+mod escrow;
 mod rental_core;
 mod storage;
 mod types;
 
 // Re-export types for Candid interface
 pub use types::{
-    NFTAttribute, NFTMetadata, Property, PropertyType, RentalAgreement, RentalStatus, Role, User,
+    AanganError, Acceptance, AssignmentStatus, AttestedStatus, DateRange, Escrow, EscrowStatus,
+    GeoPoint, HttpRequest, HttpResponse, ImageAsset, InitArgs, LeaseAssignment, LeaseAttestation,
+    LeaseRenewal, LeaseTerms, ModerationAction, ModerationEntry, NFTAttribute, NFTEvent,
+    NFTEventKind, NFTMetadata, NFTStatus, NearbyProperty, PaymentStatus, PreparedLeaseAttestation,
+    ProfileVisibility, Property, PropertyType, PublicUser, RenewalStatus, RentPayment,
+    RentalAgreement, RentalStatus, Role, SearchCursor, SearchPage, SearchQuery,
+    SignedLeaseAttestation, SortField, SortOrder, StreamingCallbackHttpResponse,
+    StreamingCallbackToken, SupportedStandard, Termination, TerminationPenalty, TerminationTerms,
+    UploadSession, User, Visibility,
 };
 
 #[init]
-fn init() {
+fn init(args: Option<InitArgs>) {
     // Initialize storage
     storage::init_storage();
+    storage::config_store::apply_init_args(args.unwrap_or_default());
+    certification::certify_all();
+    rental_core::lifecycle::arm_timers();
 }
 
 #[pre_upgrade]
@@ -23,12 +41,61 @@ fn pre_upgrade() {
 }
 
 #[post_upgrade]
-fn post_upgrade() {
-    storage::post_upgrade();
+fn post_upgrade(args: Option<InitArgs>) {
+    let schema_outdated = storage::post_upgrade();
+    if let Some(args) = args {
+        storage::config_store::apply_init_args(args);
+    }
+    if schema_outdated {
+        rental_core::nft_minter::backfill_status();
+    }
+    // The certification tree lives on the heap
+    certification::start_rebuild();
+    // Timers do not survive upgrades
+    rental_core::lifecycle::arm_timers();
 }
 
 // Export all API methods
+pub use api::admin_api::*;
+pub use api::attestation_api::*;
+pub use api::escrow_api::*;
+pub use api::http_api::*;
+pub use api::icrc7_api::*;
+pub use api::image_api::*;
 pub use api::nft_api::*;
+pub use api::payment_api::*;
 pub use api::property_api::*;
 pub use api::rental_api::*;
 pub use api::user_api::*;
+
+// Candid interface generated from the annotated endpoints above
+#[query(name = "__get_candid_interface_tmp_hack")]
+fn export_candid() -> String {
+    __export_service()
+}
+
+candid::export_service!();
+
+#[cfg(test)]
+mod test {
+    use crate::__export_service;
+    use candid_parser::utils::{service_equal, CandidSource};
+    use std::path::Path;
+
+    /// Checks that the canister interface is compatible with the committed candid file.
+    /// After changing an endpoint, regenerate `Aangan_backend.did` from `__export_service()`.
+    #[test]
+    fn check_candid_interface_compatibility() {
+        let canister_interface = __export_service();
+        service_equal(
+            CandidSource::Text(&canister_interface),
+            CandidSource::File(Path::new("Aangan_backend.did")),
+        )
+        .unwrap_or_else(|e| {
+            panic!(
+                "the canister code interface is not equal to the did file: {:?}",
+                e
+            )
+        });
+    }
+}
@@ -0,0 +1,302 @@
+//! Two-sided acceptance of lease terms.
+//!
+//! The landlord attaches the lease document's hash and clauses to an application, and both
+//! parties sign the resulting terms hash before the landlord can accept the application.
+//! The hash covers every term the parties agree to, so an acceptance recorded against it
+//! shows exactly what was signed.
+
+use candid::{CandidType, Principal};
+use sha2::Digest;
+
+use crate::escrow;
+use crate::storage::rental_store;
+use crate::types::{
+    AanganError, Acceptance, LeaseTerms, RentalAgreement, RentalStatus, TerminationTerms,
+};
+
+/// Longest accepted clause list.
+pub const MAX_CLAUSES: usize = 100;
+
+/// Longest accepted clause.
+pub const MAX_CLAUSE_LENGTH: usize = 2_000;
+
+/// Separates terms hashes from any other SHA-256 hash of the same bytes.
+const TERMS_HASH_DOMAIN: &[u8] = b"aangan-lease-terms";
+
+/// Everything the parties agree to when they sign.
+#[derive(CandidType)]
+struct SignedTerms {
+    rental_id: u64,
+    property_id: u64,
+    landlord: Principal,
+    tenant: Principal,
+    start_date: u64,
+    end_date: u64,
+    rent_amount: u64,
+    deposit_amount: u64,
+    termination_terms: TerminationTerms,
+    terms: Option<LeaseTerms>,
+}
+
+pub fn terms_hash(rental: &RentalAgreement) -> [u8; 32] {
+    let signed = SignedTerms {
+        rental_id: rental.id,
+        property_id: rental.property_id,
+        landlord: rental.landlord,
+        tenant: rental.tenant,
+        start_date: rental.start_date,
+        end_date: rental.end_date,
+        rent_amount: rental.rent_amount,
+        deposit_amount: rental.deposit_amount,
+        termination_terms: rental.termination_terms(),
+        terms: rental.terms.clone(),
+    };
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(TERMS_HASH_DOMAIN);
+    hasher.update(candid::encode_one(&signed).expect("Failed to encode lease terms"));
+    hasher.finalize().into()
+}
+
+fn get_application(rental_id: u64, caller: Principal) -> Result<RentalAgreement, AanganError> {
+    let rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+
+    if rental.landlord != caller && rental.tenant != caller {
+        return Err(AanganError::unauthorized("Access denied"));
+    }
+
+    if rental.status != RentalStatus::Requested {
+        return Err(AanganError::invalid_state(
+            "Requested",
+            format!("{:?}", rental.status),
+        ));
+    }
+
+    Ok(rental)
+}
+
+/// Attaches the lease document to an application. Earlier acceptances no longer apply.
+pub fn set_lease_terms(
+    rental_id: u64,
+    terms: LeaseTerms,
+    caller: Principal,
+) -> Result<RentalAgreement, AanganError> {
+    let mut rental = get_application(rental_id, caller)?;
+
+    if rental.landlord != caller {
+        return Err(AanganError::unauthorized(
+            "Only landlord can set lease terms",
+        ));
+    }
+
+    if terms.document_hash.len() != 32 {
+        return Err(AanganError::validation(
+            "document_hash",
+            "must be a 32-byte SHA-256 hash",
+        ));
+    }
+    if terms.clauses.len() > MAX_CLAUSES {
+        return Err(AanganError::validation(
+            "clauses",
+            format!("at most {} clauses are allowed", MAX_CLAUSES),
+        ));
+    }
+    if terms
+        .clauses
+        .iter()
+        .any(|clause| clause.trim().is_empty() || clause.chars().count() > MAX_CLAUSE_LENGTH)
+    {
+        return Err(AanganError::validation(
+            "clauses",
+            format!(
+                "must be non-empty and at most {} characters",
+                MAX_CLAUSE_LENGTH
+            ),
+        ));
+    }
+
+    // The application may be accepted against the current terms right now
+    if escrow::is_locked(rental_id) {
+        return Err(AanganError::conflict(
+            "A ledger operation is already in progress for this rental",
+        ));
+    }
+
+    rental.set_terms(terms);
+    rental_store::update_rental(rental.clone())?;
+
+    Ok(rental)
+}
+
+/// Records the caller's acceptance of the terms identified by `expected_hash`, which must
+/// still be the current terms hash.
+pub fn sign_lease(
+    rental_id: u64,
+    expected_hash: &[u8],
+    caller: Principal,
+) -> Result<RentalAgreement, AanganError> {
+    let mut rental = get_application(rental_id, caller)?;
+
+    if rental.terms.is_none() {
+        return Err(AanganError::not_found("Lease terms", rental_id));
+    }
+
+    let current = terms_hash(&rental);
+    if expected_hash != current.as_slice() {
+        return Err(AanganError::conflict(
+            "The terms have changed since they were reviewed",
+        ));
+    }
+
+    rental.record_acceptance(Acceptance::new(caller, current));
+    rental_store::update_rental(rental.clone())?;
+
+    Ok(rental)
+}
+
+/// Fails unless both the landlord and the tenant have signed the current terms.
+pub fn ensure_fully_signed(rental: &RentalAgreement) -> Result<(), AanganError> {
+    if rental.terms.is_none() {
+        return Err(AanganError::not_found("Lease terms", rental.id));
+    }
+
+    let current = terms_hash(rental);
+    for (party, signer) in [("landlord", rental.landlord), ("tenant", rental.tenant)] {
+        if !rental.is_signed_by(&signer, &current) {
+            return Err(AanganError::invalid_state(
+                format!("terms signed by the {}", party),
+                "unsigned",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn get_terms_hash(rental_id: u64, caller: Principal) -> Result<[u8; 32], AanganError> {
+    let rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+
+    if rental.landlord != caller && rental.tenant != caller {
+        return Err(AanganError::unauthorized("Access denied"));
+    }
+
+    Ok(terms_hash(&rental))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::get_next_rental_id;
+    use crate::types::TerminationPenalty;
+    use serde_bytes::ByteBuf;
+
+    const START: u64 = 1_800_000_000_000_000_000;
+    const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+    fn landlord() -> Principal {
+        Principal::from_slice(&[2])
+    }
+
+    fn tenant() -> Principal {
+        Principal::from_slice(&[3])
+    }
+
+    fn terms(clause: &str) -> LeaseTerms {
+        LeaseTerms {
+            document_hash: ByteBuf::from(vec![7u8; 32]),
+            clauses: vec![clause.to_string()],
+        }
+    }
+
+    fn store_application() -> RentalAgreement {
+        let rental = RentalAgreement::new(
+            get_next_rental_id(),
+            1,
+            landlord(),
+            tenant(),
+            START,
+            START + 30 * DAY_NANOS,
+            25_000_000,
+            100_000_000,
+        );
+        rental_store::create_rental(rental.clone()).unwrap();
+        set_lease_terms(rental.id, terms("No pets"), landlord()).unwrap()
+    }
+
+    fn sign(rental_id: u64, signer: Principal) -> Result<RentalAgreement, AanganError> {
+        let hash = get_terms_hash(rental_id, signer)?;
+        sign_lease(rental_id, &hash, signer)
+    }
+
+    #[test]
+    fn terms_hash_is_stable_and_covers_every_term() {
+        let rental = store_application();
+        assert_eq!(terms_hash(&rental), terms_hash(&rental.clone()));
+
+        let mut other_clauses = rental.clone();
+        other_clauses.set_terms(terms("Pets allowed"));
+        let mut other_rent = rental.clone();
+        other_rent.rent_amount += 1;
+        let mut other_termination = rental.clone();
+        other_termination.set_termination_terms(TerminationTerms {
+            notice_period_days: 60,
+            penalty: TerminationPenalty::MonthsOfRent(1),
+        });
+
+        for changed in [other_clauses, other_rent, other_termination] {
+            assert_ne!(terms_hash(&changed), terms_hash(&rental));
+        }
+    }
+
+    #[test]
+    fn setting_terms_clears_signatures() {
+        let rental = store_application();
+        sign(rental.id, landlord()).unwrap();
+        let signed = sign(rental.id, tenant()).unwrap();
+        assert!(ensure_fully_signed(&signed).is_ok());
+
+        let amended = set_lease_terms(rental.id, terms("Pets allowed"), landlord()).unwrap();
+
+        assert!(amended.acceptances.is_none());
+        assert!(ensure_fully_signed(&amended).is_err());
+    }
+
+    #[test]
+    fn only_the_parties_sign() {
+        let rental = store_application();
+        let hash = terms_hash(&rental);
+
+        assert!(matches!(
+            sign_lease(rental.id, &hash, Principal::from_slice(&[9])),
+            Err(AanganError::Unauthorized(_))
+        ));
+    }
+
+    #[test]
+    fn signatures_must_match_the_current_terms() {
+        let rental = store_application();
+        let stale = terms_hash(&rental);
+        set_lease_terms(rental.id, terms("Pets allowed"), landlord()).unwrap();
+
+        assert!(matches!(
+            sign_lease(rental.id, &stale, tenant()),
+            Err(AanganError::Conflict(_))
+        ));
+    }
+
+    #[test]
+    fn confirmation_waits_for_both_signatures() {
+        let rental = store_application();
+        assert!(ensure_fully_signed(&rental).is_err());
+
+        let rental_after_landlord = sign(rental.id, landlord()).unwrap();
+        assert!(matches!(
+            ensure_fully_signed(&rental_after_landlord),
+            Err(AanganError::InvalidState { .. })
+        ));
+
+        let rental_after_tenant = sign(rental.id, tenant()).unwrap();
+        assert!(ensure_fully_signed(&rental_after_tenant).is_ok());
+    }
+}
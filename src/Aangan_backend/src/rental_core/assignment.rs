@@ -0,0 +1,406 @@
+//! Lease assignment: a tenant hands their lease over mid-term by transferring the rental NFT.
+//!
+//! Modelled on ICRC-37: the tenant proposes a transfer to a new principal and the landlord,
+//! acting as the approver, completes it. Only one proposal can be pending per rental.
+//!
+//! Installments not yet due pass to the new tenant with the lease. Overdue rent has to be
+//! settled, and a disputed deposit resolved, before the landlord can approve a transfer.
+//! The deposit itself stays in escrow and still belongs to the tenant who funded it.
+
+use crate::escrow;
+use crate::rental_core::payments;
+use crate::storage::{assignment_store, nft_event_store, rental_store, user_store};
+use crate::types::{
+    AanganError, AssignmentStatus, EscrowStatus, LeaseAssignment, NFTEventKind, NFTMetadata,
+    RentalAgreement, RentalStatus, Role,
+};
+use candid::Principal;
+
+fn get_nft_and_rental(nft_id: u64) -> Result<(NFTMetadata, RentalAgreement), AanganError> {
+    let nft = rental_store::get_nft(nft_id).ok_or_else(|| AanganError::not_found("NFT", nft_id))?;
+    let rental = rental_store::get_rental(nft.rental_agreement_id)
+        .ok_or_else(|| AanganError::not_found("Rental", nft.rental_agreement_id))?;
+    Ok((nft, rental))
+}
+
+/// Only the rental's current NFT can be assigned. A token superseded by a renewal or voided
+/// by an admin no longer stands for the lease.
+fn ensure_assignable(nft: &NFTMetadata, rental: &RentalAgreement) -> Result<(), AanganError> {
+    if rental.nft_id != Some(nft.id) || nft.is_retired() {
+        return Err(AanganError::invalid_state(
+            "current rental NFT",
+            format!(
+                "{} NFT",
+                nft.status
+                    .as_ref()
+                    .map_or("Unknown", |status| status.as_str())
+            ),
+        ));
+    }
+
+    if rental.status != RentalStatus::Confirmed && rental.status != RentalStatus::Active {
+        return Err(AanganError::invalid_state(
+            "Confirmed or Active",
+            format!("{:?}", rental.status),
+        ));
+    }
+    Ok(())
+}
+
+pub fn propose_transfer(
+    nft_id: u64,
+    to: Principal,
+    caller: Principal,
+) -> Result<LeaseAssignment, AanganError> {
+    let (nft, rental) = get_nft_and_rental(nft_id)?;
+
+    if nft.owner != caller || rental.tenant != caller {
+        return Err(AanganError::unauthorized(
+            "Only the current tenant can transfer the lease",
+        ));
+    }
+
+    ensure_assignable(&nft, &rental)?;
+
+    if to == caller || to == rental.landlord || to == Principal::anonymous() {
+        return Err(AanganError::validation(
+            "to",
+            "must be a principal other than the tenant and landlord",
+        ));
+    }
+
+    let recipient = user_store::get_user(&to).ok_or_else(|| AanganError::not_found("User", to))?;
+    if !recipient.has_role(&Role::Tenant) {
+        return Err(AanganError::validation(
+            "to",
+            "leases can only be transferred to tenants",
+        ));
+    }
+
+    if assignment_store::get_pending_assignment(rental.id).is_some() {
+        return Err(AanganError::conflict("A lease transfer is already pending"));
+    }
+
+    let assignment = LeaseAssignment::new(
+        rental.id,
+        nft_id,
+        assignment_store::next_sequence(rental.id),
+        caller,
+        to,
+    );
+    assignment_store::create_assignment(assignment.clone())?;
+
+    Ok(assignment)
+}
+
+/// Completes the pending transfer. The NFT owner, the rental tenant, the tenant and owner
+/// indexes and the assignment record are all written in this one message, so they change
+/// together or not at all.
+pub fn approve_transfer(nft_id: u64, caller: Principal) -> Result<LeaseAssignment, AanganError> {
+    let (mut nft, mut rental) = get_nft_and_rental(nft_id)?;
+
+    if rental.landlord != caller {
+        return Err(AanganError::unauthorized(
+            "Only the landlord can approve a lease transfer",
+        ));
+    }
+
+    let mut assignment = assignment_store::get_pending_assignment(rental.id)
+        .ok_or_else(|| AanganError::not_found("Pending assignment", rental.id))?;
+
+    ensure_assignable(&nft, &rental)?;
+
+    if rental.tenant != assignment.from {
+        return Err(AanganError::conflict(
+            "The tenant has changed since the transfer was proposed",
+        ));
+    }
+
+    // A rent payment or deposit settlement in flight still pays on behalf of the old tenant.
+    if escrow::is_locked(rental.id) {
+        return Err(AanganError::conflict(
+            "A ledger operation is already in progress for this rental",
+        ));
+    }
+
+    if payments::has_arrears(rental.id, crate::clock::now()) {
+        return Err(AanganError::conflict(
+            "The outgoing tenant has overdue rent to settle first",
+        ));
+    }
+
+    if rental
+        .escrow
+        .as_ref()
+        .is_some_and(|escrow| escrow.status == EscrowStatus::Disputed)
+    {
+        return Err(AanganError::conflict(
+            "The deposit dispute has to be resolved first",
+        ));
+    }
+
+    rental.set_tenant(assignment.to);
+    nft.set_owner(assignment.to);
+    assignment.resolve(AssignmentStatus::Completed);
+
+    rental_store::update_rental(rental)?;
+    rental_store::update_nft(nft)?;
+    assignment_store::update_assignment(assignment.clone())?;
+    nft_event_store::record_event(
+        nft_id,
+        NFTEventKind::Transferred {
+            from: assignment.from,
+            to: assignment.to,
+        },
+    );
+
+    Ok(assignment)
+}
+
+pub fn reject_transfer(nft_id: u64, caller: Principal) -> Result<LeaseAssignment, AanganError> {
+    let (_, rental) = get_nft_and_rental(nft_id)?;
+
+    if rental.landlord != caller {
+        return Err(AanganError::unauthorized(
+            "Only the landlord can reject a lease transfer",
+        ));
+    }
+
+    resolve_pending(rental.id, AssignmentStatus::Rejected)
+}
+
+pub fn cancel_transfer(nft_id: u64, caller: Principal) -> Result<LeaseAssignment, AanganError> {
+    let (_, rental) = get_nft_and_rental(nft_id)?;
+
+    let pending = assignment_store::get_pending_assignment(rental.id)
+        .ok_or_else(|| AanganError::not_found("Pending assignment", rental.id))?;
+
+    if pending.from != caller {
+        return Err(AanganError::unauthorized(
+            "Only the proposing tenant can cancel a lease transfer",
+        ));
+    }
+
+    resolve_pending(rental.id, AssignmentStatus::Cancelled)
+}
+
+fn resolve_pending(
+    rental_id: u64,
+    status: AssignmentStatus,
+) -> Result<LeaseAssignment, AanganError> {
+    let mut assignment = assignment_store::get_pending_assignment(rental_id)
+        .ok_or_else(|| AanganError::not_found("Pending assignment", rental_id))?;
+
+    assignment.resolve(status);
+    assignment_store::update_assignment(assignment.clone())?;
+
+    Ok(assignment)
+}
+
+/// The pending transfer of an NFT's lease, visible to the landlord and the two tenants
+/// involved.
+pub fn get_pending_transfer(
+    nft_id: u64,
+    caller: Principal,
+) -> Result<Option<LeaseAssignment>, AanganError> {
+    let (_, rental) = get_nft_and_rental(nft_id)?;
+    let pending = assignment_store::get_pending_assignment(rental.id);
+
+    let is_party = rental.landlord == caller
+        || rental.tenant == caller
+        || pending
+            .as_ref()
+            .is_some_and(|assignment| assignment.to == caller);
+
+    if !is_party {
+        return Err(AanganError::unauthorized("Access denied"));
+    }
+
+    Ok(pending)
+}
+
+/// The landlord, the current tenant and every earlier tenant of the lease may read its
+/// assignment history.
+pub fn get_assignment_history(
+    rental_id: u64,
+    caller: Principal,
+) -> Result<Vec<LeaseAssignment>, AanganError> {
+    let rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+
+    let history = assignment_store::get_assignment_history(rental_id);
+
+    let is_party = rental.landlord == caller
+        || rental.tenant == caller
+        || history
+            .iter()
+            .any(|assignment| assignment.from == caller || assignment.to == caller);
+
+    if !is_party {
+        return Err(AanganError::unauthorized("Access denied"));
+    }
+
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{get_next_nft_id, get_next_rental_id, payment_store};
+    use crate::types::{Escrow, PaymentStatus, RentPayment, User};
+
+    const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+    const DEPOSIT: u64 = 100_000_000;
+
+    fn landlord() -> Principal {
+        Principal::from_slice(&[2])
+    }
+
+    fn tenant() -> Principal {
+        Principal::from_slice(&[3])
+    }
+
+    fn newcomer() -> Principal {
+        Principal::from_slice(&[5])
+    }
+
+    /// An active lease with a funded deposit, held through its NFT by `tenant()`.
+    fn store_lease() -> (NFTMetadata, RentalAgreement) {
+        for (user, role) in [
+            (landlord(), Role::Landlord),
+            (tenant(), Role::Tenant),
+            (newcomer(), Role::Tenant),
+        ] {
+            user_store::create_user(User::new(user, role, None, None, None)).unwrap();
+        }
+
+        let start = crate::clock::now();
+        let mut rental = RentalAgreement::new(
+            get_next_rental_id(),
+            1,
+            landlord(),
+            tenant(),
+            start,
+            start + 90 * DAY_NANOS,
+            25_000_000,
+            DEPOSIT,
+        );
+        let nft = NFTMetadata::new(
+            get_next_nft_id(),
+            tenant(),
+            rental.property_id,
+            rental.id,
+            "Flat".to_string(),
+            "12 MG Road".to_string(),
+            String::new(),
+            rental.rent_amount,
+            rental.start_date,
+            rental.end_date,
+        );
+        rental.status = RentalStatus::Active;
+        rental.set_nft(nft.id);
+        rental.set_escrow(Escrow::new(tenant(), DEPOSIT, 1));
+        rental_store::create_rental(rental.clone()).unwrap();
+        rental_store::create_nft(nft.clone()).unwrap();
+        (nft, rental)
+    }
+
+    /// Schedules one installment, due `due_in` from now (negative when already overdue).
+    fn schedule_installment(rental: &RentalAgreement, period: u32, due_in: i64) {
+        let due_date = crate::clock::now().saturating_add_signed(due_in);
+        payment_store::create_payments(vec![RentPayment::new(
+            rental.id,
+            period,
+            due_date,
+            rental.rent_amount,
+        )])
+        .unwrap();
+    }
+
+    #[test]
+    fn approved_transfers_move_the_lease_but_not_the_deposit() {
+        let (nft, rental) = store_lease();
+        schedule_installment(&rental, 0, DAY_NANOS as i64);
+        propose_transfer(nft.id, newcomer(), tenant()).unwrap();
+
+        let assignment = approve_transfer(nft.id, landlord()).unwrap();
+
+        assert_eq!(assignment.status, AssignmentStatus::Completed);
+        let rental = rental_store::get_rental(rental.id).unwrap();
+        assert_eq!(rental.tenant, newcomer());
+        assert_eq!(rental_store::get_nft(nft.id).unwrap().owner, newcomer());
+        assert_eq!(rental_store::get_nfts_by_owner(&newcomer()).len(), 1);
+        assert!(rental_store::get_nfts_by_owner(&tenant()).is_empty());
+        // Rent falling due later is the new tenant's; the deposit is still the old tenant's
+        assert_eq!(
+            payment_store::get_payment(rental.id, 0).unwrap().status,
+            PaymentStatus::Due
+        );
+        assert_eq!(escrow::depositor(&rental), Some(tenant()));
+        assert!(assignment_store::get_pending_assignment(rental.id).is_none());
+    }
+
+    #[test]
+    fn overdue_rent_blocks_approval() {
+        let (nft, rental) = store_lease();
+        schedule_installment(&rental, 0, -(DAY_NANOS as i64));
+        propose_transfer(nft.id, newcomer(), tenant()).unwrap();
+
+        assert!(matches!(
+            approve_transfer(nft.id, landlord()),
+            Err(AanganError::Conflict(_))
+        ));
+
+        let mut payment = payment_store::get_payment(rental.id, 0).unwrap();
+        payment.mark_paid(9);
+        payment_store::update_payment(payment).unwrap();
+        approve_transfer(nft.id, landlord()).unwrap();
+        assert_eq!(
+            rental_store::get_rental(rental.id).unwrap().tenant,
+            newcomer()
+        );
+    }
+
+    #[test]
+    fn disputed_deposit_blocks_approval() {
+        let (nft, rental) = store_lease();
+        propose_transfer(nft.id, newcomer(), tenant()).unwrap();
+        escrow::dispute_deposit(rental.id, landlord()).unwrap();
+
+        assert!(matches!(
+            approve_transfer(nft.id, landlord()),
+            Err(AanganError::Conflict(_))
+        ));
+        assert_eq!(
+            rental_store::get_rental(rental.id).unwrap().tenant,
+            tenant()
+        );
+    }
+
+    #[test]
+    fn only_the_landlord_approves() {
+        let (nft, _) = store_lease();
+        propose_transfer(nft.id, newcomer(), tenant()).unwrap();
+
+        for caller in [tenant(), newcomer()] {
+            assert!(matches!(
+                approve_transfer(nft.id, caller),
+                Err(AanganError::Unauthorized(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn pending_transfers_are_visible_to_the_parties_only() {
+        let (nft, _) = store_lease();
+        propose_transfer(nft.id, newcomer(), tenant()).unwrap();
+
+        for caller in [landlord(), tenant(), newcomer()] {
+            assert!(get_pending_transfer(nft.id, caller).unwrap().is_some());
+        }
+        assert!(matches!(
+            get_pending_transfer(nft.id, Principal::from_slice(&[9])),
+            Err(AanganError::Unauthorized(_))
+        ));
+    }
+}
@@ -0,0 +1,128 @@
+//! Lease attestations: canister-signed proof of tenancy that third parties can verify
+//! without querying the canister.
+//!
+//! As with delegations and credentials in the vendored internet-identity canister, the
+//! tenant prepares an attestation in an update call, which adds a canister signature on its
+//! encoding, and fetches the signature with a query call under the resulting certificate.
+
+use candid::Principal;
+use ic_canister_sig_creation::signature_map::CanisterSigInputs;
+use serde_bytes::ByteBuf;
+
+use aangan_attestation::{ATTESTATION_SEED, ATTESTATION_SIG_DOMAIN};
+
+use crate::certification;
+use crate::storage::{property_store, rental_store};
+use crate::types::{
+    AanganError, AttestedStatus, LeaseAttestation, PreparedLeaseAttestation, RentalAgreement,
+    RentalStatus, SignedLeaseAttestation,
+};
+
+/// Longest validity of an attestation; a verifier wanting fresher proof asks for a new one.
+pub const ATTESTATION_VALIDITY_NANOS: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+
+fn sig_inputs(message: &[u8]) -> CanisterSigInputs<'_> {
+    CanisterSigInputs {
+        domain: ATTESTATION_SIG_DOMAIN,
+        seed: ATTESTATION_SEED,
+        message,
+    }
+}
+
+fn get_tenancy(rental_id: u64, caller: Principal) -> Result<RentalAgreement, AanganError> {
+    let rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+
+    if rental.tenant != caller {
+        return Err(AanganError::unauthorized(
+            "Only tenant can request a lease attestation",
+        ));
+    }
+
+    Ok(rental)
+}
+
+fn attest(rental: &RentalAgreement, now: u64) -> Result<LeaseAttestation, AanganError> {
+    let status = match rental.status {
+        RentalStatus::Active => AttestedStatus::Active,
+        RentalStatus::Terminating => AttestedStatus::Terminating,
+        _ => {
+            return Err(AanganError::invalid_state(
+                "Active or Terminating",
+                format!("{:?}", rental.status),
+            ))
+        }
+    };
+
+    let period = rental.period();
+    if now >= period.end {
+        return Err(AanganError::invalid_state("running lease", "ended"));
+    }
+
+    let property = property_store::get_property(rental.property_id)
+        .ok_or_else(|| AanganError::not_found("Property", rental.property_id))?;
+
+    Ok(LeaseAttestation {
+        issuer: ic_cdk::id(),
+        rental_id: rental.id,
+        property_id: rental.property_id,
+        property_address: property.address,
+        landlord: rental.landlord,
+        tenant: rental.tenant,
+        start_date: rental.start_date,
+        end_date: period.end,
+        rent_amount: rental.rent_amount,
+        status,
+        issued_at: now,
+        expires_at: now
+            .saturating_add(ATTESTATION_VALIDITY_NANOS)
+            .min(period.end),
+    })
+}
+
+/// Signs an attestation of the caller's running lease. The signature can be fetched with
+/// [get_lease_attestation] for the next minute.
+pub fn prepare_lease_attestation(
+    rental_id: u64,
+    caller: Principal,
+) -> Result<PreparedLeaseAttestation, AanganError> {
+    let rental = get_tenancy(rental_id, caller)?;
+    let attestation = attest(&rental, ic_cdk::api::time())?;
+
+    let message = attestation.encode();
+    certification::add_signature(&sig_inputs(&message));
+
+    Ok(PreparedLeaseAttestation {
+        attestation,
+        message: ByteBuf::from(message),
+        canister_sig_pk_der: ByteBuf::from(aangan_attestation::public_key_der(ic_cdk::id())),
+    })
+}
+
+/// Returns the signature on an attestation prepared by [prepare_lease_attestation].
+pub fn get_lease_attestation(
+    rental_id: u64,
+    message: &[u8],
+    caller: Principal,
+) -> Result<SignedLeaseAttestation, AanganError> {
+    get_tenancy(rental_id, caller)?;
+
+    let attestation =
+        LeaseAttestation::decode(message).map_err(|e| AanganError::validation("message", e))?;
+    if attestation.rental_id != rental_id {
+        return Err(AanganError::validation(
+            "message",
+            "attests a different rental",
+        ));
+    }
+
+    let signature = certification::get_signature(&sig_inputs(message))
+        .map_err(|_| AanganError::not_found("Lease attestation", rental_id))?;
+
+    Ok(SignedLeaseAttestation {
+        attestation,
+        message: ByteBuf::from(message.to_vec()),
+        signature: ByteBuf::from(signature),
+        canister_sig_pk_der: ByteBuf::from(aangan_attestation::public_key_der(ic_cdk::id())),
+    })
+}
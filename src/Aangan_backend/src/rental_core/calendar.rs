@@ -0,0 +1,101 @@
+//! Per-property occupancy calendar.
+//!
+//! A property is occupied for the `[start_date, end_date)` period of every Confirmed, Active
+//! or Terminating agreement on it; a Terminating agreement only until its effective date.
+//! The calendar is derived from those agreements on demand, so it never drifts from the
+//! rental records.
+
+use crate::storage::rental_store;
+use crate::types::{AanganError, DateRange};
+
+/// Occupied periods of the property, sorted by start.
+pub fn occupied(property_id: u64) -> Vec<DateRange> {
+    let mut periods: Vec<DateRange> = rental_store::get_rentals_by_property(property_id)
+        .iter()
+        .filter(|rental| rental.is_live())
+        .map(|rental| rental.period())
+        .collect();
+    periods.sort_by_key(|period| (period.start, period.end));
+    periods
+}
+
+/// Whether `period` is free, ignoring the agreement `except` (e.g. the lease being renewed).
+pub fn is_free(property_id: u64, period: &DateRange, except: Option<u64>) -> bool {
+    !rental_store::get_rentals_by_property(property_id)
+        .iter()
+        .filter(|rental| rental.is_live() && Some(rental.id) != except)
+        .any(|rental| rental.period().overlaps(period))
+}
+
+/// The sub-ranges of `within` not covered by any occupied period.
+pub fn free_windows(property_id: u64, within: &DateRange) -> Vec<DateRange> {
+    let mut windows = Vec::new();
+    let mut cursor = within.start;
+    for period in occupied(property_id) {
+        if period.end <= cursor {
+            continue;
+        }
+        if period.start >= within.end {
+            break;
+        }
+        if period.start > cursor {
+            windows.push(DateRange {
+                start: cursor,
+                end: period.start,
+            });
+        }
+        cursor = period.end;
+    }
+    if cursor < within.end {
+        windows.push(DateRange {
+            start: cursor,
+            end: within.end,
+        });
+    }
+    windows
+}
+
+/// Checks that `period` is non-empty and does not start before `now`.
+pub fn validate_period(period: &DateRange, now: u64) -> Result<(), AanganError> {
+    if period.start < now {
+        return Err(AanganError::validation(
+            "start_date",
+            "must not be in the past",
+        ));
+    }
+    if period.end <= period.start {
+        return Err(AanganError::validation(
+            "end_date",
+            "must be after start_date",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000_000_000_000;
+
+    fn period(start: u64, end: u64) -> DateRange {
+        DateRange { start, end }
+    }
+
+    #[test]
+    fn periods_must_not_start_in_the_past() {
+        assert!(validate_period(&period(NOW, NOW + 1), NOW).is_ok());
+        assert!(matches!(
+            validate_period(&period(NOW - 1, NOW + 1), NOW),
+            Err(AanganError::Validation { field, .. }) if field == "start_date"
+        ));
+    }
+
+    #[test]
+    fn periods_must_not_be_empty() {
+        assert!(matches!(
+            validate_period(&period(NOW + 5, NOW + 5), NOW),
+            Err(AanganError::Validation { field, .. }) if field == "end_date"
+        ));
+    }
+}
@@ -0,0 +1,160 @@
+//! ICRC-7 view of the rental NFT collection.
+//!
+//! Token ids are the NFT ids and every token is held by the default account of its owner.
+//! Superseded and voided NFTs are no longer tokens: they have no owner and count towards
+//! neither balances nor the total supply, but their metadata is still served.
+
+use crate::certification;
+use crate::storage::rental_store;
+use crate::types::{NFTAttribute, NFTMetadata, SupportedStandard};
+use candid::{Nat, Principal};
+use icrc_ledger_types::icrc::generic_value::{ICRC3Map, ICRC3Value};
+use icrc_ledger_types::icrc1::account::Account;
+
+pub const SYMBOL: &str = "AANGAN";
+pub const NAME: &str = "Aangan Rental Agreements";
+pub const DESCRIPTION: &str = "Each token represents a tenant's rental agreement on Aangan.";
+/// Batch queries with more ids or accounts than this trap instead of being truncated.
+pub const MAX_QUERY_BATCH_SIZE: usize = 100;
+pub const DEFAULT_TAKE_VALUE: usize = 50;
+pub const MAX_TAKE_VALUE: usize = 100;
+
+fn nat(value: impl Into<Nat>) -> ICRC3Value {
+    ICRC3Value::Nat(value.into())
+}
+
+fn text(value: impl Into<String>) -> ICRC3Value {
+    ICRC3Value::Text(value.into())
+}
+
+pub fn collection_metadata() -> ICRC3Map {
+    ICRC3Map::from([
+        ("icrc7:symbol".to_string(), text(SYMBOL)),
+        ("icrc7:name".to_string(), text(NAME)),
+        ("icrc7:description".to_string(), text(DESCRIPTION)),
+        (
+            "icrc7:total_supply".to_string(),
+            nat(rental_store::nft_supply()),
+        ),
+        (
+            "icrc7:max_query_batch_size".to_string(),
+            nat(MAX_QUERY_BATCH_SIZE),
+        ),
+        (
+            "icrc7:default_take_value".to_string(),
+            nat(DEFAULT_TAKE_VALUE),
+        ),
+        ("icrc7:max_take_value".to_string(), nat(MAX_TAKE_VALUE)),
+    ])
+}
+
+/// Traps if a batch query asks for more than [MAX_QUERY_BATCH_SIZE] items.
+pub fn check_batch_size(len: usize) {
+    if len > MAX_QUERY_BATCH_SIZE {
+        ic_cdk::trap(&format!(
+            "batch of {} exceeds icrc7:max_query_batch_size of {}",
+            len, MAX_QUERY_BATCH_SIZE
+        ));
+    }
+}
+
+/// Numeric attributes (ids, rent, dates) become `Nat`, everything else `Text`.
+fn attribute_value(attribute: &NFTAttribute) -> ICRC3Value {
+    match attribute.value.parse::<u64>() {
+        Ok(value) => nat(value),
+        Err(_) => text(attribute.value.clone()),
+    }
+}
+
+pub fn token_metadata(nft: &NFTMetadata) -> ICRC3Map {
+    let attributes = nft
+        .attributes
+        .iter()
+        .map(|attribute| {
+            ICRC3Value::Map(ICRC3Map::from([
+                ("trait_type".to_string(), text(attribute.trait_type.clone())),
+                ("value".to_string(), attribute_value(attribute)),
+            ]))
+        })
+        .collect();
+
+    ICRC3Map::from([
+        ("icrc7:name".to_string(), text(nft.name.clone())),
+        (
+            "icrc7:description".to_string(),
+            text(nft.description.clone()),
+        ),
+        (
+            "icrc7:image".to_string(),
+            text(certification::canister_url(&certification::svg_path(
+                nft.id,
+            ))),
+        ),
+        (
+            "icrc97:metadata".to_string(),
+            ICRC3Value::Array(vec![text(certification::canister_url(
+                &certification::json_path(nft.id),
+            ))]),
+        ),
+        (
+            "icrc7:attributes".to_string(),
+            ICRC3Value::Array(attributes),
+        ),
+        ("aangan:property_id".to_string(), nat(nft.property_id)),
+        (
+            "aangan:rental_agreement_id".to_string(),
+            nat(nft.rental_agreement_id),
+        ),
+        ("aangan:created_at".to_string(), nat(nft.created_at)),
+        (
+            "aangan:status".to_string(),
+            text(
+                nft.status
+                    .as_ref()
+                    .map_or("Unknown", |status| status.as_str()),
+            ),
+        ),
+    ])
+}
+
+/// NFT ids are `u64`; larger ICRC-7 token ids cannot exist.
+pub fn token_id(id: &Nat) -> Option<u64> {
+    u64::try_from(id.0.clone()).ok()
+}
+
+pub fn owner_account(owner: Principal) -> Account {
+    Account {
+        owner,
+        subaccount: None,
+    }
+}
+
+/// Tokens are only ever held by default accounts, so other subaccounts own nothing.
+pub fn holds_tokens(account: &Account) -> bool {
+    account
+        .subaccount
+        .is_none_or(|subaccount| subaccount == [0; 32])
+}
+
+pub fn supported_standards() -> Vec<SupportedStandard> {
+    [
+        (
+            "ICRC-7",
+            "https://github.com/dfinity/ICRC/tree/main/ICRCs/ICRC-7",
+        ),
+        (
+            "ICRC-10",
+            "https://github.com/dfinity/ICRC/tree/main/ICRCs/ICRC-10",
+        ),
+        (
+            "ICRC-97",
+            "https://github.com/dfinity/ICRC/tree/main/ICRCs/ICRC-97",
+        ),
+    ]
+    .into_iter()
+    .map(|(name, url)| SupportedStandard {
+        name: name.to_string(),
+        url: url.to_string(),
+    })
+    .collect()
+}
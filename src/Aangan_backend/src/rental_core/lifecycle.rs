@@ -0,0 +1,176 @@
+//! Periodic lease lifecycle sweep.
+//!
+//! Confirmed rentals become Active once `start_date` passes, Active rentals are Completed
+//! after `end_date`, Terminating rentals are Completed on their effective date, and
+//! Requested rentals that were never confirmed expire. Image uploads that were never
+//! committed are purged on the same schedule.
+
+use crate::rental_core::{payments, rental_manager};
+use crate::storage::{image_store, rental_store};
+use crate::types::{AanganError, RentalStatus};
+
+/// How often the sweep runs.
+pub const SWEEP_INTERVAL_SECS: u64 = 60 * 60;
+
+/// Requested rentals older than this (14 days) expire even if `start_date` is still ahead.
+pub const REQUEST_TTL_NANOS: u64 = 14 * 24 * 60 * 60 * 1_000_000_000;
+
+#[cfg(feature = "timers")]
+pub fn arm_timers() {
+    ic_cdk_timers::set_timer_interval(std::time::Duration::from_secs(SWEEP_INTERVAL_SECS), || {
+        sweep(ic_cdk::api::time())
+    });
+}
+
+#[cfg(not(feature = "timers"))]
+pub fn arm_timers() {}
+
+pub fn sweep(now: u64) {
+    for rental in rental_store::get_rentals_by_status(&RentalStatus::Requested) {
+        let stale =
+            now >= rental.start_date || now.saturating_sub(rental.created_at) >= REQUEST_TTL_NANOS;
+        if stale {
+            log_failure(rental.id, rental_manager::expire_rental(rental.id));
+        }
+    }
+
+    for rental in rental_store::get_rentals_by_status(&RentalStatus::Confirmed) {
+        if now >= rental.start_date {
+            log_failure(rental.id, rental_manager::activate_rental(rental.id));
+        }
+    }
+
+    for rental in rental_store::get_rentals_by_status(&RentalStatus::Active) {
+        if now >= rental.end_date {
+            log_failure(rental.id, rental_manager::complete_rental(rental.id));
+        } else {
+            payments::mark_late_payments(rental.id, now);
+        }
+    }
+
+    for rental in rental_store::get_rentals_by_status(&RentalStatus::Terminating) {
+        // An unpaid termination penalty stays owed after the lease completes
+        if now >= rental.period().end {
+            log_failure(rental.id, rental_manager::complete_rental(rental.id));
+        } else {
+            payments::mark_late_payments(rental.id, now);
+        }
+    }
+
+    image_store::purge_stale_uploads(now);
+}
+
+fn log_failure<T>(rental_id: u64, result: Result<T, AanganError>) {
+    if let Err(err) = result {
+        ic_cdk::println!("lifecycle sweep failed for rental {}: {}", rental_id, err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{get_next_rental_id, payment_store};
+    use crate::types::{PaymentStatus, RentalAgreement, Termination};
+    use candid::Principal;
+
+    const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+    fn store_rental(status: RentalStatus, start_date: u64, end_date: u64) -> RentalAgreement {
+        let mut rental = RentalAgreement::new(
+            get_next_rental_id(),
+            1,
+            Principal::from_slice(&[2]),
+            Principal::from_slice(&[3]),
+            start_date,
+            end_date,
+            25_000_000,
+            0,
+        );
+        rental.status = status;
+        rental_store::create_rental(rental.clone()).unwrap();
+        rental
+    }
+
+    fn status_of(rental: &RentalAgreement) -> RentalStatus {
+        rental_store::get_rental(rental.id).unwrap().status
+    }
+
+    #[test]
+    fn stale_requests_expire_after_the_ttl() {
+        let rental = store_rental(RentalStatus::Requested, u64::MAX - 1, u64::MAX);
+
+        sweep(rental.created_at + REQUEST_TTL_NANOS - 1);
+        assert_eq!(status_of(&rental), RentalStatus::Requested);
+
+        sweep(rental.created_at + REQUEST_TTL_NANOS);
+        assert_eq!(status_of(&rental), RentalStatus::Expired);
+        assert!(rental_store::get_rentals_by_status(&RentalStatus::Requested).is_empty());
+    }
+
+    #[test]
+    fn requests_expire_once_the_lease_would_have_started() {
+        let now = crate::clock::now();
+        let rental = store_rental(
+            RentalStatus::Requested,
+            now + DAY_NANOS,
+            now + 30 * DAY_NANOS,
+        );
+
+        sweep(now + DAY_NANOS - 1);
+        assert_eq!(status_of(&rental), RentalStatus::Requested);
+
+        sweep(now + DAY_NANOS);
+        assert_eq!(status_of(&rental), RentalStatus::Expired);
+    }
+
+    #[test]
+    fn confirmed_leases_activate_on_the_start_date() {
+        let start = crate::clock::now() + DAY_NANOS;
+        let rental = store_rental(RentalStatus::Confirmed, start, start + 90 * DAY_NANOS);
+
+        sweep(start - 1);
+        assert_eq!(status_of(&rental), RentalStatus::Confirmed);
+
+        sweep(start);
+        assert_eq!(status_of(&rental), RentalStatus::Active);
+        assert_eq!(payment_store::get_payments_by_rental(rental.id).len(), 3);
+    }
+
+    #[test]
+    fn active_leases_complete_once_they_run_out() {
+        let start = crate::clock::now();
+        let end = start + 60 * DAY_NANOS;
+        let rental = store_rental(RentalStatus::Confirmed, start, end);
+        sweep(start);
+
+        // The first installment is overdue a day after the start
+        sweep(start + DAY_NANOS);
+        assert_eq!(status_of(&rental), RentalStatus::Active);
+        let payments = payment_store::get_payments_by_rental(rental.id);
+        assert_eq!(payments[0].status, PaymentStatus::Late);
+        assert_eq!(payments[1].status, PaymentStatus::Due);
+
+        sweep(end);
+        assert_eq!(status_of(&rental), RentalStatus::Completed);
+        assert!(rental_store::get_rentals_by_status(&RentalStatus::Active).is_empty());
+    }
+
+    #[test]
+    fn terminating_leases_complete_on_the_effective_date_with_the_penalty_owed() {
+        let start = crate::clock::now();
+        let mut rental = store_rental(RentalStatus::Active, start, start + 90 * DAY_NANOS);
+        let effective_date = start + 30 * DAY_NANOS;
+        rental.terminate(Termination::new(rental.tenant, effective_date, 25_000_000));
+        rental_store::update_rental(rental.clone()).unwrap();
+
+        sweep(effective_date - 1);
+        assert_eq!(status_of(&rental), RentalStatus::Terminating);
+
+        sweep(effective_date);
+        let rental = rental_store::get_rental(rental.id).unwrap();
+        assert_eq!(rental.status, RentalStatus::Completed);
+        assert!(rental
+            .termination
+            .is_some_and(|termination| termination.is_penalty_outstanding()));
+    }
+}
@@ -1,2 +1,11 @@
+pub mod acceptance;
+pub mod assignment;
+pub mod attestation;
+pub mod calendar;
+pub mod icrc7;
+pub mod lifecycle;
 pub mod nft_minter;
+pub mod payments;
+pub mod renewal;
 pub mod rental_manager;
+pub mod termination;
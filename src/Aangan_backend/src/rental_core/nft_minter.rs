@@ -1,4 +1,7 @@
-use crate::types::NFTMetadata;
+use crate::storage::{nft_event_store, rental_store};
+use crate::types::{
+    AanganError, NFTEventKind, NFTMetadata, NFTStatus, RentalAgreement, RentalStatus,
+};
 use candid::Principal;
 
 pub fn create_rental_nft(
@@ -26,3 +29,73 @@ pub fn create_rental_nft(
         end_date,
     )
 }
+
+/// NFT status for a rental in `status`; requested rentals have no NFT yet.
+fn nft_status(status: &RentalStatus) -> Option<NFTStatus> {
+    match status {
+        RentalStatus::Requested | RentalStatus::Rejected => None,
+        RentalStatus::Confirmed => Some(NFTStatus::Confirmed),
+        RentalStatus::Active | RentalStatus::Terminating => Some(NFTStatus::Active),
+        RentalStatus::Completed => Some(NFTStatus::Completed),
+        RentalStatus::Cancelled | RentalStatus::Expired => Some(NFTStatus::Void),
+    }
+}
+
+/// Brings the rental's NFT in line with the rental status and records the change in the
+/// NFT's event history. Must be called whenever a rental with an NFT changes status.
+pub fn sync_status(rental: &RentalAgreement) -> Result<(), AanganError> {
+    let (Some(nft_id), Some(status)) = (rental.nft_id, nft_status(&rental.status)) else {
+        return Ok(());
+    };
+
+    let mut nft =
+        rental_store::get_nft(nft_id).ok_or_else(|| AanganError::not_found("NFT", nft_id))?;
+    // A void NFT stays void, even if an administrator voided it while the rental went on
+    if nft.status.as_ref() == Some(&status) || nft.status == Some(NFTStatus::Void) {
+        return Ok(());
+    }
+
+    let event = match status {
+        NFTStatus::Confirmed => NFTEventKind::Minted,
+        NFTStatus::Active => NFTEventKind::Activated,
+        NFTStatus::Completed => NFTEventKind::Completed,
+        NFTStatus::Void => NFTEventKind::Voided,
+        // Only a renewal supersedes an NFT, and it records its own event
+        NFTStatus::Superseded => return Ok(()),
+    };
+
+    nft.set_status(status);
+    rental_store::update_nft(nft)?;
+    nft_event_store::record_event(nft_id, event);
+
+    Ok(())
+}
+
+/// Voids an NFT regardless of its rental's status.
+pub fn void_nft(nft_id: u64) -> Result<NFTMetadata, AanganError> {
+    let mut nft =
+        rental_store::get_nft(nft_id).ok_or_else(|| AanganError::not_found("NFT", nft_id))?;
+    if nft.status == Some(NFTStatus::Void) {
+        return Err(AanganError::invalid_state("not Void", "Void"));
+    }
+
+    nft.set_status(NFTStatus::Void);
+    rental_store::update_nft(nft.clone())?;
+    nft_event_store::record_event(nft_id, NFTEventKind::Voided);
+
+    Ok(nft)
+}
+
+/// Derives the status of NFTs minted before they carried one.
+pub fn backfill_status() {
+    for nft in rental_store::get_all_nfts() {
+        if nft.status.is_some() {
+            continue;
+        }
+        if let Some(rental) = rental_store::get_rental(nft.rental_agreement_id) {
+            if let Err(err) = sync_status(&rental) {
+                ic_cdk::println!("NFT status backfill failed for NFT {}: {}", nft.id, err);
+            }
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use candid::Principal;
+use icrc_ledger_types::icrc1::account::Account;
+
+use crate::escrow::{self, ledger, LedgerGuard};
+use crate::storage::{payment_store, rental_store};
+use crate::types::{AanganError, RentPayment, RentalAgreement, RentalStatus};
+
+/// Length of one billing period (30 days) in nanoseconds.
+pub const BILLING_PERIOD_NANOS: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+
+/// Splits the lease term into monthly installments, the first one due on `start_date`.
+pub fn generate_schedule(rental: &RentalAgreement) -> Vec<RentPayment> {
+    let mut payments = Vec::new();
+    let mut due_date = rental.start_date;
+    let mut period = 0u32;
+
+    while due_date < rental.end_date {
+        payments.push(RentPayment::new(
+            rental.id,
+            period,
+            due_date,
+            rental.rent_amount,
+        ));
+        period += 1;
+        due_date = rental
+            .start_date
+            .saturating_add(BILLING_PERIOD_NANOS.saturating_mul(period as u64));
+    }
+
+    payments
+}
+
+pub fn create_schedule(rental: &RentalAgreement) -> Result<(), AanganError> {
+    payment_store::create_payments(generate_schedule(rental))
+}
+
+/// Schedules the installments a renewal added to the lease term. Installments that were
+/// already scheduled keep their amount; the new ones are due at the amended rent.
+pub fn extend_schedule(rental: &RentalAgreement) -> Result<(), AanganError> {
+    let next_period = payment_store::get_payments_by_rental(rental.id)
+        .last()
+        .map_or(0, |payment| payment.period + 1);
+    payment_store::create_payments(
+        generate_schedule(rental)
+            .into_iter()
+            .filter(|payment| payment.period >= next_period)
+            .collect(),
+    )
+}
+
+/// Waives the outstanding installments due on or after `from`, which fall outside a lease
+/// that ends early.
+pub fn waive_from(rental_id: u64, from: u64) -> Result<(), AanganError> {
+    for mut payment in payment_store::get_payments_by_rental(rental_id) {
+        if payment.due_date >= from && payment.is_outstanding() {
+            payment.waive();
+            payment_store::update_payment(payment)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the payment history with overdue installments reported as late.
+pub fn get_payment_history(rental_id: u64) -> Vec<RentPayment> {
+    let now = crate::clock::now();
+    payment_store::get_payments_by_rental(rental_id)
+        .into_iter()
+        .map(|mut payment| {
+            payment.refresh_status(now);
+            payment
+        })
+        .collect()
+}
+
+/// Whether any installment is overdue at `now`, whether or not it has been marked late yet.
+pub fn has_arrears(rental_id: u64, now: u64) -> bool {
+    payment_store::get_payments_by_rental(rental_id)
+        .iter()
+        .any(|payment| payment.is_outstanding() && now > payment.due_date)
+}
+
+/// Persists the late status of overdue installments.
+pub fn mark_late_payments(rental_id: u64, now: u64) {
+    for mut payment in payment_store::get_payments_by_rental(rental_id) {
+        if payment.refresh_status(now) {
+            let _ = payment_store::update_payment(payment);
+        }
+    }
+}
+
+/// Transfers one installment from the tenant to the landlord through the ICRC-2 ledger.
+pub async fn pay_rent(
+    rental_id: u64,
+    period: u32,
+    caller: Principal,
+) -> Result<RentPayment, AanganError> {
+    let rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+
+    if rental.tenant != caller {
+        return Err(AanganError::unauthorized("Only tenant can pay rent"));
+    }
+
+    // Rent stays due through the notice period of a terminating lease
+    if !matches!(
+        rental.status,
+        RentalStatus::Active | RentalStatus::Terminating
+    ) {
+        return Err(AanganError::invalid_state(
+            "Active or Terminating",
+            format!("{:?}", rental.status),
+        ));
+    }
+
+    let payment = payment_store::get_payment(rental_id, period)
+        .ok_or_else(|| AanganError::not_found("Payment", format!("{}/{}", rental_id, period)))?;
+
+    if !payment.is_outstanding() {
+        return Err(AanganError::invalid_state(
+            "Due or Late",
+            format!("{:?}", payment.status),
+        ));
+    }
+
+    let _guard = LedgerGuard::acquire(rental_id)?;
+    let block = ledger::transfer_from(
+        escrow::ledger_canister()?,
+        Account::from(rental.tenant),
+        Account::from(rental.landlord),
+        payment.amount,
+    )
+    .await?;
+
+    let mut payment = payment_store::get_payment(rental_id, period)
+        .ok_or_else(|| AanganError::not_found("Payment", format!("{}/{}", rental_id, period)))?;
+    payment.mark_paid(block);
+    payment_store::update_payment(payment.clone())?;
+
+    Ok(payment)
+}
+
+pub fn waive_rent(
+    rental_id: u64,
+    period: u32,
+    caller: Principal,
+) -> Result<RentPayment, AanganError> {
+    let rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+
+    if rental.landlord != caller {
+        return Err(AanganError::unauthorized("Only landlord can waive rent"));
+    }
+
+    if escrow::is_locked(rental_id) {
+        return Err(AanganError::conflict(
+            "A ledger operation is already in progress for this rental",
+        ));
+    }
+
+    let mut payment = payment_store::get_payment(rental_id, period)
+        .ok_or_else(|| AanganError::not_found("Payment", format!("{}/{}", rental_id, period)))?;
+
+    if !payment.is_outstanding() {
+        return Err(AanganError::invalid_state(
+            "Due or Late",
+            format!("{:?}", payment.status),
+        ));
+    }
+
+    payment.waive();
+    payment_store::update_payment(payment.clone())?;
+
+    Ok(payment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+
+    const START: u64 = 1_700_000_000_000_000_000;
+    const RENT: u64 = 25_000_000;
+
+    fn rental(end_date: u64) -> RentalAgreement {
+        RentalAgreement::new(
+            1,
+            1,
+            Principal::from_slice(&[2]),
+            Principal::from_slice(&[3]),
+            START,
+            end_date,
+            RENT,
+            0,
+        )
+    }
+
+    #[test]
+    fn schedule_covers_whole_periods() {
+        let schedule = generate_schedule(&rental(START + 3 * BILLING_PERIOD_NANOS));
+
+        assert_eq!(schedule.len(), 3);
+        for (period, payment) in schedule.iter().enumerate() {
+            assert_eq!(payment.period, period as u32);
+            assert_eq!(
+                payment.due_date,
+                START + period as u64 * BILLING_PERIOD_NANOS
+            );
+            assert_eq!(payment.amount, RENT);
+        }
+    }
+
+    #[test]
+    fn partial_period_is_billed_in_full() {
+        let schedule = generate_schedule(&rental(START + 3 * BILLING_PERIOD_NANOS + 1));
+
+        assert_eq!(schedule.len(), 4);
+        assert_eq!(
+            schedule.last().unwrap().due_date,
+            START + 3 * BILLING_PERIOD_NANOS
+        );
+        assert_eq!(schedule.last().unwrap().amount, RENT);
+    }
+
+    #[test]
+    fn short_lease_has_one_installment_due_at_start() {
+        let schedule = generate_schedule(&rental(START + 1));
+
+        assert_eq!(schedule.len(), 1);
+        assert_eq!(schedule[0].due_date, START);
+    }
+
+    #[test]
+    fn empty_lease_has_no_installments() {
+        assert!(generate_schedule(&rental(START)).is_empty());
+    }
+}
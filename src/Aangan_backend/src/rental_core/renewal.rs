@@ -0,0 +1,306 @@
+//! Lease renewal: the landlord or the tenant proposes a later end date, and optionally a
+//! new rent, and the other party accepts it.
+//!
+//! A proposal carries the terms hash of the amended agreement and counts as the proposer's
+//! signature of it; the other party accepts by naming the same hash. Accepting amends the
+//! agreement in place, replaces the acceptances of the old terms with the two signatures
+//! of the new ones and mints a successor NFT for the renewed term. Only one proposal can be
+//! pending per rental; accepted proposals form its amendment chain.
+
+use crate::escrow;
+use crate::rental_core::{acceptance, calendar, rental_manager};
+use crate::storage::{renewal_store, rental_store};
+use crate::types::{
+    AanganError, DateRange, LeaseRenewal, NFTStatus, RenewalStatus, RentalAgreement,
+};
+use candid::Principal;
+
+fn get_rental(rental_id: u64) -> Result<RentalAgreement, AanganError> {
+    rental_store::get_rental(rental_id).ok_or_else(|| AanganError::not_found("Rental", rental_id))
+}
+
+fn ensure_renewable(rental: &RentalAgreement) -> Result<(), AanganError> {
+    if !rental.is_renewable() {
+        return Err(AanganError::invalid_state(
+            "Confirmed or Active",
+            format!("{:?}", rental.status),
+        ));
+    }
+
+    // A successor would turn a lease voided by an administrator back into a valid token
+    let nft = rental.nft_id.and_then(rental_store::get_nft);
+    if nft.is_some_and(|nft| nft.status == Some(NFTStatus::Void)) {
+        return Err(AanganError::invalid_state("valid rental NFT", "Void"));
+    }
+
+    Ok(())
+}
+
+fn ensure_free(rental: &RentalAgreement, new_end_date: u64) -> Result<(), AanganError> {
+    let period = DateRange {
+        start: rental.start_date,
+        end: new_end_date,
+    };
+    if !calendar::is_free(rental.property_id, &period, Some(rental.id)) {
+        return Err(AanganError::conflict(
+            "The extended term overlaps another lease",
+        ));
+    }
+    Ok(())
+}
+
+pub fn propose_renewal(
+    rental_id: u64,
+    new_end_date: u64,
+    new_rent_amount: Option<u64>,
+    caller: Principal,
+) -> Result<LeaseRenewal, AanganError> {
+    let rental = get_rental(rental_id)?;
+
+    let counterparty = if caller == rental.landlord {
+        rental.tenant
+    } else if caller == rental.tenant {
+        rental.landlord
+    } else {
+        return Err(AanganError::unauthorized(
+            "Only landlord or tenant can propose a renewal",
+        ));
+    };
+
+    ensure_renewable(&rental)?;
+
+    if new_end_date <= rental.end_date {
+        return Err(AanganError::validation(
+            "new_end_date",
+            "must be after the current end_date",
+        ));
+    }
+
+    if new_rent_amount == Some(0) {
+        return Err(AanganError::validation(
+            "new_rent_amount",
+            "must be greater than zero",
+        ));
+    }
+
+    ensure_free(&rental, new_end_date)?;
+
+    if renewal_store::get_pending_renewal(rental_id).is_some() {
+        return Err(AanganError::conflict("A lease renewal is already pending"));
+    }
+
+    let new_rent_amount = new_rent_amount.unwrap_or(rental.rent_amount);
+    let mut amended = rental.clone();
+    amended.amend(new_end_date, new_rent_amount);
+
+    let renewal = LeaseRenewal::new(
+        rental_id,
+        renewal_store::next_sequence(rental_id),
+        caller,
+        counterparty,
+        rental.end_date,
+        new_end_date,
+        rental.rent_amount,
+        new_rent_amount,
+        acceptance::terms_hash(&amended),
+    );
+    renewal_store::create_renewal(renewal.clone())?;
+
+    Ok(renewal)
+}
+
+/// Accepts the pending renewal whose amended terms hash to `terms_hash`. The rental, both
+/// NFTs, the payment schedule and the renewal record are all written in this one message,
+/// so they change together or not at all.
+pub fn accept_renewal(
+    rental_id: u64,
+    terms_hash: &[u8],
+    caller: Principal,
+) -> Result<LeaseRenewal, AanganError> {
+    let rental = get_rental(rental_id)?;
+
+    let mut renewal = renewal_store::get_pending_renewal(rental_id)
+        .ok_or_else(|| AanganError::not_found("Pending renewal", rental_id))?;
+
+    if renewal.counterparty != caller {
+        return Err(AanganError::unauthorized(
+            "Only the other party can accept a lease renewal",
+        ));
+    }
+
+    if terms_hash != renewal.terms_hash.as_slice() {
+        return Err(AanganError::conflict(
+            "The renewal terms have changed since they were reviewed",
+        ));
+    }
+
+    ensure_renewable(&rental)?;
+
+    // The lease was assigned or its term changed since the proposal was made
+    let parties = [rental.landlord, rental.tenant];
+    if !parties.contains(&renewal.proposed_by)
+        || !parties.contains(&renewal.counterparty)
+        || rental.end_date != renewal.previous_end_date
+        || rental.rent_amount != renewal.previous_rent_amount
+    {
+        return Err(AanganError::conflict(
+            "The lease has changed since the renewal was proposed",
+        ));
+    }
+
+    ensure_free(&rental, renewal.new_end_date)?;
+
+    // A rent payment in flight was priced against the current schedule
+    if escrow::is_locked(rental_id) {
+        return Err(AanganError::conflict(
+            "A ledger operation is already in progress for this rental",
+        ));
+    }
+
+    rental_manager::renew_rental(rental_id, &mut renewal)?;
+    renewal_store::update_renewal(renewal.clone())?;
+
+    Ok(renewal)
+}
+
+pub fn reject_renewal(rental_id: u64, caller: Principal) -> Result<LeaseRenewal, AanganError> {
+    let pending = renewal_store::get_pending_renewal(rental_id)
+        .ok_or_else(|| AanganError::not_found("Pending renewal", rental_id))?;
+
+    if pending.counterparty != caller {
+        return Err(AanganError::unauthorized(
+            "Only the other party can reject a lease renewal",
+        ));
+    }
+
+    resolve_pending(pending, RenewalStatus::Rejected)
+}
+
+pub fn withdraw_renewal(rental_id: u64, caller: Principal) -> Result<LeaseRenewal, AanganError> {
+    let pending = renewal_store::get_pending_renewal(rental_id)
+        .ok_or_else(|| AanganError::not_found("Pending renewal", rental_id))?;
+
+    if pending.proposed_by != caller {
+        return Err(AanganError::unauthorized(
+            "Only the proposing party can withdraw a lease renewal",
+        ));
+    }
+
+    resolve_pending(pending, RenewalStatus::Withdrawn)
+}
+
+fn resolve_pending(
+    mut renewal: LeaseRenewal,
+    status: RenewalStatus,
+) -> Result<LeaseRenewal, AanganError> {
+    renewal.resolve(status);
+    renewal_store::update_renewal(renewal.clone())?;
+    Ok(renewal)
+}
+
+pub fn get_pending_renewal(
+    rental_id: u64,
+    caller: Principal,
+) -> Result<Option<LeaseRenewal>, AanganError> {
+    let rental = get_rental(rental_id)?;
+
+    if rental.landlord != caller && rental.tenant != caller {
+        return Err(AanganError::unauthorized("Access denied"));
+    }
+
+    Ok(renewal_store::get_pending_renewal(rental_id))
+}
+
+/// Every renewal proposed for the lease, oldest first. The accepted ones are its amendment
+/// chain. The landlord, the current tenant and every party to an earlier proposal may read it.
+pub fn get_renewal_history(
+    rental_id: u64,
+    caller: Principal,
+) -> Result<Vec<LeaseRenewal>, AanganError> {
+    let rental = get_rental(rental_id)?;
+
+    let history = renewal_store::get_renewal_history(rental_id);
+
+    let is_party = rental.landlord == caller
+        || rental.tenant == caller
+        || history
+            .iter()
+            .any(|renewal| renewal.proposed_by == caller || renewal.counterparty == caller);
+
+    if !is_party {
+        return Err(AanganError::unauthorized("Access denied"));
+    }
+
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::get_next_rental_id;
+    use crate::types::{Acceptance, RentalStatus};
+
+    const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+    const START: u64 = 1_800_000_000_000_000_000;
+
+    fn landlord() -> Principal {
+        Principal::from_slice(&[2])
+    }
+
+    fn tenant() -> Principal {
+        Principal::from_slice(&[3])
+    }
+
+    fn store_signed_rental() -> RentalAgreement {
+        let mut rental = RentalAgreement::new(
+            get_next_rental_id(),
+            1,
+            landlord(),
+            tenant(),
+            START,
+            START + 30 * DAY_NANOS,
+            25_000_000,
+            0,
+        );
+        rental.status = RentalStatus::Active;
+        let hash = acceptance::terms_hash(&rental);
+        rental.record_acceptance(Acceptance::new(landlord(), hash));
+        rental.record_acceptance(Acceptance::new(tenant(), hash));
+        rental_store::create_rental(rental.clone()).unwrap();
+        rental
+    }
+
+    #[test]
+    fn proposals_carry_the_hash_of_the_amended_terms() {
+        let rental = store_signed_rental();
+        let new_end_date = rental.end_date + 30 * DAY_NANOS;
+
+        let renewal = propose_renewal(rental.id, new_end_date, Some(30_000_000), tenant()).unwrap();
+
+        let mut amended = rental.clone();
+        amended.amend(new_end_date, 30_000_000);
+        assert_eq!(
+            renewal.terms_hash.as_slice(),
+            acceptance::terms_hash(&amended).as_slice()
+        );
+        // What the parties signed before does not cover the renewed lease
+        assert!(!rental.is_signed_by(&tenant(), &renewal.terms_hash));
+        assert!(renewal
+            .acceptances()
+            .iter()
+            .all(|signature| signature.terms_hash == renewal.terms_hash));
+    }
+
+    #[test]
+    fn accepting_requires_the_proposed_terms_hash() {
+        let rental = store_signed_rental();
+        propose_renewal(rental.id, rental.end_date + DAY_NANOS, None, tenant()).unwrap();
+
+        let stale = acceptance::terms_hash(&rental);
+        assert!(matches!(
+            accept_renewal(rental.id, &stale, landlord()),
+            Err(AanganError::Conflict(_))
+        ));
+        assert!(renewal_store::get_pending_renewal(rental.id).is_some());
+    }
+}
@@ -1,41 +1,147 @@
-use crate::rental_core::nft_minter;
-use crate::storage::{property_store, rental_store};
-use crate::types::RentalAgreement;
+use crate::rental_core::{acceptance, nft_minter, payments};
+use crate::storage::{nft_event_store, property_store, rental_store};
+use crate::types::{AanganError, ImageAsset, LeaseRenewal, NFTEventKind, RentalAgreement};
 
-pub fn mint_rental_nft(rental: &RentalAgreement) -> Result<u64, String> {
+pub fn mint_rental_nft(rental: &RentalAgreement) -> Result<u64, AanganError> {
+    mint_nft(rental, None)
+}
+
+fn mint_nft(rental: &RentalAgreement, predecessor_id: Option<u64>) -> Result<u64, AanganError> {
     let property = property_store::get_property(rental.property_id)
-        .ok_or_else(|| "Property not found".to_string())?;
+        .ok_or_else(|| AanganError::not_found("Property", rental.property_id))?;
 
     let nft_id = crate::storage::get_next_nft_id();
 
-    let nft = nft_minter::create_rental_nft(
+    let mut nft = nft_minter::create_rental_nft(
         nft_id,
         rental.tenant,
         rental.property_id,
         rental.id,
         property.title.clone(),
         property.address.clone(),
-        property.images.get(0).cloned().unwrap_or_default(),
+        property
+            .images
+            .first()
+            .and_then(|id| id.parse().ok())
+            .map(ImageAsset::url)
+            .unwrap_or_default(),
         rental.rent_amount,
         rental.start_date,
         rental.end_date,
     );
+    if let Some(predecessor_id) = predecessor_id {
+        nft.set_predecessor(predecessor_id);
+    }
 
     rental_store::create_nft(nft)?;
+    nft_event_store::record_event(nft_id, NFTEventKind::Minted);
 
     Ok(nft_id)
 }
 
-pub fn activate_rental(rental_id: u64) -> Result<(), String> {
-    let mut rental =
-        rental_store::get_rental(rental_id).ok_or_else(|| "Rental not found".to_string())?;
+/// Amends the rental with the renewal's terms, which both parties must have signed, and
+/// mints a successor NFT carrying them. The rental's previous NFT is superseded and linked
+/// to the successor in both directions.
+pub fn renew_rental(
+    rental_id: u64,
+    renewal: &mut LeaseRenewal,
+) -> Result<RentalAgreement, AanganError> {
+    let mut rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+
+    if !rental.is_renewable() {
+        return Err(AanganError::invalid_state(
+            "Confirmed or Active",
+            format!("{:?}", rental.status),
+        ));
+    }
+
+    let predecessor_id = rental.nft_id;
+    rental.amend(renewal.new_end_date, renewal.new_rent_amount);
+    if acceptance::terms_hash(&rental).as_slice() != renewal.terms_hash.as_slice() {
+        return Err(AanganError::conflict(
+            "The lease has changed since the renewal was proposed",
+        ));
+    }
+    for signature in renewal.acceptances() {
+        rental.record_acceptance(signature);
+    }
+    let nft_id = mint_nft(&rental, predecessor_id)?;
+    rental.set_nft(nft_id);
+
+    if let Some(predecessor_id) = predecessor_id {
+        let mut predecessor = rental_store::get_nft(predecessor_id)
+            .ok_or_else(|| AanganError::not_found("NFT", predecessor_id))?;
+        predecessor.supersede(nft_id);
+        rental_store::update_nft(predecessor)?;
+        nft_event_store::record_event(predecessor_id, NFTEventKind::Renewed { successor: nft_id });
+    }
+
+    // Leases that have not started get their whole schedule at the new rent on activation
+    if rental.status == crate::types::RentalStatus::Active {
+        payments::extend_schedule(&rental)?;
+    }
+    rental_store::update_rental(rental.clone())?;
+    nft_minter::sync_status(&rental)?;
+    renewal.accept(predecessor_id, nft_id);
+
+    Ok(rental)
+}
+
+pub fn activate_rental(rental_id: u64) -> Result<RentalAgreement, AanganError> {
+    let mut rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
 
     if rental.status != crate::types::RentalStatus::Confirmed {
-        return Err("Rental must be confirmed before activation".to_string());
+        return Err(AanganError::invalid_state(
+            "Confirmed",
+            format!("{:?}", rental.status),
+        ));
     }
 
     rental.activate();
-    rental_store::update_rental(rental)?;
+    payments::create_schedule(&rental)?;
+    rental_store::update_rental(rental.clone())?;
+    nft_minter::sync_status(&rental)?;
+
+    Ok(rental)
+}
+
+pub fn complete_rental(rental_id: u64) -> Result<RentalAgreement, AanganError> {
+    let mut rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+
+    if !matches!(
+        rental.status,
+        crate::types::RentalStatus::Active | crate::types::RentalStatus::Terminating
+    ) {
+        return Err(AanganError::invalid_state(
+            "Active or Terminating",
+            format!("{:?}", rental.status),
+        ));
+    }
+
+    rental.complete();
+    rental_store::update_rental(rental.clone())?;
+    nft_minter::sync_status(&rental)?;
+
+    Ok(rental)
+}
+
+pub fn expire_rental(rental_id: u64) -> Result<RentalAgreement, AanganError> {
+    let mut rental = rental_store::get_rental(rental_id)
+        .ok_or_else(|| AanganError::not_found("Rental", rental_id))?;
+
+    if rental.status != crate::types::RentalStatus::Requested {
+        return Err(AanganError::invalid_state(
+            "Requested",
+            format!("{:?}", rental.status),
+        ));
+    }
+
+    rental.expire();
+    rental_store::update_rental(rental.clone())?;
+    nft_minter::sync_status(&rental)?;
 
-    Ok(())
+    Ok(rental)
 }
@@ -0,0 +1,196 @@
+//! Early termination: the landlord or the tenant gives notice on an Active lease.
+//!
+//! The lease turns Terminating and stays occupied for the agreement's notice period, after
+//! which the lifecycle sweep completes it and frees the property's calendar. The party that
+//! gave notice owes the other the penalty set in the agreement's termination terms. An
+//! unpaid penalty does not hold the lease open: it stays on the termination record as a
+//! debt, which can be paid after completion. A penalty the tenant still owes when their
+//! deposit is refunded is taken out of the deposit first, if the deposit covers it.
+
+use candid::Principal;
+use icrc_ledger_types::icrc1::account::Account;
+
+use crate::escrow::{self, ledger, LedgerGuard};
+use crate::rental_core::{nft_minter, payments};
+use crate::storage::rental_store;
+use crate::types::{
+    AanganError, EscrowStatus, RentalAgreement, RentalStatus, Termination, TerminationTerms,
+};
+
+fn get_rental(rental_id: u64) -> Result<RentalAgreement, AanganError> {
+    rental_store::get_rental(rental_id).ok_or_else(|| AanganError::not_found("Rental", rental_id))
+}
+
+/// Sets the termination terms of an application. They are part of the signed terms, so
+/// earlier acceptances no longer apply.
+pub fn set_termination_terms(
+    rental_id: u64,
+    terms: TerminationTerms,
+    caller: Principal,
+) -> Result<RentalAgreement, AanganError> {
+    let mut rental = get_rental(rental_id)?;
+
+    if rental.landlord != caller {
+        return Err(AanganError::unauthorized(
+            "Only landlord can set termination terms",
+        ));
+    }
+
+    if rental.status != RentalStatus::Requested {
+        return Err(AanganError::invalid_state(
+            "Requested",
+            format!("{:?}", rental.status),
+        ));
+    }
+
+    // The application may be accepted against the current terms right now
+    if escrow::is_locked(rental_id) {
+        return Err(AanganError::conflict(
+            "A ledger operation is already in progress for this rental",
+        ));
+    }
+
+    rental.set_termination_terms(terms);
+    rental_store::update_rental(rental.clone())?;
+
+    Ok(rental)
+}
+
+/// Gives notice on an Active lease. Installments due on or after the effective date are
+/// waived.
+pub fn request_termination(
+    rental_id: u64,
+    caller: Principal,
+) -> Result<RentalAgreement, AanganError> {
+    let mut rental = get_rental(rental_id)?;
+
+    if rental.landlord != caller && rental.tenant != caller {
+        return Err(AanganError::unauthorized(
+            "Only landlord or tenant can terminate rental",
+        ));
+    }
+
+    if rental.status != RentalStatus::Active {
+        return Err(AanganError::invalid_state(
+            "Active",
+            format!("{:?}", rental.status),
+        ));
+    }
+
+    if escrow::is_locked(rental_id) {
+        return Err(AanganError::conflict(
+            "A ledger operation is already in progress for this rental",
+        ));
+    }
+
+    let terms = rental.termination_terms();
+    let effective_date = ic_cdk::api::time().saturating_add(terms.notice_period_nanos());
+    if effective_date >= rental.end_date {
+        return Err(AanganError::conflict(
+            "The lease ends before the notice period would elapse",
+        ));
+    }
+
+    rental.terminate(Termination::new(
+        caller,
+        effective_date,
+        terms.penalty_amount(rental.rent_amount),
+    ));
+    payments::waive_from(rental_id, effective_date)?;
+    rental_store::update_rental(rental.clone())?;
+    nft_minter::sync_status(&rental)?;
+
+    Ok(rental)
+}
+
+/// Transfers the termination penalty from the party that gave notice to the other party
+/// through the ICRC-2 ledger.
+pub async fn pay_termination_penalty(
+    rental_id: u64,
+    caller: Principal,
+) -> Result<RentalAgreement, AanganError> {
+    let rental = get_rental(rental_id)?;
+
+    let termination = rental
+        .termination
+        .clone()
+        .ok_or_else(|| AanganError::not_found("Termination", rental_id))?;
+
+    if termination.requested_by != caller {
+        return Err(AanganError::unauthorized(
+            "Only the party that gave notice pays the penalty",
+        ));
+    }
+
+    if !termination.is_penalty_outstanding() {
+        return Err(AanganError::conflict(
+            "No termination penalty is outstanding",
+        ));
+    }
+
+    let recipient = if caller == rental.landlord {
+        rental.tenant
+    } else {
+        rental.landlord
+    };
+
+    let _guard = LedgerGuard::acquire(rental_id)?;
+    let block = ledger::transfer_from(
+        escrow::ledger_canister()?,
+        Account::from(caller),
+        Account::from(recipient),
+        termination.penalty_amount,
+    )
+    .await?;
+
+    record_penalty_paid(rental_id, block)
+}
+
+fn record_penalty_paid(rental_id: u64, block: u64) -> Result<RentalAgreement, AanganError> {
+    // Re-read the agreement: it may have changed while the transfer was in flight
+    let mut rental = get_rental(rental_id)?;
+    let mut termination = rental
+        .termination
+        .clone()
+        .ok_or_else(|| AanganError::not_found("Termination", rental_id))?;
+    termination.mark_penalty_paid(block);
+    rental.termination = Some(termination);
+    rental.updated_at = ic_cdk::api::time();
+    rental_store::update_rental(rental.clone())?;
+
+    Ok(rental)
+}
+
+/// Whether the outstanding penalty is owed by the tenant and can be taken from their
+/// funded deposit. A deposit left by an earlier tenant of an assigned lease is not theirs
+/// to draw on. The ledger fee is checked when the deposit is drawn.
+pub fn is_penalty_covered_by_deposit(rental: &RentalAgreement) -> bool {
+    let Some(termination) = &rental.termination else {
+        return false;
+    };
+    termination.is_penalty_outstanding()
+        && termination.requested_by == rental.tenant
+        && rental.escrow.as_ref().is_some_and(|escrow| {
+            escrow.status == EscrowStatus::Funded
+                && escrow.depositor_or(rental.tenant) == rental.tenant
+                && escrow.amount > termination.penalty_amount
+        })
+}
+
+/// Pays the tenant's outstanding penalty to the landlord out of their deposit.
+pub async fn settle_penalty_from_deposit(rental_id: u64) -> Result<RentalAgreement, AanganError> {
+    let rental = get_rental(rental_id)?;
+
+    if !is_penalty_covered_by_deposit(&rental) {
+        return Err(AanganError::conflict(
+            "The deposit cannot cover the termination penalty",
+        ));
+    }
+    let penalty_amount = rental
+        .termination
+        .as_ref()
+        .map_or(0, |termination| termination.penalty_amount);
+
+    let block = escrow::deduct(rental_id, rental.landlord, penalty_amount).await?;
+    record_penalty_paid(rental_id, block)
+}
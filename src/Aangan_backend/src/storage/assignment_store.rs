@@ -0,0 +1,59 @@
+use super::LEASE_ASSIGNMENTS;
+use crate::types::{AanganError, LeaseAssignment, Stored};
+
+/// Assignments of a rental are keyed by `(rental_id, sequence)`, so the last entry is the
+/// most recent one and at most that one can still be pending.
+pub fn get_latest_assignment(rental_id: u64) -> Option<LeaseAssignment> {
+    LEASE_ASSIGNMENTS.with(|store| {
+        store
+            .borrow()
+            .range((rental_id, 0)..=(rental_id, u32::MAX))
+            .last()
+            .map(|(_, assignment)| assignment.into_value())
+    })
+}
+
+pub fn get_pending_assignment(rental_id: u64) -> Option<LeaseAssignment> {
+    get_latest_assignment(rental_id).filter(LeaseAssignment::is_pending)
+}
+
+pub fn next_sequence(rental_id: u64) -> u32 {
+    get_latest_assignment(rental_id).map_or(0, |assignment| assignment.sequence + 1)
+}
+
+pub fn create_assignment(assignment: LeaseAssignment) -> Result<(), AanganError> {
+    LEASE_ASSIGNMENTS.with(|store| {
+        let mut store = store.borrow_mut();
+        let key = (assignment.rental_id, assignment.sequence);
+        if store.contains_key(&key) {
+            return Err(AanganError::conflict("Assignment already exists"));
+        }
+        store.insert(key, Stored::new(assignment));
+        Ok(())
+    })
+}
+
+pub fn update_assignment(assignment: LeaseAssignment) -> Result<(), AanganError> {
+    LEASE_ASSIGNMENTS.with(|store| {
+        let mut store = store.borrow_mut();
+        let key = (assignment.rental_id, assignment.sequence);
+        if !store.contains_key(&key) {
+            return Err(AanganError::not_found(
+                "Assignment",
+                format!("{}/{}", assignment.rental_id, assignment.sequence),
+            ));
+        }
+        store.insert(key, Stored::new(assignment));
+        Ok(())
+    })
+}
+
+pub fn get_assignment_history(rental_id: u64) -> Vec<LeaseAssignment> {
+    LEASE_ASSIGNMENTS.with(|store| {
+        store
+            .borrow()
+            .range((rental_id, 0)..=(rental_id, u32::MAX))
+            .map(|(_, assignment)| assignment.into_value())
+            .collect()
+    })
+}
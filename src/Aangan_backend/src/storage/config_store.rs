@@ -0,0 +1,21 @@
+use super::CONFIG;
+use crate::types::{Config, InitArgs};
+
+pub fn get_config() -> Config {
+    CONFIG.with(|config| config.borrow().get().clone())
+}
+
+pub fn apply_init_args(args: InitArgs) {
+    update_config(|config| config.apply(args));
+}
+
+/// Applies `f` to the stored config and writes it back. Returns what `f` returned.
+pub fn update_config<R>(f: impl FnOnce(&mut Config) -> R) -> R {
+    CONFIG.with(|config| {
+        let mut config = config.borrow_mut();
+        let mut updated = config.get().clone();
+        let result = f(&mut updated);
+        config.set(updated).expect("Failed to write config cell");
+        result
+    })
+}
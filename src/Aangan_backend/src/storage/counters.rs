@@ -0,0 +1,47 @@
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+
+/// ID allocators kept in stable memory so they survive upgrades.
+///
+/// Encoded as consecutive little-endian `u64`s; counters missing from an older, shorter
+/// encoding decode as zero, so new allocators can be appended without a migration.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IdCounters {
+    pub property: u64,
+    pub rental: u64,
+    pub nft: u64,
+    pub image: u64,
+}
+
+impl IdCounters {
+    fn fields(&self) -> [u64; 4] {
+        [self.property, self.rental, self.nft, self.image]
+    }
+}
+
+impl Storable for IdCounters {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(
+            self.fields()
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+        )
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let mut values = bytes
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()));
+        let mut next = || values.next().unwrap_or(0);
+        Self {
+            property: next(),
+            rental: next(),
+            nft: next(),
+            image: next(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
@@ -0,0 +1,304 @@
+//! Geohash index over property locations.
+//!
+//! A location is encoded as a 52-bit integer geohash (26 bits of longitude interleaved with
+//! 26 bits of latitude). All points inside a geohash cell share a key prefix, so every cell
+//! is one contiguous range of the `(geohash, property_id)` index.
+
+use super::GEO_INDEX;
+use crate::types::{GeoPoint, Property};
+
+const BITS_PER_AXIS: u32 = 26;
+const EARTH_RADIUS_M: f64 = 6_371_008.8;
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// Upper bound on the number of cells scanned for a bounding box.
+const MAX_BBOX_CELLS: u64 = 64;
+
+fn quantize(value: f64, min: f64, max: f64, bits: u32) -> u64 {
+    let cells = 1u64 << bits;
+    let scaled = ((value - min) / (max - min) * cells as f64).floor();
+    (scaled.max(0.0) as u64).min(cells - 1)
+}
+
+fn interleave(x: u64, y: u64, bits: u32) -> u64 {
+    let mut hash = 0u64;
+    for bit in (0..bits).rev() {
+        hash = (hash << 1) | ((x >> bit) & 1);
+        hash = (hash << 1) | ((y >> bit) & 1);
+    }
+    hash
+}
+
+fn cell_of(point: &GeoPoint, bits: u32) -> (u64, u64) {
+    (
+        quantize(point.longitude, -180.0, 180.0, bits),
+        quantize(point.latitude, -90.0, 90.0, bits),
+    )
+}
+
+pub fn geohash(point: &GeoPoint) -> u64 {
+    let (x, y) = cell_of(point, BITS_PER_AXIS);
+    interleave(x, y, BITS_PER_AXIS)
+}
+
+/// Great-circle distance in meters.
+pub fn haversine_m(a: &GeoPoint, b: &GeoPoint) -> f64 {
+    let (lat1, lat2) = (a.latitude.to_radians(), b.latitude.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (b.longitude - a.longitude).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().asin()
+}
+
+/// Ids of properties in cell `(x, y)` at `bits` bits of precision per axis.
+fn scan_cell(x: u64, y: u64, bits: u32, out: &mut Vec<u64>) {
+    let shift = 2 * (BITS_PER_AXIS - bits);
+    let start = interleave(x, y, bits) << shift;
+    let end = start + (1u64 << shift);
+    GEO_INDEX.with(|index| {
+        out.extend(
+            index
+                .borrow()
+                .range((start, 0)..(end, 0))
+                .map(|((_, property_id), _)| property_id),
+        );
+    });
+}
+
+/// Ids of properties that may lie within `radius_m` of `center`: the cell containing the
+/// center and its eight neighbours, at the finest precision whose cells are still at
+/// least `radius_m` across.
+pub fn candidates_near(center: &GeoPoint, radius_m: f64) -> Vec<u64> {
+    let lon_scale = center.latitude.to_radians().cos().max(1e-6);
+    let bits = (0..=BITS_PER_AXIS)
+        .rev()
+        .find(|bits| {
+            let cells = (1u64 << bits) as f64;
+            let height_m = 180.0 / cells * METERS_PER_DEGREE;
+            let width_m = 360.0 / cells * METERS_PER_DEGREE * lon_scale;
+            height_m >= radius_m && width_m >= radius_m
+        })
+        .unwrap_or(0);
+
+    let mut ids = Vec::new();
+    if bits == 0 {
+        scan_cell(0, 0, 0, &mut ids);
+        return ids;
+    }
+
+    let cells = 1u64 << bits;
+    let (x, y) = cell_of(center, bits);
+    let mut columns = vec![(x + cells - 1) % cells, x, (x + 1) % cells];
+    columns.sort_unstable();
+    columns.dedup();
+    let rows = y.saturating_sub(1)..=(y + 1).min(cells - 1);
+
+    for column in columns {
+        for row in rows.clone() {
+            scan_cell(column, row, bits, &mut ids);
+        }
+    }
+    ids
+}
+
+/// Ids of properties that may lie inside the box. A `min_lon` greater than `max_lon`
+/// denotes a box crossing the antimeridian.
+pub fn candidates_in_bbox(min: &GeoPoint, max: &GeoPoint) -> Vec<u64> {
+    let column_ranges = |bits: u32| {
+        let (min_x, _) = cell_of(min, bits);
+        let (max_x, _) = cell_of(max, bits);
+        if min.longitude <= max.longitude {
+            vec![(min_x, max_x)]
+        } else {
+            vec![(min_x, (1u64 << bits) - 1), (0, max_x)]
+        }
+    };
+    let row_range = |bits: u32| (cell_of(min, bits).1, cell_of(max, bits).1);
+    let cell_count = |bits: u32| {
+        let (min_y, max_y) = row_range(bits);
+        let columns: u64 = column_ranges(bits)
+            .iter()
+            .map(|(from, to)| to - from + 1)
+            .sum();
+        columns * (max_y - min_y + 1)
+    };
+
+    let bits = (0..=BITS_PER_AXIS)
+        .rev()
+        .find(|bits| cell_count(*bits) <= MAX_BBOX_CELLS)
+        .unwrap_or(0);
+
+    let mut ids = Vec::new();
+    let (min_y, max_y) = row_range(bits);
+    for (from, to) in column_ranges(bits) {
+        for column in from..=to {
+            for row in min_y..=max_y {
+                scan_cell(column, row, bits, &mut ids);
+            }
+        }
+    }
+    ids
+}
+
+pub fn index_property(property: &Property) {
+    if let Some(location) = &property.location {
+        GEO_INDEX.with(|index| {
+            index
+                .borrow_mut()
+                .insert((geohash(location), property.id), ())
+        });
+    }
+}
+
+pub fn unindex_property(property: &Property) {
+    if let Some(location) = &property.location {
+        GEO_INDEX.with(|index| index.borrow_mut().remove(&(geohash(location), property.id)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PropertyType;
+    use candid::Principal;
+
+    fn point(latitude: f64, longitude: f64) -> GeoPoint {
+        GeoPoint {
+            latitude,
+            longitude,
+        }
+    }
+
+    fn index_at(id: u64, location: GeoPoint) {
+        let mut property = Property::new(
+            id,
+            Principal::from_slice(&[2]),
+            "Flat".to_string(),
+            String::new(),
+            String::new(),
+            25_000_000,
+            100_000_000,
+            PropertyType::Apartment,
+            2,
+            1,
+            800,
+            vec![],
+            vec![],
+        );
+        property.location = Some(location);
+        index_property(&property);
+    }
+
+    fn sorted(mut ids: Vec<u64>) -> Vec<u64> {
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn haversine_matches_known_distances() {
+        let one_degree = EARTH_RADIUS_M * std::f64::consts::PI / 180.0;
+        assert_close(
+            haversine_m(&point(0.0, 0.0), &point(0.0, 1.0)),
+            one_degree,
+            1.0,
+        );
+        assert_close(
+            haversine_m(&point(-90.0, 0.0), &point(90.0, 0.0)),
+            180.0 * one_degree,
+            1.0,
+        );
+        // Crossing the antimeridian is the short way round
+        assert_close(
+            haversine_m(&point(0.0, 179.5), &point(0.0, -179.5)),
+            one_degree,
+            1.0,
+        );
+        // All meridians meet at the pole
+        assert_close(
+            haversine_m(&point(90.0, 0.0), &point(90.0, 120.0)),
+            0.0,
+            1e-6,
+        );
+    }
+
+    #[test]
+    fn geohash_shares_prefix_within_a_cell() {
+        let a = geohash(&point(12.9716, 77.5946));
+        let b = geohash(&point(12.9717, 77.5947));
+        let far = geohash(&point(-33.8688, 151.2093));
+        assert_eq!(a >> 20, b >> 20);
+        assert_ne!(a >> 40, far >> 40);
+    }
+
+    #[test]
+    fn geohash_clamps_the_edges_of_the_grid() {
+        let max = (1u64 << (2 * BITS_PER_AXIS)) - 1;
+        assert_eq!(geohash(&point(90.0, 180.0)), max);
+        assert_eq!(geohash(&point(-90.0, -180.0)), 0);
+    }
+
+    #[test]
+    fn nearby_search_finds_neighbouring_cells() {
+        index_at(1, point(12.9716, 77.5946));
+        index_at(2, point(12.9800, 77.6000));
+        index_at(3, point(13.5, 78.0));
+
+        let ids = sorted(candidates_near(&point(12.9716, 77.5946), 2_000.0));
+        assert!(ids.contains(&1) && ids.contains(&2));
+        assert!(!ids.contains(&3));
+    }
+
+    #[test]
+    fn nearby_search_wraps_around_the_antimeridian() {
+        index_at(1, point(0.0, 179.999));
+        index_at(2, point(0.0, -179.999));
+        index_at(3, point(0.0, 0.0));
+
+        assert_eq!(
+            sorted(candidates_near(&point(0.0, 179.9995), 1_000.0)),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn nearby_search_near_the_pole_covers_all_longitudes() {
+        index_at(1, point(89.999, 0.0));
+        index_at(2, point(89.999, 180.0));
+
+        // Both lie within 200 m of the center, on opposite sides of the pole
+        let ids = sorted(candidates_near(&point(89.999, 90.0), 1_000.0));
+        assert_eq!(ids, vec![1, 2]);
+    }
+
+    #[test]
+    fn bbox_crossing_the_antimeridian_covers_both_sides() {
+        index_at(1, point(10.0, 179.0));
+        index_at(2, point(10.0, -179.0));
+        index_at(3, point(10.0, 0.0));
+
+        let ids = sorted(candidates_in_bbox(&point(5.0, 178.0), &point(15.0, -178.0)));
+        assert!(ids.contains(&1) && ids.contains(&2));
+        assert!(!ids.contains(&3));
+    }
+
+    #[test]
+    fn bbox_excludes_points_outside() {
+        index_at(1, point(12.97, 77.59));
+        index_at(2, point(28.61, 77.20));
+
+        let ids = sorted(candidates_in_bbox(&point(12.0, 77.0), &point(13.0, 78.0)));
+        assert!(ids.contains(&1));
+        assert!(!ids.contains(&2));
+    }
+}
@@ -0,0 +1,534 @@
+//! Chunked image uploads kept in stable memory.
+//!
+//! An upload session and the asset it becomes share one id, so chunks are written once
+//! under `(id, chunk_index)` and stay in place when the upload is committed.
+//!
+//! Only raster formats are accepted: images are served from the canister's own origin, so
+//! an SVG could run scripts there.
+
+use super::{property_store, IMAGES_BY_OWNER, IMAGE_ASSETS, IMAGE_CHUNKS, UPLOADS};
+use crate::types::{AanganError, ImageAsset, Stored, UploadSession};
+use candid::Principal;
+use sha2::{Digest, Sha256};
+
+/// Largest chunk accepted by `put_chunk`, leaving headroom below the 2 MiB ingress limit.
+pub const MAX_CHUNK_BYTES: usize = 1_900_000;
+pub const MAX_IMAGE_BYTES: u64 = 10 * 1024 * 1024;
+/// Upload sessions that are not committed within a day are discarded.
+pub const UPLOAD_TTL_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// Most upload sessions a principal may have open at once.
+pub const MAX_OPEN_UPLOADS: usize = 5;
+/// Most bytes a principal may have declared across its open upload sessions.
+pub const MAX_OPEN_UPLOAD_BYTES: u64 = 3 * MAX_IMAGE_BYTES;
+/// Content types accepted by `start_upload`. Matching exactly also keeps the content type
+/// within the bounded size of the stored session.
+pub const ALLOWED_CONTENT_TYPES: [&str; 5] = [
+    "image/jpeg",
+    "image/png",
+    "image/webp",
+    "image/gif",
+    "image/avif",
+];
+/// Most images a property may show.
+pub const MAX_PROPERTY_IMAGES: usize = 20;
+/// Most images a principal may keep, committed or still uploading.
+pub const MAX_IMAGES_PER_OWNER: usize = 200;
+
+/// Fails with `RateLimited` if opening an upload of `total_size` bytes would take `owner`
+/// over its quota of open sessions. The retry time is when the oldest one is discarded.
+fn check_upload_quota(owner: &Principal, total_size: u64, now: u64) -> Result<(), AanganError> {
+    let open: Vec<UploadSession> = UPLOADS.with(|uploads| {
+        uploads
+            .borrow()
+            .values()
+            .map(Stored::into_value)
+            .filter(|session| session.owner == *owner)
+            .collect()
+    });
+    let open_bytes: u64 = open.iter().map(|session| session.total_size).sum();
+
+    if count_assets_by_owner(owner) + open.len() >= MAX_IMAGES_PER_OWNER {
+        return Err(AanganError::validation(
+            "images",
+            format!(
+                "at most {} images may be kept; delete unused ones first",
+                MAX_IMAGES_PER_OWNER
+            ),
+        ));
+    }
+
+    if open.len() >= MAX_OPEN_UPLOADS || open_bytes + total_size > MAX_OPEN_UPLOAD_BYTES {
+        let oldest = open
+            .iter()
+            .map(|session| session.created_at)
+            .min()
+            .unwrap_or(now);
+        let expires_at = oldest.saturating_add(UPLOAD_TTL_NANOS);
+        return Err(AanganError::rate_limited(
+            expires_at.saturating_sub(now).div_ceil(1_000_000_000),
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn start_upload(
+    owner: Principal,
+    content_type: String,
+    total_size: u64,
+    now: u64,
+) -> Result<UploadSession, AanganError> {
+    if !ALLOWED_CONTENT_TYPES.contains(&content_type.as_str()) {
+        return Err(AanganError::validation(
+            "content_type",
+            format!("must be one of {}", ALLOWED_CONTENT_TYPES.join(", ")),
+        ));
+    }
+
+    if total_size == 0 || total_size > MAX_IMAGE_BYTES {
+        return Err(AanganError::validation(
+            "total_size",
+            format!("must be between 1 and {} bytes", MAX_IMAGE_BYTES),
+        ));
+    }
+
+    check_upload_quota(&owner, total_size, now)?;
+
+    let session = UploadSession::new(
+        super::get_next_image_id(),
+        owner,
+        content_type,
+        total_size,
+        now,
+    );
+    UPLOADS.with(|uploads| {
+        uploads
+            .borrow_mut()
+            .insert(session.id, Stored::new(session.clone()))
+    });
+    Ok(session)
+}
+
+fn get_owned_session(upload_id: u64, caller: &Principal) -> Result<UploadSession, AanganError> {
+    let session = UPLOADS
+        .with(|uploads| uploads.borrow().get(&upload_id).map(Stored::into_value))
+        .ok_or_else(|| AanganError::not_found("Upload", upload_id))?;
+
+    if session.owner != *caller {
+        return Err(AanganError::unauthorized(
+            "Only the uploader can change an upload",
+        ));
+    }
+
+    Ok(session)
+}
+
+pub fn put_chunk(
+    upload_id: u64,
+    chunk_index: u32,
+    content: Vec<u8>,
+    caller: &Principal,
+) -> Result<(), AanganError> {
+    let mut session = get_owned_session(upload_id, caller)?;
+
+    if content.is_empty() || content.len() > MAX_CHUNK_BYTES {
+        return Err(AanganError::validation(
+            "content",
+            format!("chunk size must be between 1 and {} bytes", MAX_CHUNK_BYTES),
+        ));
+    }
+
+    let replaced = IMAGE_CHUNKS
+        .with(|chunks| chunks.borrow().get(&(upload_id, chunk_index)))
+        .map_or(0, |chunk| chunk.len() as u64);
+    let received_bytes = session.received_bytes - replaced + content.len() as u64;
+
+    if received_bytes > session.total_size {
+        return Err(AanganError::validation(
+            "content",
+            "chunk exceeds the declared upload size",
+        ));
+    }
+
+    IMAGE_CHUNKS.with(|chunks| {
+        chunks
+            .borrow_mut()
+            .insert((upload_id, chunk_index), content)
+    });
+    session.received_bytes = received_bytes;
+    UPLOADS.with(|uploads| uploads.borrow_mut().insert(upload_id, Stored::new(session)));
+    Ok(())
+}
+
+/// Verifies that chunks `0..n` cover the declared size, records the SHA-256 of the content
+/// and turns the session into a servable asset. If `expected_sha256` is given it must match.
+pub fn commit_upload(
+    upload_id: u64,
+    expected_sha256: Option<Vec<u8>>,
+    caller: &Principal,
+) -> Result<ImageAsset, AanganError> {
+    let session = get_owned_session(upload_id, caller)?;
+
+    if session.received_bytes != session.total_size {
+        return Err(AanganError::invalid_state(
+            format!("{} bytes received", session.total_size),
+            format!("{} bytes received", session.received_bytes),
+        ));
+    }
+
+    let mut hasher = Sha256::new();
+    let mut chunk_count = 0u32;
+    IMAGE_CHUNKS.with(|chunks| -> Result<(), AanganError> {
+        for ((_, index), content) in chunks
+            .borrow()
+            .range((upload_id, 0)..=(upload_id, u32::MAX))
+        {
+            if index != chunk_count {
+                return Err(AanganError::not_found(
+                    "Chunk",
+                    format!("{}/{}", upload_id, chunk_count),
+                ));
+            }
+            hasher.update(&content);
+            chunk_count += 1;
+        }
+        Ok(())
+    })?;
+    let sha256: [u8; 32] = hasher.finalize().into();
+
+    if let Some(expected) = expected_sha256 {
+        if expected != sha256 {
+            return Err(AanganError::validation(
+                "sha256",
+                "does not match the uploaded content",
+            ));
+        }
+    }
+
+    let asset = ImageAsset::from_upload(session, sha256, chunk_count);
+    IMAGE_ASSETS.with(|assets| {
+        assets
+            .borrow_mut()
+            .insert(asset.id, Stored::new(asset.clone()))
+    });
+    IMAGES_BY_OWNER.with(|index| index.borrow_mut().insert((asset.owner, asset.id), ()));
+    UPLOADS.with(|uploads| uploads.borrow_mut().remove(&upload_id));
+    Ok(asset)
+}
+
+/// Deletes a committed image, or abandons an upload that is still open. Images shown by one
+/// of the owner's properties must be removed from the listing first. Returns the deleted
+/// asset, or `None` for an abandoned upload.
+pub fn delete_image(id: u64, caller: &Principal) -> Result<Option<ImageAsset>, AanganError> {
+    let Some(asset) = get_asset(id) else {
+        get_owned_session(id, caller)?;
+        remove_chunks(id);
+        UPLOADS.with(|uploads| uploads.borrow_mut().remove(&id));
+        return Ok(None);
+    };
+
+    if asset.owner != *caller {
+        return Err(AanganError::unauthorized(
+            "Only the uploader can delete an image",
+        ));
+    }
+
+    let image = id.to_string();
+    if let Some(property) = property_store::get_properties_by_owner(caller)
+        .into_iter()
+        .find(|property| property.images.contains(&image))
+    {
+        return Err(AanganError::conflict(format!(
+            "Image is shown by property {}",
+            property.id
+        )));
+    }
+
+    remove_chunks(id);
+    IMAGE_ASSETS.with(|assets| assets.borrow_mut().remove(&id));
+    IMAGES_BY_OWNER.with(|index| index.borrow_mut().remove(&(asset.owner, id)));
+    Ok(Some(asset))
+}
+
+fn count_assets_by_owner(owner: &Principal) -> usize {
+    IMAGES_BY_OWNER.with(|index| {
+        index
+            .borrow()
+            .range((*owner, 0)..=(*owner, u64::MAX))
+            .count()
+    })
+}
+
+/// Builds the owner index for images committed before it existed.
+pub fn backfill_indexes() {
+    if IMAGES_BY_OWNER.with(|index| index.borrow().is_empty()) {
+        IMAGE_ASSETS.with(|assets| {
+            for asset in assets.borrow().values().map(Stored::into_value) {
+                IMAGES_BY_OWNER
+                    .with(|index| index.borrow_mut().insert((asset.owner, asset.id), ()));
+            }
+        });
+    }
+}
+
+pub fn get_asset(id: u64) -> Option<ImageAsset> {
+    IMAGE_ASSETS.with(|assets| assets.borrow().get(&id).map(Stored::into_value))
+}
+
+pub fn get_all_assets() -> Vec<ImageAsset> {
+    IMAGE_ASSETS.with(|assets| assets.borrow().values().map(Stored::into_value).collect())
+}
+
+/// Up to `limit` committed images in id order, starting after `after`.
+pub fn get_asset_batch(after: Option<u64>, limit: usize) -> Vec<ImageAsset> {
+    let start = match after {
+        Some(u64::MAX) => return Vec::new(),
+        Some(after) => after + 1,
+        None => 0,
+    };
+    IMAGE_ASSETS.with(|assets| {
+        assets
+            .borrow()
+            .range(start..)
+            .take(limit)
+            .map(|(_, asset)| asset.into_value())
+            .collect()
+    })
+}
+
+pub fn get_chunk(id: u64, chunk_index: u32) -> Option<Vec<u8>> {
+    IMAGE_CHUNKS.with(|chunks| chunks.borrow().get(&(id, chunk_index)))
+}
+
+fn remove_chunks(id: u64) {
+    IMAGE_CHUNKS.with(|chunks| {
+        let mut chunks = chunks.borrow_mut();
+        let keys: Vec<(u64, u32)> = chunks
+            .range((id, 0)..=(id, u32::MAX))
+            .map(|(key, _)| key)
+            .collect();
+        for key in keys {
+            chunks.remove(&key);
+        }
+    });
+}
+
+/// Drops upload sessions (and their chunks) that were never committed.
+pub fn purge_stale_uploads(now: u64) {
+    let stale: Vec<u64> = UPLOADS.with(|uploads| {
+        uploads
+            .borrow()
+            .iter()
+            .map(|(id, session)| (id, session.into_value()))
+            .filter(|(_, session)| now.saturating_sub(session.created_at) >= UPLOAD_TTL_NANOS)
+            .map(|(id, _)| id)
+            .collect()
+    });
+
+    for id in stale {
+        remove_chunks(id);
+        UPLOADS.with(|uploads| uploads.borrow_mut().remove(&id));
+    }
+}
+
+/// Checks that `Property.images` lists at most [MAX_PROPERTY_IMAGES] distinct committed
+/// assets owned by `owner`.
+pub fn validate_property_images(images: &[String], owner: &Principal) -> Result<(), AanganError> {
+    if images.len() > MAX_PROPERTY_IMAGES {
+        return Err(AanganError::validation(
+            "images",
+            format!("at most {} images are allowed", MAX_PROPERTY_IMAGES),
+        ));
+    }
+
+    for (i, image) in images.iter().enumerate() {
+        if images[..i].contains(image) {
+            return Err(AanganError::validation(
+                "images",
+                format!("image {} is listed twice", image),
+            ));
+        }
+
+        let asset = image
+            .parse::<u64>()
+            .ok()
+            .and_then(get_asset)
+            .ok_or_else(|| AanganError::not_found("Image", image))?;
+
+        if asset.owner != *owner {
+            return Err(AanganError::validation(
+                "images",
+                format!("image {} belongs to another user", image),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000_000_000_000;
+
+    fn start(owner: Principal, total_size: u64, now: u64) -> Result<UploadSession, AanganError> {
+        start_upload(owner, "image/png".to_string(), total_size, now)
+    }
+
+    fn upload(owner: Principal) -> ImageAsset {
+        let session = start(owner, 3, NOW).unwrap();
+        put_chunk(session.id, 0, vec![1, 2, 3], &owner).unwrap();
+        commit_upload(session.id, None, &owner).unwrap()
+    }
+
+    #[test]
+    fn only_raster_content_types_are_accepted() {
+        let owner = Principal::from_slice(&[2]);
+        for content_type in [
+            "image/svg+xml".to_string(),
+            "text/html".to_string(),
+            format!("image/png{}", " ".repeat(1_000)),
+        ] {
+            assert!(matches!(
+                start_upload(owner, content_type, 1, NOW),
+                Err(AanganError::Validation { .. })
+            ));
+        }
+        assert!(start_upload(owner, "image/webp".to_string(), 1, NOW).is_ok());
+    }
+
+    #[test]
+    fn stored_images_are_capped_per_owner() {
+        let owner = Principal::from_slice(&[2]);
+        for _ in 0..MAX_IMAGES_PER_OWNER {
+            upload(owner);
+        }
+
+        assert!(matches!(
+            start(owner, 1, NOW),
+            Err(AanganError::Validation { .. })
+        ));
+
+        let deleted = get_all_assets()[0].id;
+        delete_image(deleted, &owner).unwrap();
+        assert!(start(owner, 1, NOW).is_ok());
+    }
+
+    #[test]
+    fn property_images_are_capped_and_distinct() {
+        let owner = Principal::from_slice(&[2]);
+        let ids: Vec<String> = (0..=MAX_PROPERTY_IMAGES)
+            .map(|_| upload(owner).id.to_string())
+            .collect();
+
+        assert!(validate_property_images(&ids[..MAX_PROPERTY_IMAGES], &owner).is_ok());
+        assert!(validate_property_images(&ids, &owner).is_err());
+        assert!(validate_property_images(&[ids[0].clone(), ids[0].clone()], &owner).is_err());
+        assert!(validate_property_images(&ids[..1], &Principal::from_slice(&[3])).is_err());
+    }
+
+    #[test]
+    fn deleting_an_image_removes_its_content() {
+        let owner = Principal::from_slice(&[2]);
+        let asset = upload(owner);
+
+        assert!(matches!(
+            delete_image(asset.id, &Principal::from_slice(&[3])),
+            Err(AanganError::Unauthorized(_))
+        ));
+
+        assert!(delete_image(asset.id, &owner).unwrap().is_some());
+        assert!(get_asset(asset.id).is_none());
+        assert!(get_chunk(asset.id, 0).is_none());
+        assert_eq!(count_assets_by_owner(&owner), 0);
+    }
+
+    #[test]
+    fn images_shown_by_a_property_are_kept() {
+        let owner = Principal::from_slice(&[2]);
+        let asset = upload(owner);
+        property_store::create_property(crate::types::Property::new(
+            1,
+            owner,
+            "Flat".to_string(),
+            String::new(),
+            String::new(),
+            25_000_000,
+            0,
+            crate::types::PropertyType::Apartment,
+            2,
+            1,
+            800,
+            vec![asset.id.to_string()],
+            vec![],
+        ))
+        .unwrap();
+
+        assert!(matches!(
+            delete_image(asset.id, &owner),
+            Err(AanganError::Conflict(_))
+        ));
+        assert!(get_asset(asset.id).is_some());
+    }
+
+    #[test]
+    fn open_uploads_can_be_abandoned() {
+        let owner = Principal::from_slice(&[2]);
+        let session = start(owner, 3, NOW).unwrap();
+        put_chunk(session.id, 0, vec![1], &owner).unwrap();
+
+        assert!(delete_image(session.id, &owner).unwrap().is_none());
+        assert!(get_chunk(session.id, 0).is_none());
+        assert!(matches!(
+            put_chunk(session.id, 1, vec![2], &owner),
+            Err(AanganError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn open_sessions_are_capped_per_owner() {
+        let owner = Principal::from_slice(&[2]);
+        for _ in 0..MAX_OPEN_UPLOADS {
+            start(owner, 1, NOW).unwrap();
+        }
+
+        assert!(matches!(
+            start(owner, 1, NOW),
+            Err(AanganError::RateLimited { .. })
+        ));
+        // Other principals have their own quota
+        assert!(start(Principal::from_slice(&[3]), 1, NOW).is_ok());
+    }
+
+    #[test]
+    fn open_bytes_are_capped_per_owner() {
+        let owner = Principal::from_slice(&[2]);
+        for _ in 0..3 {
+            start(owner, MAX_IMAGE_BYTES, NOW).unwrap();
+        }
+
+        assert!(matches!(
+            start(owner, 1, NOW),
+            Err(AanganError::RateLimited { .. })
+        ));
+    }
+
+    #[test]
+    fn retry_after_points_at_the_oldest_session_expiring() {
+        let owner = Principal::from_slice(&[2]);
+        let created_at = NOW;
+        for _ in 0..MAX_OPEN_UPLOADS {
+            start(owner, 1, created_at).unwrap();
+        }
+
+        let now = created_at + UPLOAD_TTL_NANOS - 10 * 1_000_000_000;
+        let Err(AanganError::RateLimited { retry_after_secs }) = start(owner, 1, now) else {
+            panic!("quota should be exhausted");
+        };
+        assert!((9..=10).contains(&retry_after_secs));
+
+        // Purged sessions free up the quota
+        purge_stale_uploads(created_at + UPLOAD_TTL_NANOS + 1_000_000_000);
+        assert!(start(owner, 1, created_at + UPLOAD_TTL_NANOS + 1_000_000_000).is_ok());
+    }
+}
@@ -0,0 +1,137 @@
+//! Rewrites records stored under an older schema version.
+//!
+//! Outdated records are already upgraded when read, so the migration only brings stable
+//! memory up to the current layout. It runs after every upgrade, one batch per message, so
+//! large maps never hit the instruction limit. A record that cannot be decoded traps, which
+//! stops the migration at that record.
+
+use super::{
+    Memory, IMAGE_ASSETS, LEASE_ASSIGNMENTS, LEASE_RENEWALS, NFTS, NFT_EVENTS, PROPERTIES, RENTALS,
+    RENT_PAYMENTS, UPLOADS, USERS,
+};
+use crate::types::{Stored, VersionedRecord};
+use candid::Principal;
+use ic_stable_structures::{StableBTreeMap, Storable};
+use std::cell::RefCell;
+use std::ops::Bound;
+use std::thread::LocalKey;
+
+/// Records examined per message.
+pub const BATCH_SIZE: usize = 500;
+
+type Map<K, T> = LocalKey<RefCell<StableBTreeMap<K, Stored<T>, Memory>>>;
+
+/// Position of the migration: the map being migrated and the last key examined in it.
+enum Cursor {
+    Users(Option<Principal>),
+    Properties(Option<u64>),
+    Rentals(Option<u64>),
+    Nfts(Option<u64>),
+    Payments(Option<(u64, u32)>),
+    Assignments(Option<(u64, u32)>),
+    Renewals(Option<(u64, u32)>),
+    NftEvents(Option<(u64, u32)>),
+    Uploads(Option<u64>),
+    Images(Option<u64>),
+}
+
+/// Rewrites the outdated records among the next `limit` keys after `after`. Returns the
+/// last key examined, or `None` once the end of the map is reached.
+fn migrate_batch<K, T>(map: &'static Map<K, T>, after: Option<K>, limit: usize) -> Option<K>
+where
+    K: Storable + Ord + Clone,
+    T: VersionedRecord,
+{
+    map.with(|map| {
+        let mut map = map.borrow_mut();
+        let batch: Vec<(K, Stored<T>)> = match after {
+            Some(key) => map
+                .range((Bound::Excluded(key), Bound::Unbounded))
+                .take(limit)
+                .collect(),
+            None => map.iter().take(limit).collect(),
+        };
+        let last = if batch.len() < limit {
+            None
+        } else {
+            batch.last().map(|(key, _)| key.clone())
+        };
+
+        for (key, record) in batch {
+            if record.is_outdated() {
+                // Encoding always writes the current version
+                map.insert(key, record);
+            }
+        }
+        last
+    })
+}
+
+/// Migrates one batch and returns where the next one starts, or `None` when done.
+fn step(cursor: Cursor) -> Option<Cursor> {
+    match cursor {
+        Cursor::Users(after) => Some(match migrate_batch(&USERS, after, BATCH_SIZE) {
+            Some(last) => Cursor::Users(Some(last)),
+            None => Cursor::Properties(None),
+        }),
+        Cursor::Properties(after) => Some(match migrate_batch(&PROPERTIES, after, BATCH_SIZE) {
+            Some(last) => Cursor::Properties(Some(last)),
+            None => Cursor::Rentals(None),
+        }),
+        Cursor::Rentals(after) => Some(match migrate_batch(&RENTALS, after, BATCH_SIZE) {
+            Some(last) => Cursor::Rentals(Some(last)),
+            None => Cursor::Nfts(None),
+        }),
+        Cursor::Nfts(after) => Some(match migrate_batch(&NFTS, after, BATCH_SIZE) {
+            Some(last) => Cursor::Nfts(Some(last)),
+            None => Cursor::Payments(None),
+        }),
+        Cursor::Payments(after) => Some(match migrate_batch(&RENT_PAYMENTS, after, BATCH_SIZE) {
+            Some(last) => Cursor::Payments(Some(last)),
+            None => Cursor::Assignments(None),
+        }),
+        Cursor::Assignments(after) => {
+            Some(match migrate_batch(&LEASE_ASSIGNMENTS, after, BATCH_SIZE) {
+                Some(last) => Cursor::Assignments(Some(last)),
+                None => Cursor::Renewals(None),
+            })
+        }
+        Cursor::Renewals(after) => Some(match migrate_batch(&LEASE_RENEWALS, after, BATCH_SIZE) {
+            Some(last) => Cursor::Renewals(Some(last)),
+            None => Cursor::NftEvents(None),
+        }),
+        Cursor::NftEvents(after) => Some(match migrate_batch(&NFT_EVENTS, after, BATCH_SIZE) {
+            Some(last) => Cursor::NftEvents(Some(last)),
+            None => Cursor::Uploads(None),
+        }),
+        Cursor::Uploads(after) => Some(match migrate_batch(&UPLOADS, after, BATCH_SIZE) {
+            Some(last) => Cursor::Uploads(Some(last)),
+            None => Cursor::Images(None),
+        }),
+        Cursor::Images(after) => {
+            migrate_batch(&IMAGE_ASSETS, after, BATCH_SIZE).map(|last| Cursor::Images(Some(last)))
+        }
+    }
+}
+
+#[cfg(feature = "timers")]
+pub fn start() {
+    schedule(Cursor::Users(None));
+}
+
+#[cfg(feature = "timers")]
+fn schedule(cursor: Cursor) {
+    ic_cdk_timers::set_timer(std::time::Duration::ZERO, move || {
+        if let Some(next) = step(cursor) {
+            schedule(next);
+        }
+    });
+}
+
+#[cfg(not(feature = "timers"))]
+pub fn start() {
+    let mut cursor = Some(Cursor::Users(None));
+    while let Some(current) = cursor {
+        cursor = step(current);
+    }
+}
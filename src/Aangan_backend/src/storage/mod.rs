@@ -1,12 +1,26 @@
 use candid::Principal;
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
-use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell, StableLog};
 use std::cell::RefCell;
 
 use crate::types::*;
+use counters::IdCounters;
+use text_index::Term;
 
+pub mod assignment_store;
+pub mod config_store;
+pub mod counters;
+pub mod geo_index;
+pub mod image_store;
+pub mod migration;
+pub mod moderation_store;
+pub mod nft_event_store;
+pub mod payment_store;
 pub mod property_store;
+pub mod renewal_store;
 pub mod rental_store;
+pub mod search_index;
+pub mod text_index;
 pub mod user_store;
 
 type Memory = VirtualMemory<DefaultMemoryImpl>;
@@ -16,76 +30,374 @@ thread_local! {
         MemoryManager::init(DefaultMemoryImpl::default())
     );
 
-    static USERS: RefCell<StableBTreeMap<Principal, User, Memory>> = RefCell::new(
+    static USERS: RefCell<StableBTreeMap<Principal, Stored<User>, Memory>> = RefCell::new(
         StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))),
         )
     );
 
-    static PROPERTIES: RefCell<StableBTreeMap<u64, Property, Memory>> = RefCell::new(
+    static PROPERTIES: RefCell<StableBTreeMap<u64, Stored<Property>, Memory>> = RefCell::new(
         StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1))),
         )
     );
 
-    static RENTALS: RefCell<StableBTreeMap<u64, RentalAgreement, Memory>> = RefCell::new(
+    static RENTALS: RefCell<StableBTreeMap<u64, Stored<RentalAgreement>, Memory>> = RefCell::new(
         StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2))),
         )
     );
 
-    static NFTS: RefCell<StableBTreeMap<u64, NFTMetadata, Memory>> = RefCell::new(
+    static NFTS: RefCell<StableBTreeMap<u64, Stored<NFTMetadata>, Memory>> = RefCell::new(
         StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3))),
         )
     );
 
-    static PROPERTY_COUNTER: RefCell<u64> = RefCell::new(0);
-    static RENTAL_COUNTER: RefCell<u64> = RefCell::new(0);
-    static NFT_COUNTER: RefCell<u64> = RefCell::new(0);
+    static CONFIG: RefCell<StableCell<Config, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4))),
+            Config::default(),
+        )
+        .expect("Failed to initialize config cell")
+    );
+
+    static RENT_PAYMENTS: RefCell<StableBTreeMap<(u64, u32), Stored<RentPayment>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5))),
+        )
+    );
+
+    static PROPERTIES_BY_OWNER: RefCell<StableBTreeMap<(Principal, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7))),
+        )
+    );
+
+    static RENTALS_BY_TENANT: RefCell<StableBTreeMap<(Principal, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8))),
+        )
+    );
+
+    static RENTALS_BY_LANDLORD: RefCell<StableBTreeMap<(Principal, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9))),
+        )
+    );
+
+    static RENTALS_BY_PROPERTY: RefCell<StableBTreeMap<(u64, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10))),
+        )
+    );
+
+    static NFTS_BY_OWNER: RefCell<StableBTreeMap<(Principal, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11))),
+        )
+    );
+
+    static TEXT_INDEX: RefCell<StableBTreeMap<(Term, u64), u32, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12))),
+        )
+    );
+
+    static GEO_INDEX: RefCell<StableBTreeMap<(u64, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13))),
+        )
+    );
+
+    static UPLOADS: RefCell<StableBTreeMap<u64, Stored<UploadSession>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14))),
+        )
+    );
+
+    static IMAGE_ASSETS: RefCell<StableBTreeMap<u64, Stored<ImageAsset>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(15))),
+        )
+    );
+
+    static IMAGES_BY_OWNER: RefCell<StableBTreeMap<(Principal, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(25))),
+        )
+    );
+
+    static IMAGE_CHUNKS: RefCell<StableBTreeMap<(u64, u32), Vec<u8>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(16))),
+        )
+    );
+
+    static LEASE_ASSIGNMENTS: RefCell<StableBTreeMap<(u64, u32), Stored<LeaseAssignment>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(17))),
+        )
+    );
+
+    static NFT_EVENTS: RefCell<StableBTreeMap<(u64, u32), Stored<NFTEvent>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(18))),
+        )
+    );
+
+    static MODERATION_LOG: RefCell<StableLog<ModerationEntry, Memory, Memory>> = RefCell::new(
+        StableLog::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(19))),
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(20))),
+        )
+        .expect("Failed to initialize moderation log")
+    );
+
+    static LEASE_RENEWALS: RefCell<StableBTreeMap<(u64, u32), Stored<LeaseRenewal>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(21))),
+        )
+    );
+
+    static RENTALS_BY_STATUS: RefCell<StableBTreeMap<(u8, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(22))),
+        )
+    );
+
+    static PROPERTY_SEARCH_INDEX: RefCell<StableBTreeMap<(u8, u64, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(23))),
+        )
+    );
+
+    /// Number of NFTs that are not retired, see `rental_store::nft_supply`.
+    static NFT_SUPPLY: RefCell<StableCell<u64, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(24))),
+            rental_store::UNCOUNTED_SUPPLY,
+        )
+        .expect("Failed to initialize NFT supply")
+    );
+
+    /// Schema version the stored records were last backfilled for; 0 before it was tracked.
+    static SCHEMA_VERSION: RefCell<StableCell<u32, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(26))),
+            0,
+        )
+        .expect("Failed to initialize schema version")
+    );
+
+    static ID_COUNTERS: RefCell<StableCell<IdCounters, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6))),
+            IdCounters::default(),
+        )
+        .expect("Failed to initialize ID counters")
+    );
 }
 
+/// Version of the indexes and derived state kept next to the records. Bump it when a release
+/// adds some that existing records must be backfilled into.
+pub const CURRENT_SCHEMA_VERSION: u32 = 1;
+
 pub fn init_storage() {
-    // Initialize counters
-    PROPERTY_COUNTER.with(|counter| {
-        *counter.borrow_mut() = 0;
-    });
-    RENTAL_COUNTER.with(|counter| {
-        *counter.borrow_mut() = 0;
-    });
-    NFT_COUNTER.with(|counter| {
-        *counter.borrow_mut() = 0;
-    });
+    recover_id_counters();
+    rental_store::backfill_nft_supply();
+    set_schema_version(CURRENT_SCHEMA_VERSION);
 }
 
 pub fn pre_upgrade() {
     // Stable structures handle persistence automatically
 }
 
-pub fn post_upgrade() {
-    // Stable structures handle restoration automatically
+/// Returns whether the stored schema was outdated. The storage backfills have run in that
+/// case, and the caller runs its own before the upgrade completes.
+pub fn post_upgrade() -> bool {
+    // Releases that kept the counters on the heap lost them on upgrade
+    recover_id_counters();
+    let outdated = backfill_outdated_schema();
+    migration::start();
+    outdated
+}
+
+/// Runs the full scans that build new indexes and derived state, once per schema version
+/// rather than on every upgrade.
+fn backfill_outdated_schema() -> bool {
+    if SCHEMA_VERSION.with(|version| *version.borrow().get()) >= CURRENT_SCHEMA_VERSION {
+        return false;
+    }
+    property_store::backfill_indexes();
+    rental_store::backfill_indexes();
+    rental_store::backfill_nft_supply();
+    image_store::backfill_indexes();
+    set_schema_version(CURRENT_SCHEMA_VERSION);
+    true
+}
+
+fn set_schema_version(version: u32) {
+    SCHEMA_VERSION.with(|cell| {
+        cell.borrow_mut()
+            .set(version)
+            .expect("Failed to write schema version")
+    });
+}
+
+/// Raises every ID counter to at least the largest key already in use, so IDs handed out
+/// after an upgrade never overwrite existing records.
+fn recover_id_counters() {
+    let max_property = PROPERTIES.with(|p| p.borrow().last_key_value().map(|(k, _)| k));
+    let max_rental = RENTALS.with(|r| r.borrow().last_key_value().map(|(k, _)| k));
+    let max_nft = NFTS.with(|n| n.borrow().last_key_value().map(|(k, _)| k));
+    let max_image = IMAGE_ASSETS
+        .with(|i| i.borrow().last_key_value().map(|(k, _)| k))
+        .max(UPLOADS.with(|u| u.borrow().last_key_value().map(|(k, _)| k)));
+
+    update_id_counters(|counters| {
+        counters.property = counters.property.max(max_property.unwrap_or(0));
+        counters.rental = counters.rental.max(max_rental.unwrap_or(0));
+        counters.nft = counters.nft.max(max_nft.unwrap_or(0));
+        counters.image = counters.image.max(max_image.unwrap_or(0));
+    });
+}
+
+fn update_id_counters<R>(f: impl FnOnce(&mut IdCounters) -> R) -> R {
+    ID_COUNTERS.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        let mut counters = *cell.get();
+        let result = f(&mut counters);
+        cell.set(counters).expect("Failed to write ID counters");
+        result
+    })
 }
 
 pub fn get_next_property_id() -> u64 {
-    PROPERTY_COUNTER.with(|counter| {
-        let mut counter = counter.borrow_mut();
-        *counter += 1;
-        *counter
+    update_id_counters(|counters| {
+        counters.property += 1;
+        counters.property
     })
 }
 
 pub fn get_next_rental_id() -> u64 {
-    RENTAL_COUNTER.with(|counter| {
-        let mut counter = counter.borrow_mut();
-        *counter += 1;
-        *counter
+    update_id_counters(|counters| {
+        counters.rental += 1;
+        counters.rental
     })
 }
 
 pub fn get_next_nft_id() -> u64 {
-    NFT_COUNTER.with(|counter| {
-        let mut counter = counter.borrow_mut();
-        *counter += 1;
-        *counter
+    update_id_counters(|counters| {
+        counters.nft += 1;
+        counters.nft
     })
 }
+
+pub fn get_next_image_id() -> u64 {
+    update_id_counters(|counters| {
+        counters.image += 1;
+        counters.image
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner() -> Principal {
+        Principal::from_slice(&[2])
+    }
+
+    fn property(id: u64) -> Property {
+        Property::new(
+            id,
+            owner(),
+            "Flat".to_string(),
+            String::new(),
+            String::new(),
+            25_000_000,
+            0,
+            PropertyType::Apartment,
+            2,
+            1,
+            800,
+            vec![],
+            vec![],
+        )
+    }
+
+    /// Records written by a release that kept the counters on the heap, which start from
+    /// zero again after an upgrade.
+    fn store_records_without_counters() {
+        PROPERTIES.with(|p| p.borrow_mut().insert(7, Stored::new(property(7))));
+        let rental = RentalAgreement::new(12, 7, owner(), Principal::from_slice(&[3]), 0, 1, 1, 0);
+        RENTALS.with(|r| r.borrow_mut().insert(12, Stored::new(rental)));
+        let nft = NFTMetadata::new(
+            3,
+            owner(),
+            7,
+            12,
+            String::new(),
+            String::new(),
+            String::new(),
+            1,
+            0,
+            1,
+        );
+        NFTS.with(|n| n.borrow_mut().insert(3, Stored::new(nft)));
+        let session = UploadSession::new(9, owner(), "image/png".to_string(), 1, 0);
+        let asset = ImageAsset::from_upload(session, [0; 32], 1);
+        IMAGE_ASSETS.with(|i| i.borrow_mut().insert(9, Stored::new(asset)));
+        let open = UploadSession::new(11, owner(), "image/png".to_string(), 1, 0);
+        UPLOADS.with(|u| u.borrow_mut().insert(11, Stored::new(open)));
+    }
+
+    #[test]
+    fn counters_resume_above_the_highest_stored_id() {
+        store_records_without_counters();
+        assert_eq!(
+            ID_COUNTERS.with(|c| *c.borrow().get()),
+            IdCounters::default()
+        );
+
+        recover_id_counters();
+
+        assert_eq!(get_next_property_id(), 8);
+        assert_eq!(get_next_rental_id(), 13);
+        assert_eq!(get_next_nft_id(), 4);
+        // Open uploads hold an image id as well
+        assert_eq!(get_next_image_id(), 12);
+    }
+
+    #[test]
+    fn outdated_schemas_are_backfilled_once() {
+        PROPERTIES.with(|p| p.borrow_mut().insert(7, Stored::new(property(7))));
+
+        assert!(backfill_outdated_schema());
+        assert_eq!(property_store::get_properties_by_owner(&owner()).len(), 1);
+
+        // A later upgrade leaves the indexes alone
+        PROPERTIES.with(|p| p.borrow_mut().insert(8, Stored::new(property(8))));
+        assert!(!backfill_outdated_schema());
+        assert_eq!(property_store::get_properties_by_owner(&owner()).len(), 1);
+    }
+
+    #[test]
+    fn fresh_installs_start_at_the_current_schema() {
+        init_storage();
+        assert!(!backfill_outdated_schema());
+    }
+
+    #[test]
+    fn counters_ahead_of_the_stored_ids_are_kept() {
+        store_records_without_counters();
+        update_id_counters(|counters| counters.property = 20);
+
+        recover_id_counters();
+
+        // Ids handed out before an upgrade are never reused, even if their record is gone
+        assert_eq!(get_next_property_id(), 21);
+        assert_eq!(get_next_rental_id(), 13);
+    }
+}
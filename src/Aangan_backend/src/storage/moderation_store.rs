@@ -0,0 +1,30 @@
+use super::MODERATION_LOG;
+use crate::types::{ModerationAction, ModerationEntry};
+use candid::Principal;
+
+pub fn append(admin: Principal, action: ModerationAction, reason: String) -> ModerationEntry {
+    MODERATION_LOG.with(|log| {
+        let log = log.borrow_mut();
+        let entry = ModerationEntry {
+            id: log.len(),
+            admin,
+            action,
+            reason,
+            timestamp: ic_cdk::api::time(),
+        };
+        log.append(&entry)
+            .expect("Failed to append to the moderation log");
+        entry
+    })
+}
+
+/// Up to `limit` entries starting at `start`, oldest first.
+pub fn get_entries(start: u64, limit: usize) -> Vec<ModerationEntry> {
+    MODERATION_LOG.with(|log| {
+        let log = log.borrow();
+        (start..log.len())
+            .take(limit)
+            .filter_map(|id| log.get(id))
+            .collect()
+    })
+}
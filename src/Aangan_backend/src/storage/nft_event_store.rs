@@ -0,0 +1,31 @@
+use super::NFT_EVENTS;
+use crate::types::{NFTEvent, NFTEventKind, Stored};
+
+pub fn record_event(nft_id: u64, kind: NFTEventKind) {
+    NFT_EVENTS.with(|events| {
+        let mut events = events.borrow_mut();
+        let sequence = events
+            .range((nft_id, 0)..=(nft_id, u32::MAX))
+            .last()
+            .map_or(0, |((_, sequence), _)| sequence + 1);
+        events.insert(
+            (nft_id, sequence),
+            Stored::new(NFTEvent {
+                nft_id,
+                sequence,
+                kind,
+                timestamp: crate::clock::now(),
+            }),
+        );
+    });
+}
+
+pub fn get_events(nft_id: u64) -> Vec<NFTEvent> {
+    NFT_EVENTS.with(|events| {
+        events
+            .borrow()
+            .range((nft_id, 0)..=(nft_id, u32::MAX))
+            .map(|(_, event)| event.into_value())
+            .collect()
+    })
+}
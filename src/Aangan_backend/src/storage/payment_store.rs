@@ -0,0 +1,52 @@
+use super::RENT_PAYMENTS;
+use crate::types::{AanganError, RentPayment, Stored};
+
+pub fn create_payments(payments: Vec<RentPayment>) -> Result<(), AanganError> {
+    RENT_PAYMENTS.with(|store| {
+        let mut store = store.borrow_mut();
+        if payments
+            .iter()
+            .any(|payment| store.contains_key(&(payment.rental_id, payment.period)))
+        {
+            return Err(AanganError::conflict("Payment schedule already exists"));
+        }
+        for payment in payments {
+            store.insert((payment.rental_id, payment.period), Stored::new(payment));
+        }
+        Ok(())
+    })
+}
+
+pub fn get_payment(rental_id: u64, period: u32) -> Option<RentPayment> {
+    RENT_PAYMENTS.with(|store| {
+        store
+            .borrow()
+            .get(&(rental_id, period))
+            .map(Stored::into_value)
+    })
+}
+
+pub fn update_payment(payment: RentPayment) -> Result<(), AanganError> {
+    RENT_PAYMENTS.with(|store| {
+        let mut store = store.borrow_mut();
+        let key = (payment.rental_id, payment.period);
+        if !store.contains_key(&key) {
+            return Err(AanganError::not_found(
+                "Payment",
+                format!("{}/{}", payment.rental_id, payment.period),
+            ));
+        }
+        store.insert(key, Stored::new(payment));
+        Ok(())
+    })
+}
+
+pub fn get_payments_by_rental(rental_id: u64) -> Vec<RentPayment> {
+    RENT_PAYMENTS.with(|store| {
+        store
+            .borrow()
+            .range((rental_id, 0)..=(rental_id, u32::MAX))
+            .map(|(_, payment)| payment.into_value())
+            .collect()
+    })
+}
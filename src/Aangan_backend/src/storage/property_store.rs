@@ -1,58 +1,417 @@
-use super::PROPERTIES;
-use crate::types::Property;
+use super::{geo_index, search_index, text_index, PROPERTIES, PROPERTIES_BY_OWNER};
+use crate::types::{
+    AanganError, GeoPoint, NearbyProperty, Property, SearchCursor, SearchPage, SearchQuery, Stored,
+};
 use candid::Principal;
 
-pub fn create_property(property: Property) -> Result<(), String> {
+pub fn create_property(property: Property) -> Result<(), AanganError> {
     PROPERTIES.with(|properties| {
         let mut properties = properties.borrow_mut();
-        properties.insert(property.id, property);
+        index_property(&property);
+        properties.insert(property.id, Stored::new(property));
         Ok(())
     })
 }
 
 pub fn get_property(id: u64) -> Option<Property> {
-    PROPERTIES.with(|properties| properties.borrow().get(&id))
+    PROPERTIES.with(|properties| properties.borrow().get(&id).map(Stored::into_value))
 }
 
-pub fn update_property(property: Property) -> Result<(), String> {
+pub fn update_property(property: Property) -> Result<(), AanganError> {
     PROPERTIES.with(|properties| {
         let mut properties = properties.borrow_mut();
-        if !properties.contains_key(&property.id) {
-            return Err("Property not found".to_string());
-        }
-        properties.insert(property.id, property);
+        let previous = properties
+            .get(&property.id)
+            .map(Stored::into_value)
+            .ok_or_else(|| AanganError::not_found("Property", property.id))?;
+        unindex_property(&previous);
+        index_property(&property);
+        properties.insert(property.id, Stored::new(property));
         Ok(())
     })
 }
 
+/// A property that has not been delisted. Public reads go through this, while
+/// `get_property` also returns delisted properties for their owner and administrators.
+pub fn get_listed_property(id: u64) -> Option<Property> {
+    get_property(id).filter(|property| !property.is_delisted())
+}
+
 pub fn get_all_properties() -> Vec<Property> {
     PROPERTIES.with(|properties| {
         properties
             .borrow()
-            .iter()
-            .map(|(_, property)| property)
+            .values()
+            .map(Stored::into_value)
+            .filter(|property| !property.is_delisted())
             .collect()
     })
 }
 
 pub fn get_properties_by_owner(owner: &Principal) -> Vec<Property> {
-    PROPERTIES.with(|properties| {
-        properties
+    let ids: Vec<u64> = PROPERTIES_BY_OWNER.with(|index| {
+        index
             .borrow()
-            .iter()
-            .filter(|(_, property)| property.owner == *owner)
-            .map(|(_, property)| property)
+            .range((*owner, 0)..=(*owner, u64::MAX))
+            .map(|((_, id), _)| id)
             .collect()
-    })
+    });
+    ids.into_iter().filter_map(get_property).collect()
 }
 
 pub fn get_available_properties() -> Vec<Property> {
     PROPERTIES.with(|properties| {
         properties
             .borrow()
-            .iter()
-            .filter(|(_, property)| property.is_available)
-            .map(|(_, property)| property)
+            .values()
+            .map(Stored::into_value)
+            .filter(|property| property.is_available)
             .collect()
     })
 }
+
+/// Filters, sorts and pages properties. Results are ordered by `(sort value, id)`, which
+/// also makes the cursor stable when several properties share a sort value.
+pub fn search_properties(query: &SearchQuery) -> SearchPage {
+    let page_size = query.page_size();
+    let mut page: Vec<(u64, Property)> = Vec::with_capacity(page_size);
+    let mut has_more = false;
+
+    search_index::scan(query, |value, id| {
+        let Some(property) = get_listed_property(id).filter(|property| query.matches(property))
+        else {
+            return true;
+        };
+        if page.len() == page_size {
+            has_more = true;
+            return false;
+        }
+        page.push((value, property));
+        true
+    });
+
+    let next_cursor = match page.last() {
+        Some((value, property)) if has_more => Some(SearchCursor {
+            sort_value: *value,
+            id: property.id,
+        }),
+        _ => None,
+    };
+
+    SearchPage {
+        items: page.into_iter().map(|(_, property)| property).collect(),
+        next_cursor,
+    }
+}
+
+/// Full-text search over title, description and address, best matches first.
+pub fn text_search(query: &str, limit: usize) -> Vec<Property> {
+    // Ranked without a limit so delisted matches do not shorten the page
+    text_index::search(query, usize::MAX)
+        .into_iter()
+        .filter_map(get_listed_property)
+        .take(limit)
+        .collect()
+}
+
+fn by_distance(
+    ids: Vec<u64>,
+    origin: &GeoPoint,
+    available_only: bool,
+    keep: impl Fn(&GeoPoint, f64) -> bool,
+) -> Vec<NearbyProperty> {
+    let mut nearby: Vec<NearbyProperty> = ids
+        .into_iter()
+        .filter_map(get_listed_property)
+        .filter(|property| property.is_available || !available_only)
+        .filter_map(|property| {
+            let location = property.location?;
+            let distance_m = geo_index::haversine_m(origin, &location);
+            keep(&location, distance_m).then_some(NearbyProperty {
+                property,
+                distance_m,
+            })
+        })
+        .collect();
+    nearby.sort_by(|a, b| a.distance_m.total_cmp(&b.distance_m));
+    nearby
+}
+
+/// Properties within `radius_m` of `center`, nearest first.
+pub fn properties_near(
+    center: &GeoPoint,
+    radius_m: f64,
+    available_only: bool,
+) -> Vec<NearbyProperty> {
+    let candidates = geo_index::candidates_near(center, radius_m);
+    by_distance(candidates, center, available_only, |_, distance_m| {
+        distance_m <= radius_m
+    })
+}
+
+/// Properties inside the bounding box, ordered by distance from its center.
+pub fn properties_in_bbox(
+    min: &GeoPoint,
+    max: &GeoPoint,
+    available_only: bool,
+) -> Vec<NearbyProperty> {
+    let crosses_antimeridian = min.longitude > max.longitude;
+    let center_longitude = if crosses_antimeridian {
+        let longitude = (min.longitude + max.longitude + 360.0) / 2.0;
+        if longitude > 180.0 {
+            longitude - 360.0
+        } else {
+            longitude
+        }
+    } else {
+        (min.longitude + max.longitude) / 2.0
+    };
+    let center = GeoPoint {
+        latitude: (min.latitude + max.latitude) / 2.0,
+        longitude: center_longitude,
+    };
+
+    let candidates = geo_index::candidates_in_bbox(min, max);
+    by_distance(candidates, &center, available_only, |location, _| {
+        let in_latitude = (min.latitude..=max.latitude).contains(&location.latitude);
+        let in_longitude = if crosses_antimeridian {
+            location.longitude >= min.longitude || location.longitude <= max.longitude
+        } else {
+            (min.longitude..=max.longitude).contains(&location.longitude)
+        };
+        in_latitude && in_longitude
+    })
+}
+
+fn index_property(property: &Property) {
+    PROPERTIES_BY_OWNER.with(|index| index.borrow_mut().insert((property.owner, property.id), ()));
+    search_index::index_property(property);
+    text_index::index_property(property);
+    geo_index::index_property(property);
+}
+
+fn unindex_property(property: &Property) {
+    PROPERTIES_BY_OWNER.with(|index| index.borrow_mut().remove(&(property.owner, property.id)));
+    search_index::unindex_property(property);
+    text_index::unindex_property(property);
+    geo_index::unindex_property(property);
+}
+
+/// Builds the property indexes for properties stored before they existed.
+pub fn backfill_indexes() {
+    if PROPERTIES_BY_OWNER.with(|index| index.borrow().is_empty()) {
+        PROPERTIES.with(|properties| {
+            for property in properties.borrow().values().map(Stored::into_value) {
+                PROPERTIES_BY_OWNER
+                    .with(|index| index.borrow_mut().insert((property.owner, property.id), ()));
+            }
+        });
+    }
+
+    if search_index::is_empty() {
+        PROPERTIES.with(|properties| {
+            for property in properties.borrow().values().map(Stored::into_value) {
+                search_index::index_property(&property);
+            }
+        });
+    }
+
+    if text_index::is_empty() {
+        PROPERTIES.with(|properties| {
+            for property in properties.borrow().values().map(Stored::into_value) {
+                text_index::index_property(&property);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{PropertyType, SortField, SortOrder};
+
+    fn store(id: u64, property_type: PropertyType, rent_amount: u64) {
+        create_property(Property::new(
+            id,
+            Principal::from_slice(&[2]),
+            "Flat".to_string(),
+            String::new(),
+            String::new(),
+            rent_amount,
+            0,
+            property_type,
+            2,
+            1,
+            800,
+            vec![],
+            vec![],
+        ))
+        .unwrap();
+    }
+
+    fn by_rent(order: SortOrder, limit: u32) -> SearchQuery {
+        SearchQuery {
+            sort_by: Some(SortField::Rent),
+            order: Some(order),
+            limit: Some(limit),
+            ..SearchQuery::default()
+        }
+    }
+
+    fn ids(page: &SearchPage) -> Vec<u64> {
+        page.items.iter().map(|property| property.id).collect()
+    }
+
+    /// Follows the cursor to the last page.
+    fn all_pages(mut query: SearchQuery) -> Vec<u64> {
+        let mut results = Vec::new();
+        loop {
+            let page = search_properties(&query);
+            results.extend(ids(&page));
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => return results,
+            }
+        }
+    }
+
+    fn store_sample() {
+        store(1, PropertyType::Apartment, 300);
+        store(2, PropertyType::House, 100);
+        store(3, PropertyType::Apartment, 200);
+        store(4, PropertyType::Apartment, 200);
+        store(5, PropertyType::Studio, 400);
+    }
+
+    #[test]
+    fn pages_follow_sort_value_then_id() {
+        store_sample();
+
+        let page = search_properties(&by_rent(SortOrder::Ascending, 2));
+        assert_eq!(ids(&page), vec![2, 3]);
+        assert_eq!(
+            page.next_cursor,
+            Some(SearchCursor {
+                sort_value: 200,
+                id: 3
+            })
+        );
+        assert_eq!(
+            all_pages(by_rent(SortOrder::Ascending, 2)),
+            vec![2, 3, 4, 1, 5]
+        );
+        assert_eq!(
+            all_pages(by_rent(SortOrder::Descending, 2)),
+            vec![5, 1, 4, 3, 2]
+        );
+    }
+
+    #[test]
+    fn type_and_sort_range_filters_narrow_the_scan() {
+        store_sample();
+
+        let query = SearchQuery {
+            property_type: Some(PropertyType::Apartment),
+            min_rent: Some(200),
+            max_rent: Some(250),
+            ..by_rent(SortOrder::Ascending, 1)
+        };
+        assert_eq!(all_pages(query), vec![3, 4]);
+    }
+
+    #[test]
+    fn other_filters_apply_while_scanning() {
+        store_sample();
+        let mut unavailable = get_property(3).unwrap();
+        unavailable.is_available = false;
+        update_property(unavailable).unwrap();
+
+        let query = SearchQuery {
+            available_only: Some(true),
+            ..by_rent(SortOrder::Ascending, 2)
+        };
+        assert_eq!(all_pages(query), vec![2, 4, 1, 5]);
+    }
+
+    #[test]
+    fn updates_move_properties_in_the_index() {
+        store_sample();
+        let mut property = get_property(5).unwrap();
+        property.rent_amount = 50;
+        update_property(property).unwrap();
+
+        assert_eq!(
+            all_pages(by_rent(SortOrder::Ascending, 10)),
+            vec![5, 2, 3, 4, 1]
+        );
+    }
+
+    #[test]
+    fn delisted_properties_are_left_out_of_public_reads() {
+        store_sample();
+        let center = GeoPoint {
+            latitude: 12.97,
+            longitude: 77.59,
+        };
+        for id in 1..=5 {
+            let mut property = get_property(id).unwrap();
+            property.set_location(Some(center));
+            update_property(property).unwrap();
+        }
+        let mut delisted = get_property(3).unwrap();
+        delisted.delist();
+        update_property(delisted).unwrap();
+
+        let listed = vec![1, 2, 4, 5];
+        let near = |ids: Vec<NearbyProperty>| {
+            let mut ids: Vec<u64> = ids.into_iter().map(|nearby| nearby.property.id).collect();
+            ids.sort();
+            ids
+        };
+        let mut text: Vec<u64> = text_search("flat", 10).iter().map(|p| p.id).collect();
+        text.sort();
+
+        assert!(get_property(3).is_some());
+        assert!(get_listed_property(3).is_none());
+        assert_eq!(
+            get_all_properties()
+                .iter()
+                .map(|p| p.id)
+                .collect::<Vec<_>>(),
+            listed
+        );
+        assert_eq!(
+            all_pages(by_rent(SortOrder::Ascending, 10)),
+            vec![2, 4, 1, 5]
+        );
+        assert_eq!(text, listed);
+        assert_eq!(near(properties_near(&center, 1_000.0, false)), listed);
+        assert_eq!(near(properties_in_bbox(&center, &center, false)), listed);
+
+        let mut relisted = get_property(3).unwrap();
+        relisted.relist();
+        update_property(relisted).unwrap();
+
+        assert!(!get_property(3).unwrap().is_available);
+        assert_eq!(get_all_properties().len(), 5);
+        assert_eq!(
+            near(properties_near(&center, 1_000.0, false)),
+            vec![1, 2, 3, 4, 5]
+        );
+    }
+
+    #[test]
+    fn cursor_outside_the_range_yields_nothing() {
+        store_sample();
+
+        let query = SearchQuery {
+            max_rent: Some(250),
+            cursor: Some(SearchCursor {
+                sort_value: 300,
+                id: 1,
+            }),
+            ..by_rent(SortOrder::Ascending, 10)
+        };
+        assert!(search_properties(&query).items.is_empty());
+    }
+}
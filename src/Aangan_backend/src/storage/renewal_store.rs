@@ -0,0 +1,59 @@
+use super::LEASE_RENEWALS;
+use crate::types::{AanganError, LeaseRenewal, Stored};
+
+/// Renewals of a rental are keyed by `(rental_id, sequence)`, so the last entry is the
+/// most recent one and at most that one can still be pending.
+pub fn get_latest_renewal(rental_id: u64) -> Option<LeaseRenewal> {
+    LEASE_RENEWALS.with(|store| {
+        store
+            .borrow()
+            .range((rental_id, 0)..=(rental_id, u32::MAX))
+            .last()
+            .map(|(_, renewal)| renewal.into_value())
+    })
+}
+
+pub fn get_pending_renewal(rental_id: u64) -> Option<LeaseRenewal> {
+    get_latest_renewal(rental_id).filter(LeaseRenewal::is_pending)
+}
+
+pub fn next_sequence(rental_id: u64) -> u32 {
+    get_latest_renewal(rental_id).map_or(0, |renewal| renewal.sequence + 1)
+}
+
+pub fn create_renewal(renewal: LeaseRenewal) -> Result<(), AanganError> {
+    LEASE_RENEWALS.with(|store| {
+        let mut store = store.borrow_mut();
+        let key = (renewal.rental_id, renewal.sequence);
+        if store.contains_key(&key) {
+            return Err(AanganError::conflict("Renewal already exists"));
+        }
+        store.insert(key, Stored::new(renewal));
+        Ok(())
+    })
+}
+
+pub fn update_renewal(renewal: LeaseRenewal) -> Result<(), AanganError> {
+    LEASE_RENEWALS.with(|store| {
+        let mut store = store.borrow_mut();
+        let key = (renewal.rental_id, renewal.sequence);
+        if !store.contains_key(&key) {
+            return Err(AanganError::not_found(
+                "Renewal",
+                format!("{}/{}", renewal.rental_id, renewal.sequence),
+            ));
+        }
+        store.insert(key, Stored::new(renewal));
+        Ok(())
+    })
+}
+
+pub fn get_renewal_history(rental_id: u64) -> Vec<LeaseRenewal> {
+    LEASE_RENEWALS.with(|store| {
+        store
+            .borrow()
+            .range((rental_id, 0)..=(rental_id, u32::MAX))
+            .map(|(_, renewal)| renewal.into_value())
+            .collect()
+    })
+}
@@ -1,80 +1,365 @@
-use super::{NFTS, RENTALS};
-use crate::types::{NFTMetadata, RentalAgreement};
+use super::{
+    NFTS, NFTS_BY_OWNER, NFT_SUPPLY, RENTALS, RENTALS_BY_LANDLORD, RENTALS_BY_PROPERTY,
+    RENTALS_BY_STATUS, RENTALS_BY_TENANT,
+};
+use crate::types::{AanganError, NFTMetadata, RentalAgreement, RentalStatus, Stored};
 use candid::Principal;
 
-pub fn create_rental(rental: RentalAgreement) -> Result<(), String> {
+pub fn create_rental(rental: RentalAgreement) -> Result<(), AanganError> {
     RENTALS.with(|rentals| {
         let mut rentals = rentals.borrow_mut();
-        rentals.insert(rental.id, rental);
+        index_rental(&rental);
+        rentals.insert(rental.id, Stored::new(rental));
         Ok(())
     })
 }
 
 pub fn get_rental(id: u64) -> Option<RentalAgreement> {
-    RENTALS.with(|rentals| rentals.borrow().get(&id))
+    RENTALS.with(|rentals| rentals.borrow().get(&id).map(Stored::into_value))
 }
 
-pub fn update_rental(rental: RentalAgreement) -> Result<(), String> {
+pub fn update_rental(rental: RentalAgreement) -> Result<(), AanganError> {
     RENTALS.with(|rentals| {
         let mut rentals = rentals.borrow_mut();
-        if !rentals.contains_key(&rental.id) {
-            return Err("Rental not found".to_string());
-        }
-        rentals.insert(rental.id, rental);
+        let previous = rentals
+            .get(&rental.id)
+            .map(Stored::into_value)
+            .ok_or_else(|| AanganError::not_found("Rental", rental.id))?;
+        unindex_rental(&previous);
+        index_rental(&rental);
+        rentals.insert(rental.id, Stored::new(rental));
         Ok(())
     })
 }
 
 pub fn get_rentals_by_tenant(tenant: &Principal) -> Vec<RentalAgreement> {
-    RENTALS.with(|rentals| {
-        rentals
-            .borrow()
-            .iter()
-            .filter(|(_, rental)| rental.tenant == *tenant)
-            .map(|(_, rental)| rental)
-            .collect()
-    })
+    let ids = RENTALS_BY_TENANT.with(|index| principal_range(&index.borrow(), tenant));
+    ids.into_iter().filter_map(get_rental).collect()
 }
 
 pub fn get_rentals_by_landlord(landlord: &Principal) -> Vec<RentalAgreement> {
-    RENTALS.with(|rentals| {
-        rentals
+    let ids = RENTALS_BY_LANDLORD.with(|index| principal_range(&index.borrow(), landlord));
+    ids.into_iter().filter_map(get_rental).collect()
+}
+
+pub fn get_rentals_by_status(status: &RentalStatus) -> Vec<RentalAgreement> {
+    let key = status_key(status);
+    let ids: Vec<u64> = RENTALS_BY_STATUS.with(|index| {
+        index
             .borrow()
-            .iter()
-            .filter(|(_, rental)| rental.landlord == *landlord)
-            .map(|(_, rental)| rental)
+            .range((key, 0)..=(key, u64::MAX))
+            .map(|((_, id), _)| id)
             .collect()
-    })
+    });
+    ids.into_iter().filter_map(get_rental).collect()
 }
 
-pub fn get_rental_by_property(property_id: u64) -> Option<RentalAgreement> {
-    RENTALS.with(|rentals| {
-        rentals
+pub fn get_rentals_by_property(property_id: u64) -> Vec<RentalAgreement> {
+    let ids: Vec<u64> = RENTALS_BY_PROPERTY.with(|index| {
+        index
             .borrow()
-            .iter()
-            .find(|(_, rental)| rental.property_id == property_id)
-            .map(|(_, rental)| rental)
-    })
+            .range((property_id, 0)..=(property_id, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    });
+    ids.into_iter().filter_map(get_rental).collect()
 }
 
-pub fn create_nft(nft: NFTMetadata) -> Result<(), String> {
+pub fn create_nft(nft: NFTMetadata) -> Result<(), AanganError> {
     NFTS.with(|nfts| {
         let mut nfts = nfts.borrow_mut();
-        nfts.insert(nft.id, nft);
+        NFTS_BY_OWNER.with(|index| index.borrow_mut().insert((nft.owner, nft.id), ()));
+        adjust_supply(None, &nft);
+        crate::certification::certify_nft(&nft);
+        nfts.insert(nft.id, Stored::new(nft));
         Ok(())
     })
 }
 
 pub fn get_nft(id: u64) -> Option<NFTMetadata> {
-    NFTS.with(|nfts| nfts.borrow().get(&id))
+    NFTS.with(|nfts| nfts.borrow().get(&id).map(Stored::into_value))
+}
+
+pub fn update_nft(nft: NFTMetadata) -> Result<(), AanganError> {
+    NFTS.with(|nfts| {
+        let mut nfts = nfts.borrow_mut();
+        let previous = nfts
+            .get(&nft.id)
+            .map(Stored::into_value)
+            .ok_or_else(|| AanganError::not_found("NFT", nft.id))?;
+        NFTS_BY_OWNER.with(|index| {
+            let mut index = index.borrow_mut();
+            index.remove(&(previous.owner, previous.id));
+            index.insert((nft.owner, nft.id), ());
+        });
+        adjust_supply(Some(&previous), &nft);
+        crate::certification::certify_nft(&nft);
+        nfts.insert(nft.id, Stored::new(nft));
+        Ok(())
+    })
+}
+
+pub fn get_all_nfts() -> Vec<NFTMetadata> {
+    NFTS.with(|nfts| nfts.borrow().values().map(Stored::into_value).collect())
+}
+
+/// Up to `limit` NFTs in id order, starting after `after`.
+pub fn get_nft_batch(after: Option<u64>, limit: usize) -> Vec<NFTMetadata> {
+    let start = match after {
+        Some(u64::MAX) => return Vec::new(),
+        Some(after) => after + 1,
+        None => 0,
+    };
+    NFTS.with(|nfts| {
+        nfts.borrow()
+            .range(start..)
+            .take(limit)
+            .map(|(_, nft)| nft.into_value())
+            .collect()
+    })
 }
 
 pub fn get_nfts_by_owner(owner: &Principal) -> Vec<NFTMetadata> {
+    let ids = NFTS_BY_OWNER.with(|index| principal_range(&index.borrow(), owner));
+    ids.into_iter().filter_map(get_nft).collect()
+}
+
+/// An NFT that still stands for a lease. Retired NFTs (superseded or voided) are kept for
+/// their history but are no longer ICRC-7 tokens: they count towards no balance and no
+/// supply.
+pub fn get_token(id: u64) -> Option<NFTMetadata> {
+    get_nft(id).filter(|nft| !nft.is_retired())
+}
+
+/// Marks a supply that has not been counted yet, as after the upgrade that introduced it.
+pub const UNCOUNTED_SUPPLY: u64 = u64::MAX;
+
+/// Number of tokens, i.e. NFTs that are not retired.
+pub fn nft_supply() -> u64 {
+    match NFT_SUPPLY.with(|supply| *supply.borrow().get()) {
+        UNCOUNTED_SUPPLY => count_supply(),
+        supply => supply,
+    }
+}
+
+fn count_supply() -> u64 {
     NFTS.with(|nfts| {
         nfts.borrow()
-            .iter()
-            .filter(|(_, nft)| nft.owner == *owner)
-            .map(|(_, nft)| nft)
+            .values()
+            .map(Stored::into_value)
+            .filter(|nft| !nft.is_retired())
+            .count() as u64
+    })
+}
+
+fn set_supply(supply: u64) {
+    NFT_SUPPLY.with(|cell| {
+        cell.borrow_mut()
+            .set(supply)
+            .expect("Failed to write NFT supply")
+    });
+}
+
+/// Keeps the supply in step with a minted or changed NFT.
+fn adjust_supply(previous: Option<&NFTMetadata>, nft: &NFTMetadata) {
+    let was_token = previous.is_some_and(|previous| !previous.is_retired());
+    let is_token = !nft.is_retired();
+    let supply = NFT_SUPPLY.with(|supply| *supply.borrow().get());
+    if was_token == is_token || supply == UNCOUNTED_SUPPLY {
+        return;
+    }
+    set_supply(if is_token {
+        supply + 1
+    } else {
+        supply.saturating_sub(1)
+    });
+}
+
+/// Counts the supply once for NFTs minted before it was maintained.
+pub fn backfill_nft_supply() {
+    if NFT_SUPPLY.with(|supply| *supply.borrow().get()) == UNCOUNTED_SUPPLY {
+        set_supply(count_supply());
+    }
+}
+
+/// Number of tokens held by `owner`.
+pub fn count_nfts_by_owner(owner: &Principal) -> u64 {
+    NFTS_BY_OWNER.with(|index| {
+        index
+            .borrow()
+            .range((*owner, 0)..=(*owner, u64::MAX))
+            .filter(|((_, id), _)| get_token(*id).is_some())
+            .count() as u64
+    })
+}
+
+/// Up to `take` token ids of `owner` in ascending order, starting after `prev`.
+pub fn get_nft_ids_by_owner(owner: &Principal, prev: Option<u64>, take: usize) -> Vec<u64> {
+    let start = match prev {
+        Some(u64::MAX) => return Vec::new(),
+        Some(prev) => prev + 1,
+        None => 0,
+    };
+    NFTS_BY_OWNER.with(|index| {
+        index
+            .borrow()
+            .range((*owner, start)..=(*owner, u64::MAX))
+            .map(|((_, id), _)| id)
+            .filter(|id| get_token(*id).is_some())
+            .take(take)
             .collect()
     })
 }
+
+/// Whether `a` and `b` are landlord and tenant of a Confirmed or Active rental.
+pub fn share_live_rental(a: &Principal, b: &Principal) -> bool {
+    get_rentals_by_tenant(a)
+        .iter()
+        .any(|rental| rental.landlord == *b && rental.is_live())
+        || get_rentals_by_landlord(a)
+            .iter()
+            .any(|rental| rental.tenant == *b && rental.is_live())
+}
+
+fn principal_range<M: ic_stable_structures::Memory>(
+    index: &ic_stable_structures::StableBTreeMap<(Principal, u64), (), M>,
+    principal: &Principal,
+) -> Vec<u64> {
+    index
+        .range((*principal, 0)..=(*principal, u64::MAX))
+        .map(|((_, id), _)| id)
+        .collect()
+}
+
+/// Key of `status` in the status index. The values are persisted and must not change.
+fn status_key(status: &RentalStatus) -> u8 {
+    match status {
+        RentalStatus::Requested => 0,
+        RentalStatus::Confirmed => 1,
+        RentalStatus::Active => 2,
+        RentalStatus::Completed => 3,
+        RentalStatus::Cancelled => 4,
+        RentalStatus::Expired => 5,
+        RentalStatus::Rejected => 6,
+        RentalStatus::Terminating => 7,
+    }
+}
+
+fn index_rental(rental: &RentalAgreement) {
+    RENTALS_BY_TENANT.with(|index| index.borrow_mut().insert((rental.tenant, rental.id), ()));
+    RENTALS_BY_LANDLORD.with(|index| index.borrow_mut().insert((rental.landlord, rental.id), ()));
+    RENTALS_BY_PROPERTY.with(|index| {
+        index
+            .borrow_mut()
+            .insert((rental.property_id, rental.id), ())
+    });
+    RENTALS_BY_STATUS.with(|index| {
+        index
+            .borrow_mut()
+            .insert((status_key(&rental.status), rental.id), ())
+    });
+}
+
+fn unindex_rental(rental: &RentalAgreement) {
+    RENTALS_BY_TENANT.with(|index| index.borrow_mut().remove(&(rental.tenant, rental.id)));
+    RENTALS_BY_LANDLORD.with(|index| index.borrow_mut().remove(&(rental.landlord, rental.id)));
+    RENTALS_BY_PROPERTY.with(|index| index.borrow_mut().remove(&(rental.property_id, rental.id)));
+    RENTALS_BY_STATUS.with(|index| {
+        index
+            .borrow_mut()
+            .remove(&(status_key(&rental.status), rental.id))
+    });
+}
+
+/// Builds the rental and NFT indexes for records stored before they existed.
+pub fn backfill_indexes() {
+    // The status index was added after the others
+    let missing_index = RENTALS_BY_TENANT.with(|index| index.borrow().is_empty())
+        || RENTALS_BY_STATUS.with(|index| index.borrow().is_empty());
+    if missing_index {
+        RENTALS.with(|rentals| {
+            for rental in rentals.borrow().values().map(Stored::into_value) {
+                index_rental(&rental);
+            }
+        });
+    }
+
+    if NFTS_BY_OWNER.with(|index| index.borrow().is_empty()) {
+        NFTS.with(|nfts| {
+            for nft in nfts.borrow().values().map(Stored::into_value) {
+                NFTS_BY_OWNER.with(|index| index.borrow_mut().insert((nft.owner, nft.id), ()));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::NFTStatus;
+
+    fn owner() -> Principal {
+        Principal::from_slice(&[3])
+    }
+
+    fn mint(id: u64) {
+        create_nft(NFTMetadata::new(
+            id,
+            owner(),
+            1,
+            id,
+            "Flat".to_string(),
+            "12 MG Road".to_string(),
+            String::new(),
+            25_000_000,
+            0,
+            1,
+        ))
+        .unwrap();
+    }
+
+    fn retire(id: u64, status: NFTStatus) {
+        let mut nft = get_nft(id).unwrap();
+        nft.set_status(status);
+        update_nft(nft).unwrap();
+    }
+
+    #[test]
+    fn retired_nfts_leave_balances_and_supply_alike() {
+        backfill_nft_supply();
+        for id in 1..=4 {
+            mint(id);
+        }
+        retire(2, NFTStatus::Superseded);
+        retire(3, NFTStatus::Void);
+
+        assert_eq!(nft_supply(), 2);
+        assert_eq!(nft_supply(), count_supply());
+        assert_eq!(count_nfts_by_owner(&owner()), 2);
+        assert_eq!(get_nft_ids_by_owner(&owner(), None, 10), vec![1, 4]);
+        assert_eq!(get_nft_ids_by_owner(&owner(), Some(1), 1), vec![4]);
+        assert!(get_token(2).is_none());
+        assert_eq!(get_nfts_by_owner(&owner()).len(), 4);
+
+        // A completed lease keeps its token
+        retire(1, NFTStatus::Completed);
+        assert_eq!(nft_supply(), 2);
+    }
+
+    #[test]
+    fn uncounted_supply_is_counted_once() {
+        mint(1);
+        mint(2);
+        retire(2, NFTStatus::Void);
+        assert_eq!(
+            NFT_SUPPLY.with(|supply| *supply.borrow().get()),
+            UNCOUNTED_SUPPLY
+        );
+        assert_eq!(nft_supply(), 1);
+
+        backfill_nft_supply();
+        mint(3);
+
+        assert_eq!(NFT_SUPPLY.with(|supply| *supply.borrow().get()), 2);
+    }
+}
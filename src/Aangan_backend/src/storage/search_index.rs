@@ -0,0 +1,112 @@
+//! Ordered index behind `search_properties`.
+//!
+//! Every property has one `(scope, sort value, property_id)` entry per sort field under the
+//! scope of its property type, and one under the scope matching any type. A result page is
+//! a range scan from the cursor within the query's scope, narrowed to the filter bounds on
+//! the sort value, so a query reads properties only up to the end of its page.
+
+use super::PROPERTY_SEARCH_INDEX;
+use crate::types::{Property, PropertyType, SearchQuery, SortField};
+use std::ops::Bound;
+
+type Key = (u8, u64, u64);
+
+const ANY_TYPE: u8 = 0;
+
+/// The scope values are persisted and must not change.
+fn scope(field: &SortField, property_type: Option<&PropertyType>) -> u8 {
+    let field = match field {
+        SortField::Rent => 0,
+        SortField::Area => 1,
+        SortField::CreatedAt => 2,
+    };
+    let property_type = match property_type {
+        None => ANY_TYPE,
+        Some(PropertyType::Apartment) => 1,
+        Some(PropertyType::House) => 2,
+        Some(PropertyType::Villa) => 3,
+        Some(PropertyType::Studio) => 4,
+        Some(PropertyType::Condo) => 5,
+        Some(PropertyType::Townhouse) => 6,
+    };
+    (field << 4) | property_type
+}
+
+fn keys(property: &Property) -> Vec<Key> {
+    let mut keys = Vec::with_capacity(2 * SortField::ALL.len());
+    for field in SortField::ALL {
+        let value = field.value_of(property);
+        keys.push((scope(&field, None), value, property.id));
+        keys.push((
+            scope(&field, Some(&property.property_type)),
+            value,
+            property.id,
+        ));
+    }
+    keys
+}
+
+pub fn index_property(property: &Property) {
+    PROPERTY_SEARCH_INDEX.with(|index| {
+        let mut index = index.borrow_mut();
+        for key in keys(property) {
+            index.insert(key, ());
+        }
+    });
+}
+
+pub fn unindex_property(property: &Property) {
+    PROPERTY_SEARCH_INDEX.with(|index| {
+        let mut index = index.borrow_mut();
+        for key in keys(property) {
+            index.remove(&key);
+        }
+    });
+}
+
+pub fn is_empty() -> bool {
+    PROPERTY_SEARCH_INDEX.with(|index| index.borrow().is_empty())
+}
+
+/// Visits the `(sort value, property_id)` of the properties `query` may return, in result
+/// order and starting after its cursor, until `visit` returns false.
+pub fn scan(query: &SearchQuery, mut visit: impl FnMut(u64, u64) -> bool) {
+    let scope = scope(&query.sort_field(), query.property_type.as_ref());
+    let (min, max) = query.sort_value_range();
+    let mut start = (scope, min, 0);
+    let mut end = (scope, max, u64::MAX);
+    let (mut start_excluded, mut end_excluded) = (false, false);
+
+    if let Some(cursor) = &query.cursor {
+        let position = (scope, cursor.sort_value, cursor.id);
+        if query.is_descending() && position <= end {
+            (end, end_excluded) = (position, true);
+        } else if !query.is_descending() && position >= start {
+            (start, start_excluded) = (position, true);
+        }
+    }
+
+    if start > end || (start == end && (start_excluded || end_excluded)) {
+        return;
+    }
+
+    let bound = |key: Key, excluded: bool| {
+        if excluded {
+            Bound::Excluded(key)
+        } else {
+            Bound::Included(key)
+        }
+    };
+    let range = (bound(start, start_excluded), bound(end, end_excluded));
+
+    PROPERTY_SEARCH_INDEX.with(|index| {
+        let index = index.borrow();
+        let entries = index.range(range);
+        let mut keys: Box<dyn Iterator<Item = (Key, ())>> = if query.is_descending() {
+            Box::new(entries.rev())
+        } else {
+            Box::new(entries)
+        };
+        keys.all(|((_, value, id), ())| visit(value, id));
+    });
+}
@@ -0,0 +1,208 @@
+//! Inverted index over property title, description and address.
+//!
+//! Each `(term, property_id)` pair maps to the number of times the term occurs in that
+//! property's text. Terms sort lexicographically, so a prefix lookup is a range scan.
+
+use super::TEXT_INDEX;
+use crate::types::Property;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// Longer tokens are truncated to this many bytes.
+pub const MAX_TERM_BYTES: usize = 32;
+const MIN_TERM_CHARS: usize = 2;
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Term(pub String);
+
+impl Storable for Term {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Borrowed(self.0.as_bytes())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Term(String::from_utf8(bytes.into_owned()).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: MAX_TERM_BYTES as u32,
+        is_fixed_size: false,
+    };
+}
+
+fn truncate_term(token: &str) -> String {
+    let mut end = token.len().min(MAX_TERM_BYTES);
+    while !token.is_char_boundary(end) {
+        end -= 1;
+    }
+    token[..end].to_string()
+}
+
+/// Lowercases `text` and splits it on anything that is not alphanumeric.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| token.chars().count() >= MIN_TERM_CHARS)
+        .map(|token| truncate_term(&token.to_lowercase()))
+        .collect()
+}
+
+fn term_frequencies(property: &Property) -> BTreeMap<String, u32> {
+    let mut frequencies = BTreeMap::new();
+    for text in [&property.title, &property.description, &property.address] {
+        for term in tokenize(text) {
+            *frequencies.entry(term).or_insert(0) += 1;
+        }
+    }
+    frequencies
+}
+
+pub fn index_property(property: &Property) {
+    TEXT_INDEX.with(|index| {
+        let mut index = index.borrow_mut();
+        for (term, frequency) in term_frequencies(property) {
+            index.insert((Term(term), property.id), frequency);
+        }
+    });
+}
+
+pub fn unindex_property(property: &Property) {
+    TEXT_INDEX.with(|index| {
+        let mut index = index.borrow_mut();
+        for term in term_frequencies(property).into_keys() {
+            index.remove(&(Term(term), property.id));
+        }
+    });
+}
+
+pub fn is_empty() -> bool {
+    TEXT_INDEX.with(|index| index.borrow().is_empty())
+}
+
+/// Summed term frequency per property for every term starting with `prefix`.
+fn prefix_matches(prefix: &str) -> BTreeMap<u64, u32> {
+    let mut matches = BTreeMap::new();
+    TEXT_INDEX.with(|index| {
+        for ((term, property_id), frequency) in
+            index.borrow().range((Term(prefix.to_string()), 0)..)
+        {
+            if !term.0.starts_with(prefix) {
+                break;
+            }
+            *matches.entry(property_id).or_insert(0) += frequency;
+        }
+    });
+    matches
+}
+
+fn exact_matches(term: &str) -> BTreeMap<u64, u32> {
+    let term = Term(term.to_string());
+    TEXT_INDEX.with(|index| {
+        index
+            .borrow()
+            .range((term.clone(), 0)..=(term, u64::MAX))
+            .map(|((_, property_id), frequency)| (property_id, frequency))
+            .collect()
+    })
+}
+
+/// Returns ids of properties containing every query term, best matches first. The last
+/// query term is matched as a prefix so results can be shown while the user is typing.
+pub fn search(query: &str, limit: usize) -> Vec<u64> {
+    let terms = tokenize(query);
+    let Some((last, complete)) = terms.split_last() else {
+        return Vec::new();
+    };
+
+    let mut scores = prefix_matches(last);
+    for term in complete {
+        let matches = exact_matches(term);
+        scores = scores
+            .into_iter()
+            .filter_map(|(id, score)| matches.get(&id).map(|frequency| (id, score + frequency)))
+            .collect();
+    }
+
+    let mut ranked: Vec<(u64, u32)> = scores.into_iter().collect();
+    ranked.sort_by(|(a_id, a_score), (b_id, b_score)| b_score.cmp(a_score).then(a_id.cmp(b_id)));
+    ranked.into_iter().take(limit).map(|(id, _)| id).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PropertyType;
+    use candid::Principal;
+
+    fn property(id: u64, title: &str, description: &str) -> Property {
+        Property::new(
+            id,
+            Principal::from_slice(&[2]),
+            title.to_string(),
+            description.to_string(),
+            "12 MG Road, Bengaluru".to_string(),
+            25_000_000,
+            100_000_000,
+            PropertyType::Apartment,
+            2,
+            1,
+            800,
+            vec![],
+            vec![],
+        )
+    }
+
+    #[test]
+    fn tokenize_lowercases_and_drops_short_tokens() {
+        assert_eq!(
+            tokenize("Sunny 2BHK, near M.G. Road!"),
+            vec!["sunny", "2bhk", "near", "road"]
+        );
+    }
+
+    #[test]
+    fn tokenize_truncates_long_terms_at_char_boundary() {
+        let long = "a".repeat(40);
+        assert_eq!(tokenize(&long), vec!["a".repeat(MAX_TERM_BYTES)]);
+
+        // 31 ASCII bytes followed by a two-byte character straddling the cutoff
+        let straddling = format!("{}é", "b".repeat(31));
+        assert_eq!(tokenize(&straddling), vec!["b".repeat(31)]);
+    }
+
+    #[test]
+    fn long_query_terms_match_truncated_index_terms() {
+        let word = "supercalifragilisticexpialidocious";
+        index_property(&property(1, word, ""));
+
+        assert_eq!(search(&format!("{} flat", word), 10), Vec::<u64>::new());
+        assert_eq!(search(word, 10), vec![1]);
+        assert_eq!(search(&format!("{}ly", word), 10), vec![1]);
+    }
+
+    #[test]
+    fn last_term_matches_as_prefix_and_others_exactly() {
+        index_property(&property(1, "Garden flat", "Quiet garden"));
+        index_property(&property(2, "Penthouse", "Roof garden"));
+        index_property(&property(3, "Gardener's cottage", ""));
+
+        // Property 1 mentions the term twice and ranks first
+        assert_eq!(search("gard", 10), vec![1, 2, 3]);
+        assert_eq!(search("garden", 10), vec![1, 2, 3]);
+        assert_eq!(search("garden fl", 10), vec![1]);
+        assert_eq!(search("garden roo", 10), vec![2]);
+        assert_eq!(search("gard", 1), vec![1]);
+        assert!(search("x", 10).is_empty());
+    }
+
+    #[test]
+    fn unindexed_property_is_not_found() {
+        let flat = property(1, "Garden flat", "");
+        index_property(&flat);
+        unindex_property(&flat);
+
+        assert!(search("garden", 10).is_empty());
+        assert!(is_empty());
+    }
+}
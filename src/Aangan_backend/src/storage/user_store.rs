@@ -1,36 +1,46 @@
 use super::USERS;
-use crate::types::User;
+use crate::types::{AanganError, Stored, User};
 use candid::Principal;
+use std::ops::Bound;
 
-pub fn create_user(user: User) -> Result<(), String> {
+pub fn create_user(user: User) -> Result<(), AanganError> {
     USERS.with(|users| {
         let mut users = users.borrow_mut();
         if users.contains_key(&user.user_principal) {
-            return Err("User already exists".to_string());
+            return Err(AanganError::conflict("User already exists"));
         }
-        users.insert(user.user_principal, user);
+        users.insert(user.user_principal, Stored::new(user));
         Ok(())
     })
 }
 
 pub fn get_user(principal: &Principal) -> Option<User> {
-    USERS.with(|users| users.borrow().get(principal))
+    USERS.with(|users| users.borrow().get(principal).map(Stored::into_value))
 }
 
-pub fn update_user(principal: &Principal, updated_user: User) -> Result<User, String> {
+pub fn update_user(principal: &Principal, updated_user: User) -> Result<User, AanganError> {
     USERS.with(|users| {
         let mut users = users.borrow_mut();
         if users.contains_key(principal) {
-            users.insert(*principal, updated_user.clone());
+            users.insert(*principal, Stored::new(updated_user.clone()));
             Ok(updated_user)
         } else {
-            Err("User not found".to_string())
+            Err(AanganError::not_found("User", principal))
         }
     })
 }
 
-pub fn get_all_users() -> Vec<User> {
-    USERS.with(|users| users.borrow().values().collect())
+/// Up to `limit` users ordered by principal, starting after `after`.
+pub fn get_users_page(after: Option<Principal>, limit: usize) -> Vec<User> {
+    let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+    USERS.with(|users| {
+        users
+            .borrow()
+            .range((start, Bound::Unbounded))
+            .map(|(_, user)| user.into_value())
+            .take(limit)
+            .collect()
+    })
 }
 
 pub fn user_exists(principal: &Principal) -> bool {
@@ -0,0 +1,29 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+
+/// The written lease both parties sign: the SHA-256 hash of the lease document, which is
+/// kept off-chain, and the clauses it contains.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LeaseTerms {
+    pub document_hash: ByteBuf,
+    pub clauses: Vec<String>,
+}
+
+/// A party's acceptance of the agreement's terms as identified by `terms_hash`.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Acceptance {
+    pub signer: Principal,
+    pub terms_hash: ByteBuf,
+    pub accepted_at: u64,
+}
+
+impl Acceptance {
+    pub fn new(signer: Principal, terms_hash: [u8; 32]) -> Self {
+        Self {
+            signer,
+            terms_hash: ByteBuf::from(terms_hash.to_vec()),
+            accepted_at: crate::clock::now(),
+        }
+    }
+}
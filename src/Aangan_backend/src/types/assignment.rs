@@ -0,0 +1,58 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+use super::versioned::{VersionedRecord, LEGACY_VERSION};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum AssignmentStatus {
+    Proposed,
+    Completed,
+    Rejected,
+    Cancelled,
+}
+
+/// A tenant's request to hand their lease (and its NFT) over to another principal.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LeaseAssignment {
+    pub rental_id: u64,
+    pub nft_id: u64,
+    pub sequence: u32,
+    pub from: Principal,
+    pub to: Principal,
+    pub status: AssignmentStatus,
+    pub proposed_at: u64,
+    pub updated_at: u64,
+}
+
+impl LeaseAssignment {
+    pub fn new(rental_id: u64, nft_id: u64, sequence: u32, from: Principal, to: Principal) -> Self {
+        let timestamp = crate::clock::now();
+        Self {
+            rental_id,
+            nft_id,
+            sequence,
+            from,
+            to,
+            status: AssignmentStatus::Proposed,
+            proposed_at: timestamp,
+            updated_at: timestamp,
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.status == AssignmentStatus::Proposed
+    }
+
+    pub fn resolve(&mut self, status: AssignmentStatus) {
+        self.status = status;
+        self.updated_at = crate::clock::now();
+    }
+}
+
+impl VersionedRecord for LeaseAssignment {
+    const VERSION: u8 = LEGACY_VERSION;
+
+    fn upgrade(version: u8, _payload: &[u8]) -> Result<Self, String> {
+        Err(format!("unknown LeaseAssignment schema version {}", version))
+    }
+}
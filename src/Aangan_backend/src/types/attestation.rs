@@ -0,0 +1,24 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+
+pub use aangan_attestation::{AttestedStatus, LeaseAttestation};
+
+/// An attestation the canister has signed, ready to be fetched with `get_lease_attestation`.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PreparedLeaseAttestation {
+    pub attestation: LeaseAttestation,
+    /// The signed message, the canonical encoding of `attestation`.
+    pub message: ByteBuf,
+    pub canister_sig_pk_der: ByteBuf,
+}
+
+/// Everything a third party needs to verify an attestation against the IC root key.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SignedLeaseAttestation {
+    pub attestation: LeaseAttestation,
+    pub message: ByteBuf,
+    /// CBOR-encoded canister signature on `message`.
+    pub signature: ByteBuf,
+    pub canister_sig_pk_der: ByteBuf,
+}
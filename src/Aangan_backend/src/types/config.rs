@@ -0,0 +1,88 @@
+use candid::{CandidType, Principal};
+use ic_stable_structures::Storable;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct InitArgs {
+    pub ledger_canister_id: Option<Principal>,
+    /// Administrators in addition to the canister controllers.
+    pub admins: Option<Vec<Principal>>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Config {
+    pub ledger_canister_id: Option<Principal>,
+    pub admins: Option<Vec<Principal>>,
+}
+
+impl Config {
+    pub fn apply(&mut self, args: InitArgs) {
+        if let Some(ledger) = args.ledger_canister_id {
+            self.ledger_canister_id = Some(ledger);
+        }
+        for admin in args.admins.unwrap_or_default() {
+            self.grant_admin(admin);
+        }
+    }
+
+    /// Returns `false` if `principal` already was an administrator.
+    pub fn grant_admin(&mut self, principal: Principal) -> bool {
+        let admins = self.admins.get_or_insert_with(Vec::new);
+        if admins.contains(&principal) {
+            return false;
+        }
+        admins.push(principal);
+        true
+    }
+
+    /// Returns `false` if `principal` was not an administrator.
+    pub fn revoke_admin(&mut self, principal: &Principal) -> bool {
+        let Some(admins) = self.admins.as_mut() else {
+            return false;
+        };
+        let len = admins.len();
+        admins.retain(|admin| admin != principal);
+        admins.len() != len
+    }
+
+    pub fn is_admin(&self, principal: &Principal) -> bool {
+        self.admins
+            .as_ref()
+            .is_some_and(|admins| admins.contains(principal))
+    }
+}
+
+impl Storable for Config {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(candid::encode_one(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::decode_one(&bytes).unwrap()
+    }
+
+    const BOUND: ic_stable_structures::storable::Bound =
+        ic_stable_structures::storable::Bound::Unbounded;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn admins_are_granted_once_and_revoked() {
+        let admin = Principal::from_slice(&[9]);
+        let mut config = Config::default();
+        config.apply(InitArgs {
+            ledger_canister_id: None,
+            admins: Some(vec![admin]),
+        });
+
+        assert!(!config.grant_admin(admin));
+        assert!(config.is_admin(&admin));
+        assert!(config.revoke_admin(&admin));
+        assert!(!config.is_admin(&admin));
+        assert!(!config.revoke_admin(&admin));
+    }
+}
@@ -0,0 +1,75 @@
+use candid::CandidType;
+use serde::Deserialize;
+use std::fmt;
+
+/// Error returned by every fallible endpoint. Clients should match on the variant and its
+/// fields; the free-text parts are for humans only.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum AanganError {
+    NotFound { entity: String, id: String },
+    Unauthorized(String),
+    InvalidState { expected: String, actual: String },
+    Validation { field: String, reason: String },
+    Conflict(String),
+    RateLimited { retry_after_secs: u64 },
+    Ledger(String),
+}
+
+impl AanganError {
+    pub fn not_found(entity: &str, id: impl ToString) -> Self {
+        AanganError::NotFound {
+            entity: entity.to_string(),
+            id: id.to_string(),
+        }
+    }
+
+    pub fn unauthorized(reason: impl Into<String>) -> Self {
+        AanganError::Unauthorized(reason.into())
+    }
+
+    pub fn invalid_state(expected: impl Into<String>, actual: impl Into<String>) -> Self {
+        AanganError::InvalidState {
+            expected: expected.into(),
+            actual: actual.into(),
+        }
+    }
+
+    pub fn validation(field: &str, reason: impl Into<String>) -> Self {
+        AanganError::Validation {
+            field: field.to_string(),
+            reason: reason.into(),
+        }
+    }
+
+    pub fn conflict(reason: impl Into<String>) -> Self {
+        AanganError::Conflict(reason.into())
+    }
+
+    pub fn rate_limited(retry_after_secs: u64) -> Self {
+        AanganError::RateLimited { retry_after_secs }
+    }
+
+    pub fn ledger(reason: impl Into<String>) -> Self {
+        AanganError::Ledger(reason.into())
+    }
+}
+
+impl fmt::Display for AanganError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AanganError::NotFound { entity, id } => write!(f, "{} {} not found", entity, id),
+            AanganError::Unauthorized(reason) => write!(f, "Unauthorized: {}", reason),
+            AanganError::InvalidState { expected, actual } => {
+                write!(f, "Invalid state: expected {}, found {}", expected, actual)
+            }
+            AanganError::Validation { field, reason } => {
+                write!(f, "Invalid {}: {}", field, reason)
+            }
+            AanganError::Conflict(reason) => write!(f, "Conflict: {}", reason),
+            AanganError::RateLimited { retry_after_secs } => {
+                write!(f, "Rate limited: retry after {} seconds", retry_after_secs)
+            }
+            AanganError::Ledger(reason) => write!(f, "Ledger error: {}", reason),
+        }
+    }
+}
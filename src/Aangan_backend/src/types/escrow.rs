@@ -0,0 +1,64 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum EscrowStatus {
+    Funded,
+    Released,
+    Refunded,
+    Disputed,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Escrow {
+    pub status: EscrowStatus,
+    pub amount: u64,
+    pub funding_block: u64,
+    pub settlement_block: Option<u64>,
+    pub funded_at: u64,
+    pub updated_at: u64,
+    /// Who funded the deposit. It stays theirs when the lease is assigned to a new tenant.
+    /// Escrows recorded before this was tracked were funded by the rental's tenant.
+    pub depositor: Option<Principal>,
+}
+
+impl Escrow {
+    pub fn new(depositor: Principal, amount: u64, funding_block: u64) -> Self {
+        let timestamp = crate::clock::now();
+        Self {
+            status: EscrowStatus::Funded,
+            amount,
+            funding_block,
+            settlement_block: None,
+            funded_at: timestamp,
+            updated_at: timestamp,
+            depositor: Some(depositor),
+        }
+    }
+
+    /// The principal the deposit is returned to, given the rental's current tenant.
+    pub fn depositor_or(&self, tenant: Principal) -> Principal {
+        self.depositor.unwrap_or(tenant)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.status == EscrowStatus::Funded || self.status == EscrowStatus::Disputed
+    }
+
+    pub fn settle(&mut self, status: EscrowStatus, block: u64) {
+        self.status = status;
+        self.settlement_block = Some(block);
+        self.updated_at = crate::clock::now();
+    }
+
+    /// Records that `amount` was paid out of the deposit, which otherwise stays in escrow.
+    pub fn deduct(&mut self, amount: u64) {
+        self.amount -= amount;
+        self.updated_at = crate::clock::now();
+    }
+
+    pub fn dispute(&mut self) {
+        self.status = EscrowStatus::Disputed;
+        self.updated_at = crate::clock::now();
+    }
+}
@@ -0,0 +1,71 @@
+use candid::{define_function, CandidType};
+use serde::Deserialize;
+use serde_bytes::ByteBuf;
+
+pub type HeaderField = (String, String);
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<HeaderField>,
+    pub body: ByteBuf,
+    pub certificate_version: Option<u16>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<HeaderField>,
+    pub body: ByteBuf,
+    pub streaming_strategy: Option<StreamingStrategy>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct StreamingCallbackToken {
+    pub asset_id: u64,
+    pub chunk_index: u32,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct StreamingCallbackHttpResponse {
+    pub body: ByteBuf,
+    pub token: Option<StreamingCallbackToken>,
+}
+
+define_function!(pub StreamingCallbackFunction : (StreamingCallbackToken) -> (StreamingCallbackHttpResponse) query);
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum StreamingStrategy {
+    Callback {
+        callback: StreamingCallbackFunction,
+        token: StreamingCallbackToken,
+    },
+}
+
+impl HttpResponse {
+    pub fn new(status_code: u16, headers: Vec<HeaderField>, body: Vec<u8>) -> Self {
+        Self {
+            status_code,
+            headers,
+            body: ByteBuf::from(body),
+            streaming_strategy: None,
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Self::new(
+            404,
+            vec![("Content-Type".to_string(), "text/plain".to_string())],
+            b"Not found".to_vec(),
+        )
+    }
+
+    pub fn bad_request(message: &str) -> Self {
+        Self::new(
+            400,
+            vec![("Content-Type".to_string(), "text/plain".to_string())],
+            message.as_bytes().to_vec(),
+        )
+    }
+}
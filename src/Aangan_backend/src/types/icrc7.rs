@@ -0,0 +1,8 @@
+use candid::CandidType;
+use serde::Deserialize;
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SupportedStandard {
+    pub name: String,
+    pub url: String,
+}
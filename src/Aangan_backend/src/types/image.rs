@@ -0,0 +1,80 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+
+use super::versioned::{VersionedRecord, LEGACY_VERSION};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct UploadSession {
+    pub id: u64,
+    pub owner: Principal,
+    pub content_type: String,
+    pub total_size: u64,
+    pub received_bytes: u64,
+    pub created_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ImageAsset {
+    pub id: u64,
+    pub owner: Principal,
+    pub content_type: String,
+    pub size: u64,
+    pub sha256: ByteBuf,
+    pub chunk_count: u32,
+    pub created_at: u64,
+}
+
+impl UploadSession {
+    pub fn new(
+        id: u64,
+        owner: Principal,
+        content_type: String,
+        total_size: u64,
+        created_at: u64,
+    ) -> Self {
+        Self {
+            id,
+            owner,
+            content_type,
+            total_size,
+            received_bytes: 0,
+            created_at,
+        }
+    }
+}
+
+impl ImageAsset {
+    pub fn from_upload(session: UploadSession, sha256: [u8; 32], chunk_count: u32) -> Self {
+        Self {
+            id: session.id,
+            owner: session.owner,
+            content_type: session.content_type,
+            size: session.total_size,
+            sha256: ByteBuf::from(sha256.to_vec()),
+            chunk_count,
+            created_at: crate::clock::now(),
+        }
+    }
+
+    /// Path under which the image is served by `http_request`.
+    pub fn url(id: u64) -> String {
+        format!("/images/{}", id)
+    }
+}
+
+impl VersionedRecord for UploadSession {
+    const VERSION: u8 = LEGACY_VERSION;
+
+    fn upgrade(version: u8, _payload: &[u8]) -> Result<Self, String> {
+        Err(format!("unknown UploadSession schema version {}", version))
+    }
+}
+
+impl VersionedRecord for ImageAsset {
+    const VERSION: u8 = LEGACY_VERSION;
+
+    fn upgrade(version: u8, _payload: &[u8]) -> Result<Self, String> {
+        Err(format!("unknown ImageAsset schema version {}", version))
+    }
+}
@@ -1,9 +1,39 @@
+pub mod acceptance;
+pub mod assignment;
+pub mod attestation;
+pub mod config;
+pub mod error;
+pub mod escrow;
+pub mod http;
+pub mod icrc7;
+pub mod image;
+pub mod moderation;
 pub mod nft;
+pub mod payment;
 pub mod property;
+pub mod renewal;
 pub mod rental;
+pub mod search;
+pub mod termination;
 pub mod user;
+pub mod versioned;
 
+pub use acceptance::*;
+pub use assignment::*;
+pub use attestation::*;
+pub use config::*;
+pub use error::*;
+pub use escrow::*;
+pub use http::*;
+pub use icrc7::*;
+pub use image::*;
+pub use moderation::*;
 pub use nft::*;
+pub use payment::*;
 pub use property::*;
+pub use renewal::*;
 pub use rental::*;
+pub use search::*;
+pub use termination::*;
 pub use user::*;
+pub use versioned::{Stored, VersionedRecord};
@@ -0,0 +1,39 @@
+use candid::{CandidType, Principal};
+use ic_stable_structures::Storable;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ModerationAction {
+    SuspendUser { user: Principal },
+    ReinstateUser { user: Principal },
+    DelistProperty { property_id: u64 },
+    RelistProperty { property_id: u64 },
+    CancelRental { rental_id: u64 },
+    VoidNFT { nft_id: u64 },
+    GrantAdmin { user: Principal },
+    RevokeAdmin { user: Principal },
+}
+
+/// One administrator action, as recorded in the append-only moderation log.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ModerationEntry {
+    pub id: u64,
+    pub admin: Principal,
+    pub action: ModerationAction,
+    pub reason: String,
+    pub timestamp: u64,
+}
+
+impl Storable for ModerationEntry {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(candid::encode_one(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::decode_one(&bytes).unwrap()
+    }
+
+    const BOUND: ic_stable_structures::storable::Bound =
+        ic_stable_structures::storable::Bound::Unbounded;
+}
@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 use ic_stable_structures::Storable;
 use std::borrow::Cow;
 
+use super::versioned::{VersionedRecord, LEGACY_VERSION};
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct NFTMetadata {
     pub id: u64,
@@ -14,6 +16,41 @@ pub struct NFTMetadata {
     pub image: String,
     pub attributes: Vec<NFTAttribute>,
     pub created_at: u64,
+    pub status: Option<NFTStatus>,
+    pub updated_at: Option<u64>,
+    /// The NFT of the lease term this one renewed.
+    pub predecessor_id: Option<u64>,
+    /// The NFT minted when this lease term was renewed.
+    pub successor_id: Option<u64>,
+}
+
+/// Lease state shown by the NFT. Cancelled and expired leases leave the token `Void`; a
+/// renewed lease leaves it `Superseded` by the successor NFT.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum NFTStatus {
+    Confirmed,
+    Active,
+    Completed,
+    Void,
+    Superseded,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum NFTEventKind {
+    Minted,
+    Activated,
+    Transferred { from: Principal, to: Principal },
+    Completed,
+    Voided,
+    Renewed { successor: u64 },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct NFTEvent {
+    pub nft_id: u64,
+    pub sequence: u32,
+    pub kind: NFTEventKind,
+    pub timestamp: u64,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -22,6 +59,8 @@ pub struct NFTAttribute {
     pub value: String,
 }
 
+const STATUS_TRAIT: &str = "Status";
+
 impl NFTMetadata {
     pub fn new(
         id: u64,
@@ -35,7 +74,7 @@ impl NFTMetadata {
         start_date: u64,
         end_date: u64,
     ) -> Self {
-        let timestamp = ic_cdk::api::time();
+        let timestamp = crate::clock::now();
         Self {
             id,
             owner,
@@ -72,25 +111,95 @@ impl NFTMetadata {
                     trait_type: "End Date".to_string(),
                     value: end_date.to_string(),
                 },
+                NFTAttribute {
+                    trait_type: STATUS_TRAIT.to_string(),
+                    value: NFTStatus::Confirmed.as_str().to_string(),
+                },
             ],
             created_at: timestamp,
+            status: Some(NFTStatus::Confirmed),
+            updated_at: Some(timestamp),
+            predecessor_id: None,
+            successor_id: None,
+        }
+    }
+
+    pub fn set_owner(&mut self, owner: Principal) {
+        self.owner = owner;
+        self.updated_at = Some(crate::clock::now());
+    }
+
+    pub fn set_predecessor(&mut self, predecessor_id: u64) {
+        self.predecessor_id = Some(predecessor_id);
+        self.updated_at = Some(crate::clock::now());
+    }
+
+    /// Marks the NFT as superseded by the one minted for the renewed lease term.
+    pub fn supersede(&mut self, successor_id: u64) {
+        self.successor_id = Some(successor_id);
+        self.set_status(NFTStatus::Superseded);
+    }
+
+    /// Updates the status and its `Status` attribute, which wallets display.
+    pub fn set_status(&mut self, status: NFTStatus) {
+        let value = status.as_str().to_string();
+        match self
+            .attributes
+            .iter_mut()
+            .find(|attribute| attribute.trait_type == STATUS_TRAIT)
+        {
+            Some(attribute) => attribute.value = value,
+            None => self.attributes.push(NFTAttribute {
+                trait_type: STATUS_TRAIT.to_string(),
+                value,
+            }),
         }
+        self.status = Some(status);
+        self.updated_at = Some(crate::clock::now());
+    }
+
+    /// Whether the NFT stands for a lease that is confirmed or running.
+    pub fn is_live(&self) -> bool {
+        matches!(self.status, Some(NFTStatus::Confirmed) | Some(NFTStatus::Active))
+    }
+
+    /// Whether the NFT was replaced by a renewal or voided, so it no longer stands for the
+    /// lease.
+    pub fn is_retired(&self) -> bool {
+        matches!(
+            self.status,
+            Some(NFTStatus::Superseded) | Some(NFTStatus::Void)
+        )
     }
 }
 
-impl Storable for NFTMetadata {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(candid::encode_one(self).unwrap())
+impl NFTStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NFTStatus::Confirmed => "Confirmed",
+            NFTStatus::Active => "Active",
+            NFTStatus::Completed => "Completed",
+            NFTStatus::Void => "Void",
+            NFTStatus::Superseded => "Superseded",
+        }
     }
+}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        candid::decode_one(&bytes).unwrap()
+impl VersionedRecord for NFTMetadata {
+    // Every field added since the unversioned layout is optional
+    const VERSION: u8 = LEGACY_VERSION;
+
+    fn upgrade(version: u8, _payload: &[u8]) -> Result<Self, String> {
+        Err(format!("unknown NFTMetadata schema version {}", version))
+    }
+}
+
+impl VersionedRecord for NFTEvent {
+    const VERSION: u8 = LEGACY_VERSION;
+
+    fn upgrade(version: u8, _payload: &[u8]) -> Result<Self, String> {
+        Err(format!("unknown NFTEvent schema version {}", version))
     }
-    
-    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 2048, // 2KB should be enough for NFT metadata including attributes
-        is_fixed_size: false,
-    };
 }
 
 impl Storable for NFTAttribute {
@@ -0,0 +1,74 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use super::versioned::{VersionedRecord, LEGACY_VERSION};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum PaymentStatus {
+    Due,
+    Paid,
+    Late,
+    Waived,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RentPayment {
+    pub rental_id: u64,
+    pub period: u32,
+    pub due_date: u64,
+    pub amount: u64,
+    pub status: PaymentStatus,
+    pub paid_at: Option<u64>,
+    pub ledger_block: Option<u64>,
+    pub updated_at: u64,
+}
+
+impl RentPayment {
+    pub fn new(rental_id: u64, period: u32, due_date: u64, amount: u64) -> Self {
+        Self {
+            rental_id,
+            period,
+            due_date,
+            amount,
+            status: PaymentStatus::Due,
+            paid_at: None,
+            ledger_block: None,
+            updated_at: crate::clock::now(),
+        }
+    }
+
+    pub fn is_outstanding(&self) -> bool {
+        self.status == PaymentStatus::Due || self.status == PaymentStatus::Late
+    }
+
+    /// Flags an unpaid installment as late once its due date has passed.
+    pub fn refresh_status(&mut self, now: u64) -> bool {
+        if self.status == PaymentStatus::Due && now > self.due_date {
+            self.status = PaymentStatus::Late;
+            self.updated_at = now;
+            return true;
+        }
+        false
+    }
+
+    pub fn mark_paid(&mut self, ledger_block: u64) {
+        let timestamp = crate::clock::now();
+        self.status = PaymentStatus::Paid;
+        self.paid_at = Some(timestamp);
+        self.ledger_block = Some(ledger_block);
+        self.updated_at = timestamp;
+    }
+
+    pub fn waive(&mut self) {
+        self.status = PaymentStatus::Waived;
+        self.updated_at = crate::clock::now();
+    }
+}
+
+impl VersionedRecord for RentPayment {
+    const VERSION: u8 = LEGACY_VERSION;
+
+    fn upgrade(version: u8, _payload: &[u8]) -> Result<Self, String> {
+        Err(format!("unknown RentPayment schema version {}", version))
+    }
+}
@@ -3,6 +3,9 @@ use serde::{Deserialize, Serialize};
 use ic_stable_structures::Storable;
 use std::borrow::Cow;
 
+use super::versioned::{VersionedRecord, LEGACY_VERSION};
+use super::AanganError;
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct Property {
     pub id: u64,
@@ -16,14 +19,49 @@ pub struct Property {
     pub bedrooms: u32,
     pub bathrooms: u32,
     pub area_sqft: u32,
-    pub images: Vec<String>, // URLs or base64 encoded images
+    pub images: Vec<String>, // Image asset ids from the image store
     pub amenities: Vec<String>,
+    pub location: Option<GeoPoint>,
+    /// Whether the landlord accepts applications. Occupancy is tracked by the calendar.
     pub is_available: bool,
+    /// Set when an administrator takes the listing down. A delisted property stays
+    /// unavailable.
+    pub delisted_at: Option<u64>,
     pub created_at: u64,
     pub updated_at: u64,
 }
 
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct GeoPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl GeoPoint {
+    pub fn validate(&self) -> Result<(), AanganError> {
+        if !(-90.0..=90.0).contains(&self.latitude) {
+            return Err(AanganError::validation(
+                "latitude",
+                "must be between -90 and 90",
+            ));
+        }
+        if !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(AanganError::validation(
+                "longitude",
+                "must be between -180 and 180",
+            ));
+        }
+        Ok(())
+    }
+}
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct NearbyProperty {
+    pub property: Property,
+    pub distance_m: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum PropertyType {
     Apartment,
     House,
@@ -49,7 +87,7 @@ impl Property {
         images: Vec<String>,
         amenities: Vec<String>,
     ) -> Self {
-        let timestamp = ic_cdk::api::time();
+        let timestamp = crate::clock::now();
         Self {
             id,
             owner,
@@ -64,31 +102,55 @@ impl Property {
             area_sqft,
             images,
             amenities,
+            location: None,
             is_available: true,
+            delisted_at: None,
             created_at: timestamp,
             updated_at: timestamp,
         }
     }
 
+    pub fn set_location(&mut self, location: Option<GeoPoint>) {
+        self.location = location;
+        self.updated_at = crate::clock::now();
+    }
+
+    pub fn set_images(&mut self, images: Vec<String>) {
+        self.images = images;
+        self.updated_at = crate::clock::now();
+    }
+
     pub fn update_availability(&mut self, available: bool) {
-        self.is_available = available;
-        self.updated_at = ic_cdk::api::time();
+        self.is_available = available && !self.is_delisted();
+        self.updated_at = crate::clock::now();
     }
-}
 
-impl Storable for Property {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(candid::encode_one(self).unwrap())
+    pub fn is_delisted(&self) -> bool {
+        self.delisted_at.is_some()
     }
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        candid::decode_one(&bytes).unwrap()
+    pub fn delist(&mut self) {
+        let now = crate::clock::now();
+        self.delisted_at = Some(now);
+        self.is_available = false;
+        self.updated_at = now;
+    }
+
+    /// Puts a delisted property back on the market. It stays unavailable until the owner
+    /// opens it for applications again.
+    pub fn relist(&mut self) {
+        self.delisted_at = None;
+        self.updated_at = crate::clock::now();
+    }
+}
+
+impl VersionedRecord for Property {
+    // Every field added since the unversioned layout is optional
+    const VERSION: u8 = LEGACY_VERSION;
+
+    fn upgrade(version: u8, _payload: &[u8]) -> Result<Self, String> {
+        Err(format!("unknown Property schema version {}", version))
     }
-    
-    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 4096, // 4KB should be enough for property data including images and amenities
-        is_fixed_size: false,
-    };
 }
 
 impl Storable for PropertyType {
@@ -0,0 +1,108 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+
+use super::versioned::{VersionedRecord, LEGACY_VERSION};
+use super::Acceptance;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RenewalStatus {
+    Proposed,
+    Accepted,
+    Rejected,
+    Withdrawn,
+}
+
+/// A proposal by the landlord or the tenant to extend a lease, possibly at a new rent.
+/// Accepted renewals form the amendment chain of the agreement: each records the terms it
+/// replaced and the NFT it superseded.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LeaseRenewal {
+    pub rental_id: u64,
+    pub sequence: u32,
+    pub proposed_by: Principal,
+    pub counterparty: Principal,
+    pub previous_end_date: u64,
+    pub new_end_date: u64,
+    pub previous_rent_amount: u64,
+    pub new_rent_amount: u64,
+    /// Terms hash of the amended agreement. Proposing signs it; accepting must name it.
+    pub terms_hash: ByteBuf,
+    pub predecessor_nft_id: Option<u64>,
+    pub successor_nft_id: Option<u64>,
+    pub status: RenewalStatus,
+    pub proposed_at: u64,
+    pub updated_at: u64,
+}
+
+impl LeaseRenewal {
+    pub fn new(
+        rental_id: u64,
+        sequence: u32,
+        proposed_by: Principal,
+        counterparty: Principal,
+        previous_end_date: u64,
+        new_end_date: u64,
+        previous_rent_amount: u64,
+        new_rent_amount: u64,
+        terms_hash: [u8; 32],
+    ) -> Self {
+        let timestamp = crate::clock::now();
+        Self {
+            rental_id,
+            sequence,
+            proposed_by,
+            counterparty,
+            previous_end_date,
+            new_end_date,
+            previous_rent_amount,
+            new_rent_amount,
+            terms_hash: ByteBuf::from(terms_hash.to_vec()),
+            predecessor_nft_id: None,
+            successor_nft_id: None,
+            status: RenewalStatus::Proposed,
+            proposed_at: timestamp,
+            updated_at: timestamp,
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.status == RenewalStatus::Proposed
+    }
+
+    /// Both parties' acceptances of the amended terms: the proposer's, made by proposing,
+    /// and the counterparty's, made now.
+    pub fn acceptances(&self) -> Vec<Acceptance> {
+        vec![
+            Acceptance {
+                signer: self.proposed_by,
+                terms_hash: self.terms_hash.clone(),
+                accepted_at: self.proposed_at,
+            },
+            Acceptance {
+                signer: self.counterparty,
+                terms_hash: self.terms_hash.clone(),
+                accepted_at: crate::clock::now(),
+            },
+        ]
+    }
+
+    pub fn accept(&mut self, predecessor_nft_id: Option<u64>, successor_nft_id: u64) {
+        self.predecessor_nft_id = predecessor_nft_id;
+        self.successor_nft_id = Some(successor_nft_id);
+        self.resolve(RenewalStatus::Accepted);
+    }
+
+    pub fn resolve(&mut self, status: RenewalStatus) {
+        self.status = status;
+        self.updated_at = crate::clock::now();
+    }
+}
+
+impl VersionedRecord for LeaseRenewal {
+    const VERSION: u8 = LEGACY_VERSION;
+
+    fn upgrade(version: u8, _payload: &[u8]) -> Result<Self, String> {
+        Err(format!("unknown LeaseRenewal schema version {}", version))
+    }
+}
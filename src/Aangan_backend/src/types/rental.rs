@@ -3,6 +3,9 @@ use serde::{Deserialize, Serialize};
 use ic_stable_structures::Storable;
 use std::borrow::Cow;
 
+use super::versioned::{VersionedRecord, LEGACY_VERSION};
+use super::{Acceptance, Escrow, LeaseTerms, Termination, TerminationTerms};
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct RentalAgreement {
     pub id: u64,
@@ -15,6 +18,13 @@ pub struct RentalAgreement {
     pub rent_amount: u64,
     pub deposit_amount: u64,
     pub nft_id: Option<u64>,
+    pub escrow: Option<Escrow>,
+    /// `None` means the default terms.
+    pub termination_terms: Option<TerminationTerms>,
+    pub termination: Option<Termination>,
+    pub terms: Option<LeaseTerms>,
+    /// Signatures of the current terms. Changing the terms clears them.
+    pub acceptances: Option<Vec<Acceptance>>,
     pub created_at: u64,
     pub updated_at: u64,
 }
@@ -24,8 +34,26 @@ pub enum RentalStatus {
     Requested,
     Confirmed,
     Active,
+    /// Ending early: still occupied until the termination's effective date.
+    Terminating,
     Completed,
     Cancelled,
+    Expired,
+    /// Another application for the same property was accepted.
+    Rejected,
+}
+
+/// A half-open interval `[start, end)` of nanosecond timestamps.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct DateRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl DateRange {
+    pub fn overlaps(&self, other: &DateRange) -> bool {
+        self.start < other.end && other.start < self.end
+    }
 }
 
 impl RentalAgreement {
@@ -39,7 +67,7 @@ impl RentalAgreement {
         rent_amount: u64,
         deposit_amount: u64,
     ) -> Self {
-        let timestamp = ic_cdk::api::time();
+        let timestamp = crate::clock::now();
         Self {
             id,
             property_id,
@@ -51,6 +79,11 @@ impl RentalAgreement {
             rent_amount,
             deposit_amount,
             nft_id: None,
+            escrow: None,
+            termination_terms: None,
+            termination: None,
+            terms: None,
+            acceptances: None,
             created_at: timestamp,
             updated_at: timestamp,
         }
@@ -59,33 +92,125 @@ impl RentalAgreement {
     pub fn confirm(&mut self, nft_id: u64) {
         self.status = RentalStatus::Confirmed;
         self.nft_id = Some(nft_id);
-        self.updated_at = ic_cdk::api::time();
+        self.updated_at = crate::clock::now();
+    }
+
+    pub fn set_escrow(&mut self, escrow: Escrow) {
+        self.escrow = Some(escrow);
+        self.updated_at = crate::clock::now();
+    }
+
+    pub fn set_tenant(&mut self, tenant: Principal) {
+        self.tenant = tenant;
+        self.updated_at = crate::clock::now();
+    }
+
+    /// Applies the terms of a renewal. The acceptances of the old terms no longer apply.
+    pub fn amend(&mut self, end_date: u64, rent_amount: u64) {
+        self.end_date = end_date;
+        self.rent_amount = rent_amount;
+        self.acceptances = None;
+        self.updated_at = crate::clock::now();
+    }
+
+    pub fn set_nft(&mut self, nft_id: u64) {
+        self.nft_id = Some(nft_id);
+        self.updated_at = crate::clock::now();
     }
 
     pub fn activate(&mut self) {
         self.status = RentalStatus::Active;
-        self.updated_at = ic_cdk::api::time();
+        self.updated_at = crate::clock::now();
+    }
+
+    pub fn set_termination_terms(&mut self, terms: TerminationTerms) {
+        self.termination_terms = Some(terms);
+        self.acceptances = None;
+        self.updated_at = crate::clock::now();
+    }
+
+    pub fn set_terms(&mut self, terms: LeaseTerms) {
+        self.terms = Some(terms);
+        self.acceptances = None;
+        self.updated_at = crate::clock::now();
+    }
+
+    /// Records `acceptance`, replacing any earlier acceptance by the same signer.
+    pub fn record_acceptance(&mut self, acceptance: Acceptance) {
+        let acceptances = self.acceptances.get_or_insert_with(Vec::new);
+        acceptances.retain(|existing| existing.signer != acceptance.signer);
+        acceptances.push(acceptance);
+        self.updated_at = crate::clock::now();
+    }
+
+    pub fn is_signed_by(&self, signer: &Principal, terms_hash: &[u8]) -> bool {
+        self.acceptances.iter().flatten().any(|acceptance| {
+            acceptance.signer == *signer && acceptance.terms_hash.as_slice() == terms_hash
+        })
+    }
+
+    pub fn terminate(&mut self, termination: Termination) {
+        self.status = RentalStatus::Terminating;
+        self.termination = Some(termination);
+        self.updated_at = crate::clock::now();
     }
 
     pub fn cancel(&mut self) {
         self.status = RentalStatus::Cancelled;
-        self.updated_at = ic_cdk::api::time();
+        self.updated_at = crate::clock::now();
     }
-}
 
-impl Storable for RentalAgreement {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(candid::encode_one(self).unwrap())
+    pub fn complete(&mut self) {
+        self.status = RentalStatus::Completed;
+        self.updated_at = crate::clock::now();
     }
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        candid::decode_one(&bytes).unwrap()
+    pub fn expire(&mut self) {
+        self.status = RentalStatus::Expired;
+        self.updated_at = crate::clock::now();
+    }
+
+    pub fn reject(&mut self) {
+        self.status = RentalStatus::Rejected;
+        self.updated_at = crate::clock::now();
+    }
+
+    /// Whether the agreement occupies its property's calendar.
+    pub fn is_live(&self) -> bool {
+        matches!(
+            self.status,
+            RentalStatus::Confirmed | RentalStatus::Active | RentalStatus::Terminating
+        )
+    }
+
+    /// Whether the term can still be extended; a lease being terminated cannot.
+    pub fn is_renewable(&self) -> bool {
+        matches!(self.status, RentalStatus::Confirmed | RentalStatus::Active)
+    }
+
+    pub fn termination_terms(&self) -> TerminationTerms {
+        self.termination_terms.clone().unwrap_or_default()
+    }
+
+    /// The occupied period, which ends early once a termination has been requested.
+    pub fn period(&self) -> DateRange {
+        DateRange {
+            start: self.start_date,
+            end: self
+                .termination
+                .as_ref()
+                .map_or(self.end_date, |termination| termination.effective_date),
+        }
+    }
+}
+
+impl VersionedRecord for RentalAgreement {
+    // Every field added since the unversioned layout is optional
+    const VERSION: u8 = LEGACY_VERSION;
+
+    fn upgrade(version: u8, _payload: &[u8]) -> Result<Self, String> {
+        Err(format!("unknown RentalAgreement schema version {}", version))
     }
-    
-    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 1024, // 1KB should be enough for rental agreement data
-        is_fixed_size: false,
-    };
 }
 
 impl Storable for RentalStatus {
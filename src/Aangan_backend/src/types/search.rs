@@ -0,0 +1,123 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use super::{Property, PropertyType};
+
+pub const DEFAULT_PAGE_SIZE: u32 = 20;
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum SortField {
+    Rent,
+    Area,
+    CreatedAt,
+}
+
+impl SortField {
+    pub const ALL: [SortField; 3] = [SortField::Rent, SortField::Area, SortField::CreatedAt];
+
+    pub fn value_of(&self, property: &Property) -> u64 {
+        match self {
+            SortField::Rent => property.rent_amount,
+            SortField::Area => property.area_sqft as u64,
+            SortField::CreatedAt => property.created_at,
+        }
+    }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+/// Position after the last item of a page: the sort value and id of that item.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SearchCursor {
+    pub sort_value: u64,
+    pub id: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SearchQuery {
+    pub property_type: Option<PropertyType>,
+    pub min_rent: Option<u64>,
+    pub max_rent: Option<u64>,
+    pub min_deposit: Option<u64>,
+    pub max_deposit: Option<u64>,
+    pub min_bedrooms: Option<u32>,
+    pub min_bathrooms: Option<u32>,
+    pub min_area_sqft: Option<u32>,
+    pub max_area_sqft: Option<u32>,
+    pub amenities: Vec<String>,
+    pub available_only: Option<bool>,
+    pub sort_by: Option<SortField>,
+    pub order: Option<SortOrder>,
+    pub cursor: Option<SearchCursor>,
+    pub limit: Option<u32>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SearchPage {
+    pub items: Vec<Property>,
+    pub next_cursor: Option<SearchCursor>,
+}
+
+fn in_range<T: PartialOrd>(value: T, min: Option<T>, max: Option<T>) -> bool {
+    min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
+}
+
+impl SearchQuery {
+    pub fn matches(&self, property: &Property) -> bool {
+        if let Some(property_type) = &self.property_type {
+            if property.property_type != *property_type {
+                return false;
+            }
+        }
+
+        if self.available_only.unwrap_or(false) && !property.is_available {
+            return false;
+        }
+
+        in_range(property.rent_amount, self.min_rent, self.max_rent)
+            && in_range(property.deposit_amount, self.min_deposit, self.max_deposit)
+            && in_range(property.bedrooms, self.min_bedrooms, None)
+            && in_range(property.bathrooms, self.min_bathrooms, None)
+            && in_range(property.area_sqft, self.min_area_sqft, self.max_area_sqft)
+            && self.amenities.iter().all(|wanted| {
+                property
+                    .amenities
+                    .iter()
+                    .any(|amenity| amenity.eq_ignore_ascii_case(wanted))
+            })
+    }
+
+    pub fn sort_field(&self) -> SortField {
+        self.sort_by.clone().unwrap_or(SortField::CreatedAt)
+    }
+
+    /// Inclusive bounds the filters place on the sort value.
+    pub fn sort_value_range(&self) -> (u64, u64) {
+        match self.sort_field() {
+            SortField::Rent => (
+                self.min_rent.unwrap_or(0),
+                self.max_rent.unwrap_or(u64::MAX),
+            ),
+            SortField::Area => (
+                self.min_area_sqft.map_or(0, u64::from),
+                self.max_area_sqft.map_or(u64::MAX, u64::from),
+            ),
+            SortField::CreatedAt => (0, u64::MAX),
+        }
+    }
+
+    pub fn is_descending(&self) -> bool {
+        self.order == Some(SortOrder::Descending)
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE) as usize
+    }
+}
@@ -0,0 +1,78 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+/// Length of one day in nanoseconds.
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// What the party ending a lease early owes the other party.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum TerminationPenalty {
+    None,
+    Fixed(u64),
+    MonthsOfRent(u32),
+}
+
+/// Early-termination terms of an agreement. Agreements without terms use the default:
+/// 30 days notice and no penalty.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TerminationTerms {
+    pub notice_period_days: u32,
+    pub penalty: TerminationPenalty,
+}
+
+impl Default for TerminationTerms {
+    fn default() -> Self {
+        Self {
+            notice_period_days: 30,
+            penalty: TerminationPenalty::None,
+        }
+    }
+}
+
+impl TerminationTerms {
+    pub fn notice_period_nanos(&self) -> u64 {
+        DAY_NANOS.saturating_mul(self.notice_period_days as u64)
+    }
+
+    pub fn penalty_amount(&self, rent_amount: u64) -> u64 {
+        match self.penalty {
+            TerminationPenalty::None => 0,
+            TerminationPenalty::Fixed(amount) => amount,
+            TerminationPenalty::MonthsOfRent(months) => rent_amount.saturating_mul(months as u64),
+        }
+    }
+}
+
+/// A notice to end an Active lease before `end_date`. The lease stays occupied until
+/// `effective_date`, when it completes.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Termination {
+    pub requested_by: Principal,
+    pub requested_at: u64,
+    pub effective_date: u64,
+    pub penalty_amount: u64,
+    pub penalty_block: Option<u64>,
+    pub penalty_paid_at: Option<u64>,
+}
+
+impl Termination {
+    pub fn new(requested_by: Principal, effective_date: u64, penalty_amount: u64) -> Self {
+        Self {
+            requested_by,
+            requested_at: crate::clock::now(),
+            effective_date,
+            penalty_amount,
+            penalty_block: None,
+            penalty_paid_at: None,
+        }
+    }
+
+    pub fn is_penalty_outstanding(&self) -> bool {
+        self.penalty_amount > 0 && self.penalty_block.is_none()
+    }
+
+    pub fn mark_penalty_paid(&mut self, block: u64) {
+        self.penalty_block = Some(block);
+        self.penalty_paid_at = Some(crate::clock::now());
+    }
+}
@@ -3,19 +3,71 @@ use ic_stable_structures::Storable;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 
+use super::versioned::{VersionedRecord, LEGACY_VERSION};
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum Role {
     Landlord,
     Tenant,
 }
 
+/// Who may see a contact field besides the user and administrators.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum Visibility {
+    Private,
+    /// Landlords and tenants sharing a Confirmed or Active rental with the user.
+    #[default]
+    Counterparties,
+    Public,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ProfileVisibility {
+    pub email: Visibility,
+    pub phone: Visibility,
+}
+
+/// The caller's relationship to the user whose profile is being viewed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Viewer {
+    /// The user themselves or an administrator.
+    Owner,
+    Counterparty,
+    Public,
+}
+
+impl Visibility {
+    pub fn visible_to(self, viewer: Viewer) -> bool {
+        match viewer {
+            Viewer::Owner => true,
+            Viewer::Counterparty => self != Visibility::Private,
+            Viewer::Public => self == Visibility::Public,
+        }
+    }
+}
+
+/// A user profile as seen by someone else. Contact fields are `None` unless the user's
+/// visibility settings reveal them to the viewer.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PublicUser {
+    pub user_principal: Principal,
+    pub roles: Vec<Role>,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub created_at: u64,
+}
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct User {
     pub user_principal: Principal,
-    pub role: Role,
+    pub roles: Vec<Role>,
     pub name: Option<String>,
     pub email: Option<String>,
     pub phone: Option<String>,
+    pub suspended_at: Option<u64>,
+    /// `None` until the user changes the defaults.
+    pub visibility: Option<ProfileVisibility>,
     pub created_at: u64,
     pub updated_at: u64,
 }
@@ -28,13 +80,15 @@ impl User {
         email: Option<String>,
         phone: Option<String>,
     ) -> Self {
-        let now = ic_cdk::api::time();
+        let now = crate::clock::now();
         Self {
             user_principal,
-            role,
+            roles: vec![role],
             name,
             email,
             phone,
+            suspended_at: None,
+            visibility: None,
             created_at: now,
             updated_at: now,
         }
@@ -55,24 +109,105 @@ impl User {
         if let Some(p) = phone {
             self.phone = Some(p);
         }
-        self.updated_at = ic_cdk::api::time();
+        self.updated_at = crate::clock::now();
     }
-}
 
-impl Storable for User {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(candid::encode_one(self).unwrap())
+    pub fn set_visibility(&mut self, visibility: ProfileVisibility) {
+        self.visibility = Some(visibility);
+        self.updated_at = crate::clock::now();
     }
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        candid::decode_one(&bytes).unwrap()
+    pub fn view(&self, viewer: Viewer) -> PublicUser {
+        let visibility = self.visibility.clone().unwrap_or_default();
+        PublicUser {
+            user_principal: self.user_principal,
+            roles: self.roles.clone(),
+            name: self.name.clone(),
+            email: self.email.clone().filter(|_| visibility.email.visible_to(viewer)),
+            phone: self.phone.clone().filter(|_| visibility.phone.visible_to(viewer)),
+            created_at: self.created_at,
+        }
     }
 
-    const BOUND: ic_stable_structures::storable::Bound =
-        ic_stable_structures::storable::Bound::Bounded {
-            max_size: 1024,
-            is_fixed_size: false,
-        };
+    pub fn has_role(&self, role: &Role) -> bool {
+        self.roles.contains(role)
+    }
+
+    /// Grants `role`. Returns false if the user already held it.
+    pub fn add_role(&mut self, role: Role) -> bool {
+        if self.has_role(&role) {
+            return false;
+        }
+        self.roles.push(role);
+        self.updated_at = crate::clock::now();
+        true
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended_at.is_some()
+    }
+
+    pub fn suspend(&mut self) {
+        let now = crate::clock::now();
+        self.suspended_at = Some(now);
+        self.updated_at = now;
+    }
+
+    pub fn reinstate(&mut self) {
+        self.suspended_at = None;
+        self.updated_at = crate::clock::now();
+    }
+
+    /// Revokes `role`. Returns false if the user did not hold it.
+    pub fn remove_role(&mut self, role: &Role) -> bool {
+        if !self.has_role(role) {
+            return false;
+        }
+        self.roles.retain(|held| held != role);
+        self.updated_at = crate::clock::now();
+        true
+    }
+}
+
+/// Layout of schema version 1, which held a single role.
+#[derive(CandidType, Deserialize)]
+struct UserV1 {
+    user_principal: Principal,
+    role: Role,
+    name: Option<String>,
+    email: Option<String>,
+    phone: Option<String>,
+    created_at: u64,
+    updated_at: u64,
+}
+
+impl From<UserV1> for User {
+    fn from(user: UserV1) -> Self {
+        Self {
+            user_principal: user.user_principal,
+            roles: vec![user.role],
+            name: user.name,
+            email: user.email,
+            phone: user.phone,
+            suspended_at: None,
+            visibility: None,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+        }
+    }
+}
+
+impl VersionedRecord for User {
+    const VERSION: u8 = 2;
+
+    fn upgrade(version: u8, payload: &[u8]) -> Result<Self, String> {
+        match version {
+            LEGACY_VERSION => candid::decode_one::<UserV1>(payload)
+                .map(User::from)
+                .map_err(|e| e.to_string()),
+            _ => Err(format!("unknown User schema version {}", version)),
+        }
+    }
 }
 
 impl Storable for Role {
@@ -90,3 +225,113 @@ impl Storable for Role {
             is_fixed_size: false,
         };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tenant() -> User {
+        User::new(
+            Principal::from_slice(&[3]),
+            Role::Tenant,
+            Some("Asha".to_string()),
+            Some("asha@example.com".to_string()),
+            Some("+91 98450 00000".to_string()),
+        )
+    }
+
+    #[test]
+    fn single_role_users_are_converted() {
+        use crate::types::Stored;
+
+        let legacy = UserV1 {
+            user_principal: Principal::from_slice(&[2]),
+            role: Role::Landlord,
+            name: Some("Ravi".to_string()),
+            email: None,
+            phone: Some("+91 98450 11111".to_string()),
+            created_at: 10,
+            updated_at: 20,
+        };
+        let bytes = candid::encode_one(legacy).unwrap();
+
+        let stored = Stored::<User>::from_bytes(Cow::Owned(bytes));
+        assert!(stored.is_outdated());
+        let user = stored.into_value();
+        assert_eq!(user.user_principal, Principal::from_slice(&[2]));
+        assert_eq!(user.roles, vec![Role::Landlord]);
+        assert_eq!(user.name.as_deref(), Some("Ravi"));
+        assert_eq!(user.phone.as_deref(), Some("+91 98450 11111"));
+        assert_eq!(user.suspended_at, None);
+        assert!(user.visibility.is_none());
+        assert_eq!((user.created_at, user.updated_at), (10, 20));
+    }
+
+    #[test]
+    fn add_role_grants_each_role_once() {
+        let mut user = tenant();
+
+        assert!(user.add_role(Role::Landlord));
+        assert!(!user.add_role(Role::Landlord));
+        assert!(!user.add_role(Role::Tenant));
+        assert_eq!(user.roles, vec![Role::Tenant, Role::Landlord]);
+    }
+
+    #[test]
+    fn remove_role_revokes_only_held_roles() {
+        let mut user = tenant();
+
+        assert!(!user.remove_role(&Role::Landlord));
+        user.add_role(Role::Landlord);
+        assert!(user.remove_role(&Role::Tenant));
+        assert!(!user.has_role(&Role::Tenant));
+        assert_eq!(user.roles, vec![Role::Landlord]);
+    }
+
+    #[test]
+    fn default_visibility_shows_contacts_to_counterparties_only() {
+        let user = tenant();
+
+        let public = user.view(Viewer::Public);
+        assert_eq!(public.name.as_deref(), Some("Asha"));
+        assert_eq!(public.email, None);
+        assert_eq!(public.phone, None);
+
+        let counterparty = user.view(Viewer::Counterparty);
+        assert_eq!(counterparty.email, user.email);
+        assert_eq!(counterparty.phone, user.phone);
+    }
+
+    #[test]
+    fn visibility_is_applied_per_field() {
+        let mut user = tenant();
+        user.set_visibility(ProfileVisibility {
+            email: Visibility::Public,
+            phone: Visibility::Private,
+        });
+
+        for viewer in [Viewer::Public, Viewer::Counterparty] {
+            let view = user.view(viewer);
+            assert_eq!(view.email, user.email);
+            assert_eq!(view.phone, None);
+        }
+
+        let owner = user.view(Viewer::Owner);
+        assert_eq!(owner.email, user.email);
+        assert_eq!(owner.phone, user.phone);
+    }
+
+    #[test]
+    fn private_fields_are_hidden_from_counterparties() {
+        let mut user = tenant();
+        user.set_visibility(ProfileVisibility {
+            email: Visibility::Private,
+            phone: Visibility::Counterparties,
+        });
+
+        let counterparty = user.view(Viewer::Counterparty);
+        assert_eq!(counterparty.email, None);
+        assert_eq!(counterparty.phone, user.phone);
+        assert_eq!(user.view(Viewer::Public).phone, None);
+    }
+}
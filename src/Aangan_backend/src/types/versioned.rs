@@ -0,0 +1,211 @@
+//! Versioned stable encoding for records whose schema evolves.
+//!
+//! A record is stored as a one-byte schema version followed by its candid encoding.
+//! Records written before versioning was introduced are bare candid blobs, recognisable by
+//! the `DIDL` magic, and are read as version 1.
+//!
+//! Candid decodes a missing `Option` field as `None`, so adding one needs no new version.
+//! Bump `VERSION` only for changes that need a converter, such as a renamed, retyped or
+//! removed field, and keep the old layout around for [`VersionedRecord::upgrade`].
+
+use candid::CandidType;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+const CANDID_MAGIC: &[u8] = b"DIDL";
+
+/// Schema version of records written without a version header.
+pub const LEGACY_VERSION: u8 = 1;
+
+pub trait VersionedRecord: CandidType + DeserializeOwned {
+    /// Schema version written by this release.
+    const VERSION: u8;
+
+    /// Decodes a payload written under an older schema `version`.
+    fn upgrade(version: u8, payload: &[u8]) -> Result<Self, String>;
+}
+
+/// Stable map value wrapping a [`VersionedRecord`].
+#[derive(Clone, Debug)]
+pub struct Stored<T> {
+    version: u8,
+    value: T,
+}
+
+impl<T: VersionedRecord> Stored<T> {
+    pub fn new(value: T) -> Self {
+        Stored {
+            version: T::VERSION,
+            value,
+        }
+    }
+
+    /// Whether the record was decoded from an older schema and should be rewritten.
+    pub fn is_outdated(&self) -> bool {
+        self.version < T::VERSION
+    }
+
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+fn decode<T: VersionedRecord>(version: u8, payload: &[u8]) -> Result<T, String> {
+    match version.cmp(&T::VERSION) {
+        Ordering::Equal => candid::decode_one(payload).map_err(|e| e.to_string()),
+        Ordering::Less => T::upgrade(version, payload),
+        Ordering::Greater => Err(format!(
+            "schema version {} is newer than supported version {}",
+            version,
+            T::VERSION
+        )),
+    }
+}
+
+impl<T: VersionedRecord> Storable for Stored<T> {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = vec![T::VERSION];
+        bytes.extend(candid::encode_one(&self.value).expect("Failed to encode record"));
+        Cow::Owned(bytes)
+    }
+
+    /// Traps if the record cannot be decoded, so a bad converter fails loudly (and rolls
+    /// back the upgrade that runs the migration) instead of making records disappear.
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let decoded = if bytes.starts_with(CANDID_MAGIC) {
+            decode(LEGACY_VERSION, &bytes).map(|value| (LEGACY_VERSION, value))
+        } else {
+            match bytes.split_first() {
+                Some((&version, payload)) => decode(version, payload).map(|value| (version, value)),
+                None => Err("empty record".to_string()),
+            }
+        };
+        match decoded {
+            Ok((version, value)) => Stored { version, value },
+            Err(error) => ic_cdk::trap(&format!(
+                "undecodable {} record: {}",
+                std::any::type_name::<T>(),
+                error
+            )),
+        }
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Deserialize;
+
+    /// Layout of version 1, which had a single tag.
+    #[derive(CandidType, Deserialize)]
+    struct SampleV1 {
+        id: u64,
+        tag: String,
+    }
+
+    #[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+    struct Sample {
+        id: u64,
+        tags: Vec<String>,
+        note: Option<String>,
+    }
+
+    impl VersionedRecord for Sample {
+        const VERSION: u8 = 2;
+
+        fn upgrade(version: u8, payload: &[u8]) -> Result<Self, String> {
+            match version {
+                LEGACY_VERSION => candid::decode_one::<SampleV1>(payload)
+                    .map(|sample| Sample {
+                        id: sample.id,
+                        tags: vec![sample.tag],
+                        note: None,
+                    })
+                    .map_err(|e| e.to_string()),
+                _ => Err(format!("unknown Sample schema version {}", version)),
+            }
+        }
+    }
+
+    fn v1() -> SampleV1 {
+        SampleV1 {
+            id: 7,
+            tag: "garden".to_string(),
+        }
+    }
+
+    fn upgraded() -> Sample {
+        Sample {
+            id: 7,
+            tags: vec!["garden".to_string()],
+            note: None,
+        }
+    }
+
+    #[test]
+    fn legacy_candid_blobs_are_read_as_version_1() {
+        let bytes = candid::encode_one(v1()).unwrap();
+        assert!(bytes.starts_with(CANDID_MAGIC));
+
+        let stored = Stored::<Sample>::from_bytes(Cow::Owned(bytes));
+        assert!(stored.is_outdated());
+        assert_eq!(stored.into_value(), upgraded());
+    }
+
+    #[test]
+    fn version_1_blobs_are_converted() {
+        let mut bytes = vec![LEGACY_VERSION];
+        bytes.extend(candid::encode_one(v1()).unwrap());
+
+        let stored = Stored::<Sample>::from_bytes(Cow::Owned(bytes));
+        assert!(stored.is_outdated());
+        assert_eq!(stored.into_value(), upgraded());
+    }
+
+    #[test]
+    fn current_records_round_trip() {
+        let sample = Sample {
+            id: 9,
+            tags: vec!["roof".to_string(), "lift".to_string()],
+            note: Some("corner flat".to_string()),
+        };
+
+        let bytes = Stored::new(sample.clone()).to_bytes().into_owned();
+        assert_eq!(bytes[0], Sample::VERSION);
+
+        let stored = Stored::<Sample>::from_bytes(Cow::Owned(bytes));
+        assert!(!stored.is_outdated());
+        assert_eq!(stored.into_value(), sample);
+    }
+
+    #[test]
+    fn outdated_records_are_rewritten_at_the_current_version() {
+        let stored = Stored::<Sample>::from_bytes(Cow::Owned(candid::encode_one(v1()).unwrap()));
+
+        let bytes = stored.to_bytes().into_owned();
+        assert_eq!(bytes[0], Sample::VERSION);
+        assert_eq!(
+            Stored::<Sample>::from_bytes(Cow::Owned(bytes)).into_value(),
+            upgraded()
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn undecodable_records_trap() {
+        Stored::<Sample>::from_bytes(Cow::Owned(vec![Sample::VERSION, 0xff, 0x00]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn records_from_a_newer_schema_trap() {
+        let mut bytes = vec![Sample::VERSION + 1];
+        bytes.extend(candid::encode_one(upgraded()).unwrap());
+        Stored::<Sample>::from_bytes(Cow::Owned(bytes));
+    }
+}
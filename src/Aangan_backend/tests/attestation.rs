@@ -0,0 +1,298 @@
+//! Lease attestations signed by the backend running in PocketIC and checked with the
+//! off-chain verifier from `aangan_attestation`.
+//!
+//! The tests need `AANGAN_BACKEND_WASM`, built with
+//! `cargo build --target wasm32-unknown-unknown --release -p aangan_backend`.
+//! They are ignored by default; run them with `cargo test --test attestation -- --ignored`.
+
+use aangan_attestation::{verify_lease_attestation, AttestedStatus, LeaseAttestation, VerifyError};
+use candid::{encode_one, CandidType, Deserialize, Principal};
+use ic_canister_sig_creation::extract_raw_root_pk_from_der;
+use pocket_ic::{query_candid_as, update_candid_as, PocketIc, PocketIcBuilder};
+use serde_bytes::ByteBuf;
+use std::path::PathBuf;
+
+const RENT: u64 = 25_000_000;
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+#[derive(CandidType)]
+enum Role {
+    Landlord,
+    Tenant,
+}
+
+#[derive(CandidType)]
+enum PropertyType {
+    Apartment,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+enum AanganError {
+    NotFound { entity: String, id: String },
+    Unauthorized(String),
+    InvalidState { expected: String, actual: String },
+    Validation { field: String, reason: String },
+    Conflict(String),
+    RateLimited { retry_after_secs: u64 },
+    Ledger(String),
+}
+
+#[derive(CandidType)]
+struct LeaseTerms {
+    document_hash: ByteBuf,
+    clauses: Vec<String>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct Property {
+    id: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct RentalAgreement {
+    id: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct PreparedLeaseAttestation {
+    message: ByteBuf,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct SignedLeaseAttestation {
+    attestation: LeaseAttestation,
+    message: ByteBuf,
+    signature: ByteBuf,
+}
+
+struct Env {
+    pic: PocketIc,
+    backend: Principal,
+    landlord: Principal,
+    tenant: Principal,
+}
+
+fn setup() -> Env {
+    let path = std::env::var_os("AANGAN_BACKEND_WASM")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            PathBuf::from("../../target/wasm32-unknown-unknown/release/aangan_backend.wasm")
+        });
+    let Ok(backend_wasm) = std::fs::read(path) else {
+        panic!("attestation tests need AANGAN_BACKEND_WASM");
+    };
+
+    // The NNS subnet provides the root key the signatures are certified under.
+    let pic = PocketIcBuilder::new()
+        .with_nns_subnet()
+        .with_application_subnet()
+        .build();
+    let backend = pic.create_canister();
+    pic.add_cycles(backend, 2_000_000_000_000);
+    pic.install_canister(backend, backend_wasm, encode_one(None::<()>).unwrap(), None);
+
+    Env {
+        pic,
+        backend,
+        landlord: Principal::from_slice(&[2]),
+        tenant: Principal::from_slice(&[3]),
+    }
+}
+
+impl Env {
+    fn register(&self, user: Principal, role: Role) {
+        let (result,): (Result<candid::Reserved, AanganError>,) = update_candid_as(
+            &self.pic,
+            self.backend,
+            user,
+            "create_user",
+            (role, None::<String>, None::<String>, None::<String>),
+        )
+        .unwrap();
+        result.unwrap();
+    }
+
+    fn call_rental(&self, sender: Principal, method: &str, rental_id: u64) -> RentalAgreement {
+        let (result,): (Result<RentalAgreement, AanganError>,) =
+            update_candid_as(&self.pic, self.backend, sender, method, (rental_id,)).unwrap();
+        result.unwrap()
+    }
+
+    /// Takes a lease without a deposit from application to Active, so no ledger is needed.
+    fn active_rental(&self) -> u64 {
+        self.register(self.landlord, Role::Landlord);
+        self.register(self.tenant, Role::Tenant);
+
+        let (property,): (Result<Property, AanganError>,) = update_candid_as(
+            &self.pic,
+            self.backend,
+            self.landlord,
+            "add_property",
+            (
+                "Flat".to_string(),
+                "Two rooms".to_string(),
+                "12 MG Road".to_string(),
+                RENT,
+                0u64,
+                PropertyType::Apartment,
+                2u32,
+                1u32,
+                800u32,
+                Vec::<String>::new(),
+                Vec::<String>::new(),
+            ),
+        )
+        .unwrap();
+
+        let start_date = self.pic.get_time().as_nanos_since_unix_epoch() + DAY_NANOS;
+        let end_date = start_date + 365 * DAY_NANOS;
+        let (rental,): (Result<RentalAgreement, AanganError>,) = update_candid_as(
+            &self.pic,
+            self.backend,
+            self.tenant,
+            "request_rental",
+            (property.unwrap().id, start_date, end_date),
+        )
+        .unwrap();
+        let rental_id = rental.unwrap().id;
+
+        let terms = LeaseTerms {
+            document_hash: ByteBuf::from(vec![7u8; 32]),
+            clauses: vec!["No pets".to_string()],
+        };
+        let (result,): (Result<RentalAgreement, AanganError>,) = update_candid_as(
+            &self.pic,
+            self.backend,
+            self.landlord,
+            "set_lease_terms",
+            (rental_id, terms),
+        )
+        .unwrap();
+        result.unwrap();
+
+        let (terms_hash,): (Result<ByteBuf, AanganError>,) = query_candid_as(
+            &self.pic,
+            self.backend,
+            self.tenant,
+            "get_terms_hash",
+            (rental_id,),
+        )
+        .unwrap();
+        let terms_hash = terms_hash.unwrap();
+        for signer in [self.landlord, self.tenant] {
+            let (result,): (Result<RentalAgreement, AanganError>,) = update_candid_as(
+                &self.pic,
+                self.backend,
+                signer,
+                "sign_lease",
+                (rental_id, terms_hash.clone()),
+            )
+            .unwrap();
+            result.unwrap();
+        }
+
+        self.call_rental(self.landlord, "accept_application", rental_id);
+        self.call_rental(self.landlord, "activate_rental", rental_id);
+        rental_id
+    }
+
+    fn attest(&self, rental_id: u64) -> SignedLeaseAttestation {
+        let (prepared,): (Result<PreparedLeaseAttestation, AanganError>,) = update_candid_as(
+            &self.pic,
+            self.backend,
+            self.tenant,
+            "prepare_lease_attestation",
+            (rental_id,),
+        )
+        .unwrap();
+        let message = prepared.unwrap().message;
+
+        let (signed,): (Result<SignedLeaseAttestation, AanganError>,) = query_candid_as(
+            &self.pic,
+            self.backend,
+            self.tenant,
+            "get_lease_attestation",
+            (rental_id, message),
+        )
+        .unwrap();
+        signed.unwrap()
+    }
+
+    fn root_key(&self) -> Vec<u8> {
+        let der = self
+            .pic
+            .root_key()
+            .expect("PocketIC instance has an NNS subnet");
+        extract_raw_root_pk_from_der(&der).unwrap()
+    }
+
+    fn now(&self) -> u64 {
+        self.pic.get_time().as_nanos_since_unix_epoch()
+    }
+}
+
+#[test]
+#[ignore = "needs AANGAN_BACKEND_WASM"]
+fn attestations_verify_against_the_root_key() {
+    let env = setup();
+    let rental_id = env.active_rental();
+    let signed = env.attest(rental_id);
+
+    let attestation = verify_lease_attestation(
+        &signed.message,
+        &signed.signature,
+        env.backend,
+        &env.root_key(),
+        env.now(),
+    )
+    .unwrap();
+
+    assert_eq!(attestation, signed.attestation);
+    assert_eq!(attestation.issuer, env.backend);
+    assert_eq!(attestation.rental_id, rental_id);
+    assert_eq!(attestation.tenant, env.tenant);
+    assert_eq!(attestation.landlord, env.landlord);
+    assert_eq!(attestation.status, AttestedStatus::Active);
+}
+
+#[test]
+#[ignore = "needs AANGAN_BACKEND_WASM"]
+fn tampered_attestations_are_rejected() {
+    let env = setup();
+    let rental_id = env.active_rental();
+    let signed = env.attest(rental_id);
+
+    let mut attestation = signed.attestation.clone();
+    attestation.rent_amount /= 2;
+    let tampered = attestation.encode();
+    assert_ne!(tampered, signed.message.as_slice());
+
+    let result = verify_lease_attestation(
+        &tampered,
+        &signed.signature,
+        env.backend,
+        &env.root_key(),
+        env.now(),
+    );
+
+    assert!(matches!(result, Err(VerifyError::InvalidSignature(_))));
+}
+
+#[test]
+#[ignore = "needs AANGAN_BACKEND_WASM"]
+fn attestations_are_rejected_for_another_canister() {
+    let env = setup();
+    let rental_id = env.active_rental();
+    let signed = env.attest(rental_id);
+    let other = env.pic.create_canister();
+
+    let result = verify_lease_attestation(
+        &signed.message,
+        &signed.signature,
+        other,
+        &env.root_key(),
+        env.now(),
+    );
+
+    assert!(matches!(result, Err(VerifyError::InvalidSignature(_))));
+}
@@ -126,7 +126,7 @@ const Header = ({ variant = 'app' }: HeaderProps) => {
               </Link>
               {isAuthenticated && user && (
                 <Link
-                  to={Object.keys(user.role)[0] === 'Tenant' ? '/tenant-dashboard' : '/landlord-dashboard'}
+                  to={user.roles.some((role: any) => 'Landlord' in role) ? '/landlord-dashboard' : '/tenant-dashboard'}
                   className="flex items-center px-4 py-3 text-gray-700 hover:text-white transition-all duration-300 font-bold rounded-lg hover:bg-gradient-to-r hover:from-purple-500 hover:to-blue-500"
                   onClick={() => setMobileMenuOpen(false)}
                 >
@@ -9,7 +9,7 @@ import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from '@
 import { Checkbox } from '@/components/ui/checkbox';
 import { ArrowLeft, Home, Upload, X, Plus } from 'lucide-react';
 import { useICP } from '@/contexts/ICPContext';
-import { PropertyService } from '@/services/PropertyService';
+import { ALLOWED_IMAGE_TYPES, PropertyService } from '@/services/PropertyService';
 import { toast } from 'sonner';
 import Header from '@/components/Header';
 
@@ -36,7 +36,8 @@ const AddProperty = () => {
     amenities: [] as string[]
   });
 
-  const [images, setImages] = useState<string[]>([]);
+  // Files are uploaded to the backend when the form is submitted
+  const [images, setImages] = useState<{ file: File; preview: string }[]>([]);
   const [isSubmitting, setIsSubmitting] = useState(false);
   const [errors, setErrors] = useState<Record<string, string>>({});
 
@@ -61,7 +62,7 @@ const AddProperty = () => {
 
   // Check if user is a landlord
   React.useEffect(() => {
-    if (user && Object.keys(user.role)[0] !== 'Landlord') {
+    if (user && !user.roles.some((role: any) => 'Landlord' in role)) {
       navigate('/tenant-dashboard');
       toast.error('Only landlords can add properties');
     }
@@ -124,12 +125,18 @@ const AddProperty = () => {
   const handleImageUpload = (event: React.ChangeEvent<HTMLInputElement>) => {
     const files = event.target.files;
     if (files) {
-      // In real app, you would upload to a storage service like AWS S3 or IPFS
-      const newImages = Array.from(files).map((file, index) =>
-        URL.createObjectURL(file) // Temporary URL for preview
-      );
+      const accepted = Array.from(files).filter(file => ALLOWED_IMAGE_TYPES.includes(file.type));
+      if (accepted.length < files.length) {
+        toast.error('Only JPEG, PNG, WebP, GIF and AVIF images are supported');
+      }
+      const newImages = accepted.map(file => ({
+        file,
+        preview: URL.createObjectURL(file) // Temporary URL for preview
+      }));
       setImages(prev => [...prev, ...newImages].slice(0, 10));
-      toast.success(`${files.length} image(s) uploaded successfully`);
+      if (accepted.length > 0) {
+        toast.success(`${accepted.length} image(s) added`);
+      }
       // Clear images error
       if (errors.images) {
         setErrors(prev => ({ ...prev, images: '' }));
@@ -156,8 +163,13 @@ const AddProperty = () => {
 
     setIsSubmitting(true);
 
+    const propertyService = new PropertyService(actor);
+    const imageIds: string[] = [];
+
     try {
-      const propertyService = new PropertyService(actor);
+      for (const image of images) {
+        imageIds.push(await propertyService.uploadImage(image.file));
+      }
 
       // Prepare the property data
       const propertyData = {
@@ -170,7 +182,7 @@ const AddProperty = () => {
         bedrooms: Number(formData.bedrooms),
         bathrooms: Number(formData.bathrooms),
         area_sqft: Number(formData.area),
-        images: images, // In production, these would be IPFS hashes or URLs
+        images: imageIds,
         amenities: formData.amenities
       };
 
@@ -180,6 +192,8 @@ const AddProperty = () => {
       navigate('/landlord-dashboard');
     } catch (error) {
       console.error('Error adding property:', error);
+      // Uploaded images are not listed anywhere, so they would only use up the quota
+      await Promise.allSettled(imageIds.map(imageId => propertyService.deleteImage(imageId)));
       toast.error('Failed to save property. Please try again.');
     } finally {
       setIsSubmitting(false);
@@ -440,7 +454,7 @@ const AddProperty = () => {
                 <input
                   type="file"
                   multiple
-                  accept="image/*"
+                  accept={ALLOWED_IMAGE_TYPES.join(',')}
                   onChange={handleImageUpload}
                   className="hidden"
                   id="image-upload"
@@ -461,7 +475,7 @@ const AddProperty = () => {
                   {images.map((image, index) => (
                     <div key={index} className="relative">
                       <img
-                        src={image}
+                        src={image.preview}
                         alt={`Property image ${index + 1}`}
                         className="w-full h-32 object-cover rounded-lg"
                       />
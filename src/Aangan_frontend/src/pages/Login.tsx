@@ -40,12 +40,12 @@ const Login = () => {
 
   React.useEffect(() => {
     if (isAuthenticated && user) {
-      // Redirect based on user role
-      const userRole = Object.keys(user.role)[0];
-      if (userRole === 'Tenant') {
-        navigate('/tenant-dashboard');
-      } else if (userRole === 'Landlord') {
+      // Redirect based on user roles, preferring the landlord view
+      const userRoles = user.roles.map((role: any) => Object.keys(role)[0]);
+      if (userRoles.includes('Landlord')) {
         navigate('/landlord-dashboard');
+      } else if (userRoles.includes('Tenant')) {
+        navigate('/tenant-dashboard');
       }
     }
   }, [isAuthenticated, user, navigate]);
@@ -7,7 +7,7 @@ import { Card, CardContent } from '@/components/ui/card';
 import { Badge } from '@/components/ui/badge';
 import { Search, Filter, MapPin, Sparkles, Shield } from 'lucide-react';
 import { useICP } from '@/contexts/ICPContext';
-import { PropertyService } from '@/services/PropertyService';
+import { PropertyService, imageUrl } from '@/services/PropertyService';
 import { toast } from 'sonner';
 
 const Marketplace = () => {
@@ -36,7 +36,7 @@ const Marketplace = () => {
       rent: Number(prop.rent_amount),
       bedrooms: prop.bedrooms,
       bathrooms: prop.bathrooms,
-      images: prop.images && prop.images.length > 0 ? prop.images : ['placeholder'],
+      images: prop.images && prop.images.length > 0 ? prop.images.map(imageUrl) : ['placeholder'],
       propertyType: Object.keys(prop.property_type)[0] || 'Apartment'
     };
   };
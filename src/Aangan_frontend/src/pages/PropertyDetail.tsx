@@ -23,7 +23,7 @@ import {
 } from 'lucide-react';
 import { Carousel, CarouselContent, CarouselItem, CarouselNext, CarouselPrevious } from '@/components/ui/carousel';
 import { useICP } from '@/contexts/ICPContext';
-import { PropertyService } from '@/services/PropertyService';
+import { PropertyService, imageUrl } from '@/services/PropertyService';
 import { toast } from 'sonner';
 
 const PropertyDetail = () => {
@@ -102,7 +102,7 @@ const PropertyDetail = () => {
       area: prop.area_sqft,
       furnished: 'Furnished', // This should come from property data
       parking: true, // This should come from amenities
-      images: prop.images && prop.images.length > 0 ? prop.images : [
+      images: prop.images && prop.images.length > 0 ? prop.images.map(imageUrl) : [
         'https://images.unsplash.com/photo-1564013799919-ab600027ffc6?w=800&h=600&auto=format&fit=crop',
         'https://images.unsplash.com/photo-1583608205776-bfd35f0d9f83?w=800&h=600&auto=format&fit=crop',
         'https://images.unsplash.com/photo-1593696140826-c58b021acf8b?w=800&h=600&auto=format&fit=crop'
@@ -10,13 +10,75 @@ export interface PropertyData {
     bedrooms: number;
     bathrooms: number;
     area_sqft: number;
-    images: string[];
+    images: string[]; // Image asset ids returned by `uploadImage`
     amenities: string[];
 }
 
+// Must match `MAX_CHUNK_BYTES` and `ALLOWED_CONTENT_TYPES` in the backend image store.
+const MAX_CHUNK_BYTES = 1_900_000;
+export const ALLOWED_IMAGE_TYPES = ['image/jpeg', 'image/png', 'image/webp', 'image/gif', 'image/avif'];
+
+// URL under which the backend serves an uploaded image.
+export const imageUrl = (id: string) => {
+    const canisterId = import.meta.env.VITE_CANISTER_ID_AANGAN_BACKEND;
+    return import.meta.env.VITE_DFX_NETWORK === 'local'
+        ? `http://${canisterId}.localhost:4943/images/${id}`
+        : `https://${canisterId}.icp0.io/images/${id}`;
+};
+
 export class PropertyService {
     constructor(private actor: AanganService) { }
 
+    // Uploads an image in chunks and returns the asset id to list in `images`.
+    async uploadImage(file: File): Promise<string> {
+        try {
+            if (!ALLOWED_IMAGE_TYPES.includes(file.type)) {
+                throw new Error(`Unsupported image type: ${file.type || 'unknown'}`);
+            }
+
+            const started = await this.actor.start_upload(file.type, BigInt(file.size));
+            if (!('Ok' in started)) {
+                throw new Error(started.Err);
+            }
+            const uploadId = started.Ok.id;
+
+            const content = new Uint8Array(await file.arrayBuffer());
+            for (let offset = 0, index = 0; offset < content.length; offset += MAX_CHUNK_BYTES, index++) {
+                const result = await this.actor.put_chunk(
+                    uploadId,
+                    index,
+                    content.slice(offset, offset + MAX_CHUNK_BYTES)
+                );
+                if (!('Ok' in result)) {
+                    throw new Error(result.Err);
+                }
+            }
+
+            const sha256 = new Uint8Array(await crypto.subtle.digest('SHA-256', content));
+            const committed = await this.actor.commit_upload(uploadId, [sha256]);
+            if ('Ok' in committed) {
+                return committed.Ok.id.toString();
+            } else {
+                throw new Error(committed.Err);
+            }
+        } catch (error) {
+            console.error('Error uploading image:', error);
+            throw error;
+        }
+    }
+
+    async deleteImage(id: string) {
+        try {
+            const result = await this.actor.delete_image(BigInt(id));
+            if ('Err' in result) {
+                throw new Error(result.Err);
+            }
+        } catch (error) {
+            console.error('Error deleting image:', error);
+            throw error;
+        }
+    }
+
     async addProperty(data: PropertyData) {
         try {
             let propertyTypeVariant: any;
@@ -55,7 +117,8 @@ export class PropertyService {
                 data.bathrooms,
                 data.area_sqft,
                 data.images,
-                data.amenities
+                data.amenities,
+                []
             );
 
             if ('Ok' in result) {
@@ -156,7 +219,7 @@ export const formatPropertyForDisplay = (property: any) => {
         bathrooms: property.bathrooms,
         area: property.area_sqft,
         propertyType: Object.keys(property.property_type)[0],
-        images: property.images,
+        images: property.images.map(imageUrl),
         amenities: property.amenities,
         isAvailable: property.is_available,
         owner: property.owner.toString(),
@@ -22,9 +22,51 @@ export class RentalService {
         }
     }
 
+    // Landlord only. Attaches the lease document (hashed, the text stays off-chain) and its
+    // clauses to an application; both parties have to sign again afterwards.
+    async setLeaseTerms(rentalId: number, document: string, clauses: string[]) {
+        try {
+            const digest = await crypto.subtle.digest('SHA-256', new TextEncoder().encode(document));
+            const result = await this.actor.set_lease_terms(BigInt(rentalId), {
+                document_hash: new Uint8Array(digest),
+                clauses,
+            });
+            if ('Ok' in result) {
+                return result.Ok;
+            } else {
+                throw new Error(result.Err);
+            }
+        } catch (error) {
+            console.error('Error setting lease terms:', error);
+            throw error;
+        }
+    }
+
+    // Signs the application's current terms as the landlord or the tenant.
+    async signLease(rentalId: number) {
+        try {
+            const hash = await this.actor.get_terms_hash(BigInt(rentalId));
+            if (!('Ok' in hash)) {
+                throw new Error(hash.Err);
+            }
+            const result = await this.actor.sign_lease(BigInt(rentalId), hash.Ok);
+            if ('Ok' in result) {
+                return result.Ok;
+            } else {
+                throw new Error(result.Err);
+            }
+        } catch (error) {
+            console.error('Error signing lease:', error);
+            throw error;
+        }
+    }
+
+    // Landlord only. Signs the current terms on the landlord's behalf and accepts the
+    // application, which fails until the tenant has signed the same terms.
     async confirmRental(rentalId: number) {
         try {
-            const result = await this.actor.confirm_rental(BigInt(rentalId));
+            await this.signLease(rentalId);
+            const result = await this.actor.accept_application(BigInt(rentalId));
             if ('Ok' in result) {
                 return result.Ok;
             } else {
@@ -0,0 +1,124 @@
+//! Lease attestations: statements by the Aangan backend canister that a lease is running,
+//! signed with a [canister signature](https://internetcomputer.org/docs/current/references/ic-interface-spec/#canister-signatures).
+//!
+//! The canister signs the candid encoding of a [`LeaseAttestation`]. Anyone holding the
+//! encoded attestation, its signature and the IC root public key can check it with
+//! [`verify_lease_attestation`] without calling the canister. The canister builds this crate
+//! without the `verify` feature.
+
+use candid::{CandidType, Deserialize, Principal};
+use ic_canister_sig_creation::CanisterSigPublicKey;
+use serde::Serialize;
+
+#[cfg(feature = "verify")]
+mod verify;
+
+#[cfg(feature = "verify")]
+pub use verify::{ic_root_public_key, verify_lease_attestation, VerifyError};
+
+/// Domain separator of lease attestation signatures, so they cannot be passed off as any
+/// other canister signature.
+pub const ATTESTATION_SIG_DOMAIN: &[u8] = b"aangan-lease-attestation";
+
+/// Seed of the canister signature key that signs lease attestations.
+pub const ATTESTATION_SEED: &[u8] = b"lease-attestation";
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum AttestedStatus {
+    Active,
+    /// Notice has been given; the lease ends at `end_date`.
+    Terminating,
+}
+
+/// A running lease as recorded by the issuing canister when the attestation was prepared.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LeaseAttestation {
+    /// The Aangan backend canister that signed the attestation.
+    pub issuer: Principal,
+    pub rental_id: u64,
+    pub property_id: u64,
+    pub property_address: String,
+    pub landlord: Principal,
+    pub tenant: Principal,
+    pub start_date: u64,
+    /// End of the occupied period, earlier than the agreed end date if notice was given.
+    pub end_date: u64,
+    pub rent_amount: u64,
+    pub status: AttestedStatus,
+    pub issued_at: u64,
+    /// The attestation is not to be relied on from this time on.
+    pub expires_at: u64,
+}
+
+impl LeaseAttestation {
+    /// Canonical encoding of the attestation; this is the message the canister signs.
+    pub fn encode(&self) -> Vec<u8> {
+        candid::encode_one(self).expect("Failed to encode lease attestation")
+    }
+
+    pub fn decode(message: &[u8]) -> Result<Self, String> {
+        candid::decode_one(message).map_err(|e| e.to_string())
+    }
+}
+
+/// DER-encoded public key of the attestation signatures issued by `issuer`.
+pub fn public_key_der(issuer: Principal) -> Vec<u8> {
+    CanisterSigPublicKey::new(issuer, ATTESTATION_SEED.to_vec()).to_der()
+}
+
+/// Bytes covered by the signature: the domain separator, prefixed by its length, followed
+/// by the encoded attestation.
+pub fn signing_input(message: &[u8]) -> Vec<u8> {
+    let mut input = Vec::with_capacity(1 + ATTESTATION_SIG_DOMAIN.len() + message.len());
+    input.push(ATTESTATION_SIG_DOMAIN.len() as u8);
+    input.extend_from_slice(ATTESTATION_SIG_DOMAIN);
+    input.extend_from_slice(message);
+    input
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(crate) fn attestation() -> LeaseAttestation {
+        LeaseAttestation {
+            issuer: Principal::from_slice(&[9]),
+            rental_id: 4,
+            property_id: 2,
+            property_address: "12 MG Road".to_string(),
+            landlord: Principal::from_slice(&[2]),
+            tenant: Principal::from_slice(&[3]),
+            start_date: 1_000,
+            end_date: 2_000,
+            rent_amount: 25_000_000,
+            status: AttestedStatus::Active,
+            issued_at: 1_500,
+            expires_at: 1_800,
+        }
+    }
+
+    #[test]
+    fn encoding_round_trips() {
+        let attestation = attestation();
+        assert_eq!(
+            LeaseAttestation::decode(&attestation.encode()),
+            Ok(attestation)
+        );
+    }
+
+    #[test]
+    fn signing_input_is_domain_separated() {
+        let input = signing_input(b"lease");
+        assert_eq!(input[0] as usize, ATTESTATION_SIG_DOMAIN.len());
+        assert!(input[1..].starts_with(ATTESTATION_SIG_DOMAIN));
+        assert!(input.ends_with(b"lease"));
+    }
+
+    #[test]
+    fn public_key_depends_on_issuer() {
+        assert_ne!(
+            public_key_der(Principal::from_slice(&[9])),
+            public_key_der(Principal::from_slice(&[8]))
+        );
+    }
+}
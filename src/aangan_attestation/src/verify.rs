@@ -0,0 +1,102 @@
+//! Off-chain verification of lease attestations against the IC root key.
+
+use crate::{public_key_der, signing_input, LeaseAttestation};
+use candid::Principal;
+use ic_canister_sig_creation::{extract_raw_root_pk_from_der, IC_ROOT_PK_DER};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum VerifyError {
+    /// The signature does not certify the message for the issuer's attestation key.
+    InvalidSignature(String),
+    /// The message is not an encoded [`LeaseAttestation`].
+    Malformed(String),
+    /// The attestation names a different issuer than the canister that signed it.
+    WrongIssuer {
+        expected: Principal,
+        actual: Principal,
+    },
+    Expired {
+        expires_at: u64,
+    },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::InvalidSignature(reason) => write!(f, "Invalid signature: {}", reason),
+            VerifyError::Malformed(reason) => write!(f, "Malformed attestation: {}", reason),
+            VerifyError::WrongIssuer { expected, actual } => {
+                write!(f, "Attestation issued by {}, expected {}", actual, expected)
+            }
+            VerifyError::Expired { expires_at } => {
+                write!(f, "Attestation expired at {}", expires_at)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Raw public key of the IC mainnet root subnet.
+pub fn ic_root_public_key() -> Vec<u8> {
+    extract_raw_root_pk_from_der(IC_ROOT_PK_DER).expect("IC root key constant is valid")
+}
+
+/// Checks that `signature_cbor` is a canister signature by `issuer` on `message`, certified
+/// under `ic_root_public_key_raw`, and that the attestation it carries has not expired at
+/// `now` (nanoseconds since the epoch). Returns the attested lease.
+///
+/// `issuer` must be the Aangan backend canister the verifier trusts; the signature alone
+/// only shows that *some* canister signed the message.
+pub fn verify_lease_attestation(
+    message: &[u8],
+    signature_cbor: &[u8],
+    issuer: Principal,
+    ic_root_public_key_raw: &[u8],
+    now: u64,
+) -> Result<LeaseAttestation, VerifyError> {
+    ic_signature_verification::verify_canister_sig(
+        &signing_input(message),
+        signature_cbor,
+        &public_key_der(issuer),
+        ic_root_public_key_raw,
+    )
+    .map_err(VerifyError::InvalidSignature)?;
+
+    let attestation = LeaseAttestation::decode(message).map_err(VerifyError::Malformed)?;
+
+    if attestation.issuer != issuer {
+        return Err(VerifyError::WrongIssuer {
+            expected: issuer,
+            actual: attestation.issuer,
+        });
+    }
+
+    if now >= attestation.expires_at {
+        return Err(VerifyError::Expired {
+            expires_at: attestation.expires_at,
+        });
+    }
+
+    Ok(attestation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::attestation;
+
+    #[test]
+    fn rejects_unsigned_attestation() {
+        let attestation = attestation();
+        let result = verify_lease_attestation(
+            &attestation.encode(),
+            b"not a signature",
+            attestation.issuer,
+            &ic_root_public_key(),
+            attestation.issued_at,
+        );
+        assert!(matches!(result, Err(VerifyError::InvalidSignature(_))));
+    }
+}